@@ -0,0 +1,592 @@
+//! The source of the counters read by a `Spork` instance.
+//!
+//! By default `Spork::new` reads from the native backend for the current platform. Use `Spork::with_backend` to read
+//! from a different `StatsBackend`, such as the `MockBackend` which returns scripted values against a `FakeClock` so
+//! code that consumes `Stats` can be tested without burning real CPU time or sleeping.
+//!
+//! ```
+//! use spork::backend::{MockBackend, RawStats};
+//! use spork::{Spork, StatType};
+//! use std::time::Duration;
+//!
+//! let backend = MockBackend::new();
+//! let spork = Spork::with_backend(backend.clone()).unwrap();
+//!
+//! backend.clock().advance(Duration::from_secs(1));
+//! backend.add_cpu_time(&StatType::Process, 0.5);
+//! backend.set_memory(&StatType::Process, 1024);
+//!
+//! let stats = spork.stats(StatType::Process).unwrap();
+//! assert_eq!(stats.cpu, Some(50.0));
+//! assert_eq!(stats.memory, 1024);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use super::*;
+use topology::{CpuTopology, LogicalCpu};
+use utils::lock;
+
+#[cfg(target_os = "linux")]
+pub use crate::posix::PosixBackend as NativeBackend;
+
+#[cfg(target_os = "macos")]
+pub use crate::darwin::DarwinBackend as NativeBackend;
+
+#[cfg(windows)]
+pub use crate::windows::WindowsBackend as NativeBackend;
+
+#[cfg(all(feature = "compile_unimplemented", not(any(unix, windows, target_os = "macos"))))]
+pub use self::UnimplementedBackend as NativeBackend;
+
+/// The cumulative counters read by a backend for a `StatType`, from which `Spork` calculates a `Stats` instance.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawStats {
+    /// Total CPU time, in seconds.
+    pub cpu_time: f64,
+    /// Working set size, in bytes.
+    pub memory: u64,
+    /// Cumulative resource counters.
+    pub extended: ExtendedStats,
+}
+
+/// The CPU clock of a thread registered with `StatsBackend::register_thread`, which can be read from any other thread.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ThreadClock {
+    /// The kernel thread ID.
+    pub tid: u32,
+    /// The backend's identifier for the thread's CPU clock, such as the `clockid_t` from `pthread_getcpuclockid`.
+    pub clock_id: i64,
+}
+
+/// The cumulative CPU times for the whole system, as read by a backend.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SystemCpuTimes {
+    /// The times across all cores.
+    pub total: CpuTimes,
+    /// The times for each online logical core.
+    pub cores: Vec<CpuTimes>,
+}
+
+/// A source of CPU, memory and timing information.
+///
+/// Only `stats` is required. The remaining functions default to the system clocks and CPU information, and to
+/// `Unimplemented` errors for the optional metrics.
+pub trait StatsBackend: Debug + Send + Sync {
+    /// Read the cumulative counters for `kind`. `StatType::Thread` is read for the calling thread.
+    fn stats(&self, kind: &StatType) -> Result<RawStats, SporkError>;
+
+    /// Read a breakdown of the current memory usage of the process.
+    fn memory_stats(&self) -> Result<MemoryStats, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Memory breakdown is only implemented on Linux.",
+        ))
+    }
+
+    /// Read the current resident set size of the process, in bytes.
+    fn rss(&self) -> Result<u64, SporkError> {
+        self.memory_stats().map(|memory| memory.rss)
+    }
+
+    /// Read the CPU times of every thread in the process. Only the `tid`, `name`, `state`, `user_time` and
+    /// `system_time` fields are used.
+    fn thread_times(&self) -> Result<Vec<ThreadStats>, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Thread snapshots are only implemented on Linux.",
+        ))
+    }
+
+    /// Read every process on the system. Only the `pid`, `ppid`, `name`, `cpu_time` and `rss` fields are used.
+    fn processes(&self) -> Result<Vec<TreeProcess>, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Process trees are only implemented on Linux.",
+        ))
+    }
+
+    /// Capture the CPU clock of the calling thread so that it can be read from other threads.
+    fn register_thread(&self) -> Result<ThreadClock, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Thread handles are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the cumulative counters of a registered thread, or a `ProcessNotFound` error if the thread has exited.
+    fn thread_clock_stats(&self, _clock: &ThreadClock) -> Result<RawStats, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Thread handles are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the cumulative system-wide CPU times.
+    fn system_cpu_times(&self) -> Result<SystemCpuTimes, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "System CPU stats are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the I/O counters of the process `pid`, or of the current process if `None`. Only the counter fields are
+    /// used.
+    fn io_stats(&self, _pid: Option<u32>) -> Result<IoStats, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "I/O stats are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the memory breakdown of the process `pid`, or of the current process if `None`.
+    fn memory_breakdown(&self, _pid: Option<u32>) -> Result<MemoryBreakdown, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Memory breakdowns are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the memory mappings of the process `pid`, or of the current process if `None`.
+    fn memory_maps(&self, _pid: Option<u32>) -> Result<Vec<MemoryMapping>, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Memory maps are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the layout of the system's logical CPUs.
+    fn cpu_topology(&self) -> Result<CpuTopology, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "CPU topology is only implemented on Linux.",
+        ))
+    }
+
+    /// Read the limits and usage of the process' cgroup.
+    fn cgroup_stats(&self) -> Result<CgroupStats, SporkError> {
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Cgroup limits are only implemented on Linux.",
+        ))
+    }
+
+    /// Read the monotonic clock, relative to an arbitrary origin.
+    fn now(&self) -> Duration {
+        utils::monotonic_now()
+    }
+
+    /// Read the wall clock, in milliseconds since epoch.
+    fn now_ms(&self) -> i64 {
+        utils::now_ms()
+    }
+
+    /// Read the CPU clock speed, in Hz.
+    fn cpu_speed(&self) -> Result<u64, SporkError> {
+        utils::get_cpu_speed()
+    }
+
+    /// Read the number of CPU cores.
+    fn num_cores(&self) -> usize {
+        utils::get_num_cores()
+    }
+}
+
+/// A backend for unsupported platforms that always returns `Unimplemented` errors.
+#[cfg(all(feature = "compile_unimplemented", not(any(unix, windows, target_os = "macos"))))]
+#[derive(Clone, Debug, Default)]
+pub struct UnimplementedBackend;
+
+#[cfg(all(feature = "compile_unimplemented", not(any(unix, windows, target_os = "macos"))))]
+impl StatsBackend for UnimplementedBackend {
+    fn stats(&self, _kind: &StatType) -> Result<RawStats, SporkError> {
+        Err(SporkError::unimplemented())
+    }
+}
+
+/// A manually advanced clock. Clones share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct FakeClock {
+    // the monotonic time and the wall clock time at which it was zero, in ms since epoch
+    inner: Arc<Mutex<(Duration, i64)>>,
+}
+
+impl FakeClock {
+    /// Create a new clock at monotonic time zero, with the wall clock at the epoch.
+    pub fn new() -> FakeClock {
+        FakeClock::default()
+    }
+
+    /// Create a new clock at monotonic time zero, with the wall clock at `origin_ms` milliseconds since epoch.
+    pub fn with_origin(origin_ms: i64) -> FakeClock {
+        FakeClock {
+            inner: Arc::new(Mutex::new((Duration::ZERO, origin_ms))),
+        }
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        lock(&self.inner).0 += duration;
+    }
+
+    /// Set the monotonic time.
+    pub fn set(&self, now: Duration) {
+        lock(&self.inner).0 = now;
+    }
+
+    /// Read the monotonic time.
+    pub fn now(&self) -> Duration {
+        lock(&self.inner).0
+    }
+
+    /// Read the wall clock time, in milliseconds since epoch.
+    pub fn now_ms(&self) -> i64 {
+        let (now, origin) = *lock(&self.inner);
+        origin + now.as_millis() as i64
+    }
+}
+
+#[derive(Debug)]
+struct MockState {
+    stats: HashMap<StatType, Result<RawStats, SporkError>>,
+    memory: Result<MemoryStats, SporkError>,
+    threads: Result<Vec<ThreadStats>, SporkError>,
+    processes: Result<Vec<TreeProcess>, SporkError>,
+    thread_clocks: HashMap<u32, Result<RawStats, SporkError>>,
+    next_tid: u32,
+    system_cpu: Result<SystemCpuTimes, SporkError>,
+    cgroup: Result<CgroupStats, SporkError>,
+    topology: Option<Result<CpuTopology, SporkError>>,
+    io: HashMap<Option<u32>, Result<IoStats, SporkError>>,
+    memory_breakdown: HashMap<Option<u32>, Result<MemoryBreakdown, SporkError>>,
+    memory_maps: HashMap<Option<u32>, Result<Vec<MemoryMapping>, SporkError>>,
+    cpu_speed: u64,
+    cores: usize,
+}
+
+/// A backend that returns scripted values. Clones share the same state, so a clone can be handed to
+/// `Spork::with_backend` while the original is used to update the values as a test runs.
+///
+/// Every `StatType` starts with zeroed counters, the clock starts at zero, and the backend reports a single 1 GHz core
+/// with no cgroup unless configured otherwise. Note that `StatType::Thread` and `StatType::Children` are scripted once for every
+/// thread, although `Spork` still keeps their history per calling thread.
+#[derive(Clone, Debug)]
+pub struct MockBackend {
+    clock: FakeClock,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        MockBackend {
+            clock: FakeClock::new(),
+            state: Arc::new(Mutex::new(MockState {
+                stats: HashMap::new(),
+                memory: Ok(MemoryStats::default()),
+                threads: Ok(Vec::new()),
+                processes: Ok(Vec::new()),
+                thread_clocks: HashMap::new(),
+                next_tid: 1,
+                system_cpu: Ok(SystemCpuTimes::default()),
+                cgroup: Err(SporkError::new_borrowed(
+                    SporkErrorKind::Unimplemented,
+                    "No cgroup configured.",
+                )),
+                topology: None,
+                io: HashMap::new(),
+                memory_breakdown: HashMap::new(),
+                memory_maps: HashMap::new(),
+                cpu_speed: 1_000_000_000,
+                cores: 1,
+            })),
+        }
+    }
+}
+
+impl MockBackend {
+    /// Create a new mock backend with its own `FakeClock`.
+    pub fn new() -> MockBackend {
+        MockBackend::default()
+    }
+
+    /// Create a new mock backend that reads time from `clock`.
+    pub fn with_clock(clock: FakeClock) -> MockBackend {
+        MockBackend {
+            clock,
+            ..MockBackend::default()
+        }
+    }
+
+    /// Set the number of CPU cores reported by the backend. This must be set before the backend is passed to
+    /// `Spork::with_backend`.
+    pub fn with_cores(self, cores: usize) -> MockBackend {
+        lock(&self.state).cores = cores;
+        self
+    }
+
+    /// Set the CPU clock speed reported by the backend, in Hz. This must be set before the backend is passed to
+    /// `Spork::with_backend`.
+    pub fn with_cpu_speed(self, hz: u64) -> MockBackend {
+        lock(&self.state).cpu_speed = hz;
+        self
+    }
+
+    /// Read the clock used by the backend.
+    pub fn clock(&self) -> &FakeClock {
+        &self.clock
+    }
+
+    /// Replace the counters returned for `kind`.
+    pub fn set_stats(&self, kind: &StatType, stats: RawStats) {
+        lock(&self.state).stats.insert(kind.clone(), Ok(stats));
+    }
+
+    /// Return `error` whenever `kind` is read, until the counters are set again.
+    pub fn set_error(&self, kind: &StatType, error: SporkError) {
+        lock(&self.state).stats.insert(kind.clone(), Err(error));
+    }
+
+    /// Set the total CPU time for `kind`, in seconds.
+    pub fn set_cpu_time(&self, kind: &StatType, cpu_time: f64) {
+        self.update(kind, |stats| stats.cpu_time = cpu_time);
+    }
+
+    /// Add `cpu_time` seconds to the total CPU time for `kind`.
+    pub fn add_cpu_time(&self, kind: &StatType, cpu_time: f64) {
+        self.update(kind, |stats| stats.cpu_time += cpu_time);
+    }
+
+    /// Set the memory usage for `kind`, in bytes.
+    pub fn set_memory(&self, kind: &StatType, memory: u64) {
+        self.update(kind, |stats| stats.memory = memory);
+    }
+
+    /// Set the cumulative resource counters for `kind`.
+    pub fn set_extended(&self, kind: &StatType, extended: ExtendedStats) {
+        self.update(kind, |stats| stats.extended = extended);
+    }
+
+    /// Set the result of `memory_stats`.
+    pub fn set_memory_stats(&self, memory: Result<MemoryStats, SporkError>) {
+        lock(&self.state).memory = memory;
+    }
+
+    /// Set the result of `thread_times`.
+    pub fn set_threads(&self, threads: Result<Vec<ThreadStats>, SporkError>) {
+        lock(&self.state).threads = threads;
+    }
+
+    /// Set the result of `processes`. No processes are reported unless configured.
+    pub fn set_processes(&self, processes: Result<Vec<TreeProcess>, SporkError>) {
+        lock(&self.state).processes = processes;
+    }
+
+    /// Set the result of `thread_clock_stats` for the registered thread `tid`. Registered threads are given increasing
+    /// tids starting at 1, and start with zeroed counters.
+    pub fn set_thread_clock_stats(&self, tid: u32, stats: Result<RawStats, SporkError>) {
+        lock(&self.state).thread_clocks.insert(tid, stats);
+    }
+
+    /// Set the result of `system_cpu_times`.
+    pub fn set_system_cpu_times(&self, times: Result<SystemCpuTimes, SporkError>) {
+        lock(&self.state).system_cpu = times;
+    }
+
+    /// Set the result of `io_stats` for the process `pid`, or for the current process if `None`. Processes start with
+    /// zeroed counters.
+    pub fn set_io_stats(&self, pid: Option<u32>, io: Result<IoStats, SporkError>) {
+        lock(&self.state).io.insert(pid, io);
+    }
+
+    /// Set the result of `memory_breakdown` for the process `pid`, or for the current process if `None`. Processes start
+    /// with a zeroed breakdown.
+    pub fn set_memory_breakdown(&self, pid: Option<u32>, memory: Result<MemoryBreakdown, SporkError>) {
+        lock(&self.state).memory_breakdown.insert(pid, memory);
+    }
+
+    /// Set the result of `memory_maps` for the process `pid`, or for the current process if `None`. Processes start
+    /// with no mappings.
+    pub fn set_memory_maps(&self, pid: Option<u32>, maps: Result<Vec<MemoryMapping>, SporkError>) {
+        lock(&self.state).memory_maps.insert(pid, maps);
+    }
+
+    /// Set the result of `cpu_topology`. Unless configured, every core reported by `num_cores` is its own physical
+    /// core in a single package.
+    pub fn set_cpu_topology(&self, topology: Result<CpuTopology, SporkError>) {
+        lock(&self.state).topology = Some(topology);
+    }
+
+    /// Set the result of `cgroup_stats`. The CPU limit is read when the backend is passed to `Spork::with_backend`, so
+    /// it must be set before then to affect `Spork::effective_cores`.
+    pub fn set_cgroup_stats(&self, cgroup: Result<CgroupStats, SporkError>) {
+        lock(&self.state).cgroup = cgroup;
+    }
+
+    fn update<F>(&self, kind: &StatType, func: F)
+    where
+        F: FnOnce(&mut RawStats),
+    {
+        let mut state = lock(&self.state);
+        let entry = state
+            .stats
+            .entry(kind.clone())
+            .or_insert_with(|| Ok(RawStats::default()));
+
+        // updating a failing stat type clears the error
+        if entry.is_err() {
+            *entry = Ok(RawStats::default());
+        }
+        if let Ok(stats) = entry {
+            func(stats);
+        }
+    }
+}
+
+impl StatsBackend for MockBackend {
+    fn stats(&self, kind: &StatType) -> Result<RawStats, SporkError> {
+        match lock(&self.state).stats.get(kind) {
+            Some(stats) => stats.clone(),
+            None => Ok(RawStats::default()),
+        }
+    }
+
+    fn memory_stats(&self) -> Result<MemoryStats, SporkError> {
+        lock(&self.state).memory.clone()
+    }
+
+    fn thread_times(&self) -> Result<Vec<ThreadStats>, SporkError> {
+        lock(&self.state).threads.clone()
+    }
+
+    fn processes(&self) -> Result<Vec<TreeProcess>, SporkError> {
+        lock(&self.state).processes.clone()
+    }
+
+    fn register_thread(&self) -> Result<ThreadClock, SporkError> {
+        let mut state = lock(&self.state);
+        let tid = state.next_tid;
+        state.next_tid += 1;
+
+        Ok(ThreadClock {
+            tid,
+            clock_id: tid as i64,
+        })
+    }
+
+    fn thread_clock_stats(&self, clock: &ThreadClock) -> Result<RawStats, SporkError> {
+        match lock(&self.state).thread_clocks.get(&clock.tid) {
+            Some(stats) => stats.clone(),
+            None => Ok(RawStats::default()),
+        }
+    }
+
+    fn system_cpu_times(&self) -> Result<SystemCpuTimes, SporkError> {
+        lock(&self.state).system_cpu.clone()
+    }
+
+    fn io_stats(&self, pid: Option<u32>) -> Result<IoStats, SporkError> {
+        match lock(&self.state).io.get(&pid) {
+            Some(io) => io.clone(),
+            None => Ok(IoStats::new_empty(pid)),
+        }
+    }
+
+    fn memory_breakdown(&self, pid: Option<u32>) -> Result<MemoryBreakdown, SporkError> {
+        match lock(&self.state).memory_breakdown.get(&pid) {
+            Some(memory) => memory.clone(),
+            None => Ok(MemoryBreakdown::default()),
+        }
+    }
+
+    fn memory_maps(&self, pid: Option<u32>) -> Result<Vec<MemoryMapping>, SporkError> {
+        match lock(&self.state).memory_maps.get(&pid) {
+            Some(maps) => maps.clone(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn cpu_topology(&self) -> Result<CpuTopology, SporkError> {
+        let state = lock(&self.state);
+        match state.topology {
+            Some(ref topology) => topology.clone(),
+            None => Ok(CpuTopology {
+                cpus: (0..state.cores).map(LogicalCpu::new).collect(),
+            }),
+        }
+    }
+
+    fn cgroup_stats(&self) -> Result<CgroupStats, SporkError> {
+        lock(&self.state).cgroup.clone()
+    }
+
+    fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    fn now_ms(&self) -> i64 {
+        self.clock.now_ms()
+    }
+
+    fn cpu_speed(&self) -> Result<u64, SporkError> {
+        Ok(lock(&self.state).cpu_speed)
+    }
+
+    fn num_cores(&self) -> usize {
+        lock(&self.state).cores
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_share_fake_clock_between_clones() {
+        let clock = FakeClock::with_origin(1_000);
+        let copy = clock.clone();
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(copy.now(), Duration::from_millis(250));
+        assert_eq!(copy.now_ms(), 1_250);
+
+        copy.set(Duration::from_secs(2));
+        assert_eq!(clock.now(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn should_default_to_zeroed_stats() {
+        let backend = MockBackend::new();
+        assert_eq!(backend.stats(&StatType::Process).unwrap(), RawStats::default());
+        assert_eq!(backend.num_cores(), 1);
+    }
+
+    #[test]
+    fn should_script_mock_stats() {
+        let backend = MockBackend::new().with_cores(4);
+        let kind = StatType::Pid(42);
+
+        backend.add_cpu_time(&kind, 1.5);
+        backend.add_cpu_time(&kind, 1.0);
+        backend.set_memory(&kind, 2048);
+
+        let stats = backend.stats(&kind).unwrap();
+        assert_eq!(stats.cpu_time, 2.5);
+        assert_eq!(stats.memory, 2048);
+        assert_eq!(backend.num_cores(), 4);
+        assert_eq!(backend.stats(&StatType::Process).unwrap(), RawStats::default());
+    }
+
+    #[test]
+    fn should_clear_mock_errors_on_update() {
+        let backend = MockBackend::new();
+        backend.set_error(&StatType::Process, SporkError::unimplemented());
+        assert!(backend.stats(&StatType::Process).is_err());
+
+        backend.set_cpu_time(&StatType::Process, 1.0);
+        assert_eq!(backend.stats(&StatType::Process).unwrap().cpu_time, 1.0);
+    }
+}
@@ -0,0 +1,478 @@
+//! Inspect the CPU, memory and I/O usage of a process from the command line.
+//!
+//! ```text
+//! spork stat <pid> [--json]
+//! spork watch <pid> [--interval 1s] [--count N] [--json]
+//! spork run [--json] -- <command> [args...]
+//! ```
+
+extern crate spork;
+
+use spork::command::{self, CommandStats};
+use spork::{ExtendedStats, IoStats, Spork, SporkError, StatType, Stats};
+
+use std::env;
+use std::fmt::Write;
+use std::process::{self, Command, ExitStatus};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "Usage:
+  spork stat <pid> [--json]                             Print a one-shot summary of a process.
+  spork watch <pid> [--interval 1s] [--count N] [--json] Stream stats for a process until it exits.
+  spork run [--json] -- <command> [args...]             Run a command and print its resource usage on exit.
+
+Intervals accept a ms, s or m suffix, and default to seconds.";
+
+/// The window over which `stat` measures CPU load.
+const STAT_WINDOW: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    Stat {
+        pid: u32,
+        json: bool,
+    },
+    Watch {
+        pid: u32,
+        interval: Duration,
+        count: Option<u64>,
+        json: bool,
+    },
+    Run {
+        command: Vec<String>,
+        json: bool,
+    },
+    Help,
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.0)
+    } else if let Some(m) = value.strip_suffix('m') {
+        (m, 60.0)
+    } else {
+        (value, 1.0)
+    };
+
+    match number.parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0_f64 => Ok(Duration::from_secs_f64(n * scale)),
+        _ => Err(format!("Invalid interval: {}", value)),
+    }
+}
+
+fn parse_pid(value: Option<String>) -> Result<u32, String> {
+    let value = value.ok_or_else(|| "Missing pid.".to_owned())?;
+    value.parse().map_err(|_| format!("Invalid pid: {}", value))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Action, String> {
+    let subcommand = match args.next() {
+        Some(s) => s,
+        None => return Ok(Action::Help),
+    };
+
+    let mut interval = Duration::from_secs(1);
+    let mut count = None;
+    let mut json = false;
+    let mut rest = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--interval" | "-i" => {
+                interval = parse_interval(&args.next().ok_or("Missing value for --interval.")?)?;
+            }
+            "--count" | "-n" => {
+                let value = args.next().ok_or("Missing value for --count.")?;
+                count = Some(value.parse().map_err(|_| format!("Invalid count: {}", value))?);
+            }
+            "--" => {
+                rest.extend(args.by_ref());
+                break;
+            }
+            "-h" | "--help" => return Ok(Action::Help),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => rest.push(arg),
+        }
+    }
+
+    let single_pid = |rest: Vec<String>| {
+        let mut rest = rest.into_iter();
+        let pid = parse_pid(rest.next())?;
+        match rest.next() {
+            Some(extra) => Err(format!("Unexpected argument: {}", extra)),
+            None => Ok(pid),
+        }
+    };
+
+    match subcommand.as_str() {
+        "stat" => Ok(Action::Stat {
+            pid: single_pid(rest)?,
+            json,
+        }),
+        "watch" => Ok(Action::Watch {
+            pid: single_pid(rest)?,
+            interval,
+            count,
+            json,
+        }),
+        "run" if rest.is_empty() => Err("Missing command to run.".into()),
+        "run" => Ok(Action::Run { command: rest, json }),
+        "help" | "-h" | "--help" => Ok(Action::Help),
+        _ => Err(format!("Unknown command: {}", subcommand)),
+    }
+}
+
+// ---------------------
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024_f64 && unit < UNITS.len() - 1 {
+        value /= 1024_f64;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_percent(cpu: Option<f64>) -> String {
+    match cpu {
+        Some(cpu) => format!("{:.1}%", cpu),
+        None => "-".into(),
+    }
+}
+
+/// A single line JSON object, written by hand so the binary doesn't depend on the `serde` feature. Field names
+/// match the `serde` format of the library types.
+#[derive(Default)]
+struct JsonLine {
+    out: String,
+}
+
+impl JsonLine {
+    fn key(&mut self, key: &str) -> &mut String {
+        self.out.push(if self.out.is_empty() { '{' } else { ',' });
+        let _ = write!(self.out, "\"{}\":", key);
+        &mut self.out
+    }
+
+    fn u64(&mut self, key: &str, value: u64) -> &mut Self {
+        let _ = write!(self.key(key), "{}", value);
+        self
+    }
+
+    fn f64(&mut self, key: &str, value: Option<f64>) -> &mut Self {
+        match value {
+            Some(v) if v.is_finite() => {
+                let _ = write!(self.key(key), "{}", v);
+            }
+            _ => self.key(key).push_str("null"),
+        };
+        self
+    }
+
+    fn i64(&mut self, key: &str, value: Option<i64>) -> &mut Self {
+        match value {
+            Some(v) => {
+                let _ = write!(self.key(key), "{}", v);
+            }
+            None => self.key(key).push_str("null"),
+        };
+        self
+    }
+
+    fn extended(&mut self, extended: &ExtendedStats) -> &mut Self {
+        self.u64("minor_faults", extended.minor_faults)
+            .u64("major_faults", extended.major_faults)
+            .u64("voluntary_ctx_switches", extended.voluntary_ctx_switches)
+            .u64("involuntary_ctx_switches", extended.involuntary_ctx_switches)
+    }
+
+    fn finish(&mut self) -> String {
+        if self.out.is_empty() {
+            self.out.push('{');
+        }
+        self.out.push('}');
+        std::mem::take(&mut self.out)
+    }
+}
+
+fn stats_json(pid: u32, stats: &Stats, io: Option<&IoStats>) -> String {
+    let mut line = JsonLine::default();
+    line.u64("pid", pid as u64)
+        .u64("polled_ms", stats.polled.max(0) as u64)
+        .u64("duration_ns", stats.duration.as_nanos() as u64)
+        .f64("cpu_time_secs", Some(stats.cpu_time))
+        .f64("cpu_percent", stats.cpu)
+        .u64("memory_bytes", stats.memory)
+        .extended(&stats.extended);
+
+    if let Some(io) = io {
+        line.u64("read_bytes", io.read_bytes)
+            .u64("write_bytes", io.write_bytes)
+            .f64("read_bytes_per_sec", io.rates.as_ref().map(|r| r.read_bytes))
+            .f64("write_bytes_per_sec", io.rates.as_ref().map(|r| r.write_bytes));
+    }
+    line.finish()
+}
+
+fn command_json(stats: &CommandStats) -> String {
+    let mut line = JsonLine::default();
+    line.u64("pid", stats.pid as u64)
+        .i64("exit_code", stats.status.code().map(i64::from))
+        .i64("signal", signal(&stats.status).map(i64::from))
+        .u64("wall_time_ns", stats.wall_time.as_nanos() as u64)
+        .f64("user_time_secs", Some(stats.user_time))
+        .f64("system_time_secs", Some(stats.system_time))
+        .f64("cpu_percent", stats.cpu)
+        .u64("max_rss_bytes", stats.max_rss)
+        .extended(&stats.extended)
+        .finish()
+}
+
+// ---------------------
+
+fn stat(pid: u32, json: bool) -> Result<(), SporkError> {
+    let spork = Spork::new()?;
+    let kind = StatType::Pid(pid);
+
+    // prime the history so the CPU load and I/O rates cover a short window rather than the spork's lifetime
+    spork.stats(kind.clone())?;
+    let _ = spork.io_stats(kind.clone());
+    thread::sleep(STAT_WINDOW);
+    let stats = spork.stats(kind.clone())?;
+    let io = spork.io_stats(kind).ok();
+
+    if json {
+        println!("{}", stats_json(pid, &stats, io.as_ref()));
+        return Ok(());
+    }
+
+    println!("PID:           {}", pid);
+    println!(
+        "CPU:           {} (over {:?})",
+        format_percent(stats.cpu),
+        stats.duration
+    );
+    println!("CPU time:      {:.3}s", stats.cpu_time);
+    println!("Peak memory:   {}", format_bytes(stats.memory as f64));
+    println!(
+        "Page faults:   {} minor, {} major",
+        stats.extended.minor_faults, stats.extended.major_faults
+    );
+    println!(
+        "Ctx switches:  {} voluntary, {} involuntary",
+        stats.extended.voluntary_ctx_switches, stats.extended.involuntary_ctx_switches
+    );
+    match io {
+        Some(io) => println!(
+            "Disk I/O:      {} read, {} written",
+            format_bytes(io.read_bytes as f64),
+            format_bytes(io.write_bytes as f64)
+        ),
+        None => println!("Disk I/O:      -"),
+    };
+    Ok(())
+}
+
+fn watch(pid: u32, interval: Duration, count: Option<u64>, json: bool) -> Result<(), SporkError> {
+    let spork = Spork::new()?;
+    let kind = StatType::Pid(pid);
+
+    spork.stats(kind.clone())?;
+    let _ = spork.io_stats(kind.clone());
+
+    if !json {
+        println!(
+            "{:>8} {:>10} {:>12} {:>12} {:>12} {:>12}",
+            "CPU", "CPU TIME", "PEAK MEM", "READ/S", "WRITE/S", "FAULTS/S"
+        );
+    }
+
+    let mut polls = 0;
+    while count.map(|c| polls < c).unwrap_or(true) {
+        thread::sleep(interval);
+        let stats = match spork.stats(kind.clone()) {
+            Ok(s) => s,
+            // the process exited
+            Err(_) if polls > 0 => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let io = spork.io_stats(kind.clone()).ok();
+        polls += 1;
+
+        if json {
+            println!("{}", stats_json(pid, &stats, io.as_ref()));
+            continue;
+        }
+
+        let rate = |value: Option<f64>| value.map(|v| format_bytes(v.round())).unwrap_or_else(|| "-".into());
+        let rates = io.as_ref().and_then(|io| io.rates.as_ref());
+        let faults = stats.extended_delta.minor_faults + stats.extended_delta.major_faults;
+        println!(
+            "{:>8} {:>9.2}s {:>12} {:>12} {:>12} {:>12.0}",
+            format_percent(stats.cpu),
+            stats.cpu_time,
+            format_bytes(stats.memory as f64),
+            rate(rates.map(|r| r.read_bytes)),
+            rate(rates.map(|r| r.write_bytes)),
+            faults as f64 / stats.duration.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+    Ok(())
+}
+
+fn run(args: &[String], json: bool) -> Result<i32, SporkError> {
+    let stats = command::run(Command::new(&args[0]).args(&args[1..]))?;
+
+    // like time(1), write the summary to stderr so it doesn't mix with the command's output
+    if json {
+        eprintln!("{}", command_json(&stats));
+    } else {
+        eprintln!("Exit status:   {}", stats.status);
+        eprintln!("Wall time:     {:.3}s", stats.wall_time.as_secs_f64());
+        eprintln!("User time:     {:.3}s", stats.user_time);
+        eprintln!("System time:   {:.3}s", stats.system_time);
+        eprintln!("CPU:           {}", format_percent(stats.cpu));
+        eprintln!("Peak memory:   {}", format_bytes(stats.max_rss as f64));
+        eprintln!(
+            "Page faults:   {} minor, {} major",
+            stats.extended.minor_faults, stats.extended.major_faults
+        );
+        eprintln!(
+            "Ctx switches:  {} voluntary, {} involuntary",
+            stats.extended.voluntary_ctx_switches, stats.extended.involuntary_ctx_switches
+        );
+    }
+
+    // exit with the command's status, or 128 + the signal like a shell
+    Ok(stats
+        .status
+        .code()
+        .unwrap_or_else(|| 128 + signal(&stats.status).unwrap_or(0)))
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_: &ExitStatus) -> Option<i32> {
+    None
+}
+
+fn main() {
+    let action = match parse_args(env::args().skip(1)) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("spork: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = match action {
+        Action::Stat { pid, json } => stat(pid, json).map(|_| 0),
+        Action::Watch {
+            pid,
+            interval,
+            count,
+            json,
+        } => watch(pid, interval, count, json).map(|_| 0),
+        Action::Run { command, json } => run(&command, json),
+        Action::Help => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+    };
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("spork: {}", e.details());
+            process::exit(1);
+        }
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn should_parse_intervals() {
+        assert_eq!(parse_interval("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_interval("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_interval("1m"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_interval("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("fast").is_err());
+    }
+
+    #[test]
+    fn should_parse_subcommands() {
+        assert_eq!(
+            parse_args(args(&["stat", "42", "--json"])),
+            Ok(Action::Stat { pid: 42, json: true })
+        );
+        assert_eq!(
+            parse_args(args(&["watch", "42", "--interval", "250ms", "-n", "3"])),
+            Ok(Action::Watch {
+                pid: 42,
+                interval: Duration::from_millis(250),
+                count: Some(3),
+                json: false,
+            })
+        );
+        assert_eq!(
+            parse_args(args(&["run", "--", "ls", "--json"])),
+            Ok(Action::Run {
+                command: vec!["ls".into(), "--json".into()],
+                json: false,
+            })
+        );
+        assert_eq!(parse_args(args(&[])), Ok(Action::Help));
+    }
+
+    #[test]
+    fn should_reject_invalid_args() {
+        assert!(parse_args(args(&["stat"])).is_err());
+        assert!(parse_args(args(&["stat", "abc"])).is_err());
+        assert!(parse_args(args(&["watch", "1", "--bogus"])).is_err());
+        assert!(parse_args(args(&["run"])).is_err());
+        assert!(parse_args(args(&["frobnicate"])).is_err());
+    }
+
+    #[test]
+    fn should_format_values() {
+        assert_eq!(format_bytes(512_f64), "512 B");
+        assert_eq!(format_bytes(1536_f64), "1.5 KiB");
+        assert_eq!(format_percent(None), "-");
+        assert_eq!(format_percent(Some(12.345)), "12.3%");
+        assert_eq!(
+            JsonLine::default()
+                .u64("a", 1)
+                .f64("b", None)
+                .i64("c", Some(-1))
+                .finish(),
+            "{\"a\":1,\"b\":null,\"c\":-1}"
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+// memory.limit_in_bytes reports a page-aligned i64::MAX when no limit is set
+const V1_UNLIMITED: u64 = 1 << 62;
+
+fn parse_error(file: &str, details: &str) -> SporkError {
+    SporkError::new(SporkErrorKind::Parse, format!("Invalid {} format: {}", file, details))
+}
+
+/// The fields of a `/proc/<pid>/mountinfo` line used to locate the cgroup hierarchies.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mount {
+    pub root: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    pub super_options: Vec<String>,
+}
+
+/// Parse a `mountinfo` file. The optional fields in the middle of each line are terminated by a lone `-`.
+pub fn parse_mountinfo(contents: &str) -> Vec<Mount> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (left, right) = line.split_once(" - ")?;
+            let left: Vec<&str> = left.split_whitespace().collect();
+            let right: Vec<&str> = right.split_whitespace().collect();
+            if left.len() < 5 || right.len() < 3 {
+                return None;
+            }
+
+            Some(Mount {
+                root: left[3].to_owned(),
+                mount_point: PathBuf::from(left[4]),
+                fs_type: right[0].to_owned(),
+                super_options: right[2].split(',').map(|o| o.to_owned()).collect(),
+            })
+        })
+        .collect()
+}
+
+/// A line of `/proc/<pid>/cgroup`. The v2 hierarchy has ID 0 and no controllers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProcCgroup {
+    pub hierarchy: u32,
+    pub controllers: Vec<String>,
+    pub path: String,
+}
+
+pub fn parse_proc_cgroup(contents: &str) -> Result<Vec<ProcCgroup>, SporkError> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut parts = line.splitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(id), Some(controllers), Some(path)) => Ok(ProcCgroup {
+                    hierarchy: id.parse::<u32>().map_err(|_| parse_error("cgroup", line))?,
+                    controllers: controllers
+                        .split(',')
+                        .filter(|c| !c.is_empty())
+                        .map(|c| c.to_owned())
+                        .collect(),
+                    path: path.to_owned(),
+                }),
+                _ => Err(parse_error("cgroup", line)),
+            }
+        })
+        .collect()
+}
+
+/// The directories holding the control files for the process' cgroup.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CgroupDirs {
+    V1 {
+        cpu: Option<PathBuf>,
+        memory: Option<PathBuf>,
+    },
+    V2 {
+        mount_point: PathBuf,
+        dir: PathBuf,
+    },
+}
+
+// map a cgroup path to a directory under the mount, which may itself be a sub-tree such as inside a container
+fn cgroup_dir(mount: &Mount, path: &str) -> PathBuf {
+    let relative = path.strip_prefix(mount.root.as_str()).unwrap_or("/");
+    mount.mount_point.join(relative.trim_start_matches('/'))
+}
+
+fn find_v1_dir(cgroups: &[ProcCgroup], mounts: &[Mount], controller: &str) -> Option<PathBuf> {
+    let cgroup = cgroups
+        .iter()
+        .find(|c| c.hierarchy != 0 && c.controllers.iter().any(|name| name == controller))?;
+    let mount = mounts
+        .iter()
+        .find(|m| m.fs_type == "cgroup" && m.super_options.iter().any(|o| o == controller))?;
+
+    Some(cgroup_dir(mount, &cgroup.path))
+}
+
+/// Find the cgroup directories for the process. Hybrid systems mount the v2 hierarchy alongside v1 controllers, in
+/// which case the limits are read from the v1 `cpu` and `memory` controllers.
+pub fn find_cgroup_dirs(cgroups: &[ProcCgroup], mounts: &[Mount]) -> Option<CgroupDirs> {
+    let cpu = find_v1_dir(cgroups, mounts, "cpu");
+    let memory = find_v1_dir(cgroups, mounts, "memory");
+    if cpu.is_some() || memory.is_some() {
+        return Some(CgroupDirs::V1 { cpu, memory });
+    }
+
+    let cgroup = cgroups.iter().find(|c| c.hierarchy == 0)?;
+    let mount = mounts.iter().find(|m| m.fs_type == "cgroup2")?;
+    Some(CgroupDirs::V2 {
+        mount_point: mount.mount_point.clone(),
+        dir: cgroup_dir(mount, &cgroup.path),
+    })
+}
+
+/// Read a control file, or `None` if the controller isn't enabled for the cgroup.
+fn read_optional(path: &Path) -> Result<Option<String>, SporkError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse_u64(file: &str, value: &str) -> Result<u64, SporkError> {
+    value.trim().parse::<u64>().map_err(|_| parse_error(file, value.trim()))
+}
+
+/// Parse a v2 `cpu.max` file, such as `150000 100000`, into a number of cores. `max` means there is no limit.
+pub fn parse_cpu_max(contents: &str) -> Result<Option<f64>, SporkError> {
+    let mut fields = contents.split_whitespace();
+    match (fields.next(), fields.next()) {
+        (Some("max"), _) => Ok(None),
+        (Some(quota), period) => {
+            let quota = parse_u64("cpu.max", quota)?;
+            let period = match period {
+                Some(period) => parse_u64("cpu.max", period)?,
+                None => 100_000,
+            };
+            if period == 0 {
+                return Err(parse_error("cpu.max", contents.trim()));
+            }
+
+            Ok(Some(quota as f64 / period as f64))
+        }
+        _ => Err(parse_error("cpu.max", contents.trim())),
+    }
+}
+
+/// Parse a v2 limit such as `memory.max`, where `max` means there is no limit.
+pub fn parse_limit(file: &str, contents: &str) -> Result<Option<u64>, SporkError> {
+    match contents.trim() {
+        "max" => Ok(None),
+        value => parse_u64(file, value).map(Some),
+    }
+}
+
+/// Parse a flat keyed file such as `cpu.stat`, skipping any values that aren't integers.
+pub fn parse_keyed(contents: &str) -> HashMap<&str, u64> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next().map(|v| v.parse::<u64>())) {
+                (Some(key), Some(Ok(value))) => Some((key, value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn min_limit<T: PartialOrd>(curr: Option<T>, next: Option<T>) -> Option<T> {
+    match (curr, next) {
+        (Some(curr), Some(next)) => Some(if next < curr { next } else { curr }),
+        (curr, next) => curr.or(next),
+    }
+}
+
+fn read_v2(mount_point: &Path, dir: &Path) -> Result<CgroupStats, SporkError> {
+    let mut cpu_limit = None;
+    let mut memory_limit = None;
+
+    // limits are enforced by every ancestor too, so the effective limit is the lowest one up to the root
+    for dir in dir.ancestors().take_while(|d| d.starts_with(mount_point)) {
+        if let Some(contents) = read_optional(&dir.join("cpu.max"))? {
+            cpu_limit = min_limit(cpu_limit, parse_cpu_max(&contents)?);
+        }
+        if let Some(contents) = read_optional(&dir.join("memory.max"))? {
+            memory_limit = min_limit(memory_limit, parse_limit("memory.max", &contents)?);
+        }
+    }
+
+    let memory_usage = match read_optional(&dir.join("memory.current"))? {
+        Some(contents) => parse_u64("memory.current", &contents)?,
+        None => 0,
+    };
+    let cpu_stat = read_optional(&dir.join("cpu.stat"))?.unwrap_or_default();
+    let cpu_stat = parse_keyed(&cpu_stat);
+
+    Ok(CgroupStats {
+        version: CgroupVersion::V2,
+        cpu_limit,
+        memory_limit,
+        memory_usage,
+        nr_periods: cpu_stat.get("nr_periods").cloned().unwrap_or(0),
+        nr_throttled: cpu_stat.get("nr_throttled").cloned().unwrap_or(0),
+        throttled_usec: cpu_stat.get("throttled_usec").cloned().unwrap_or(0),
+    })
+}
+
+fn read_v1(cpu: Option<&Path>, memory: Option<&Path>) -> Result<CgroupStats, SporkError> {
+    let mut stats = CgroupStats {
+        version: CgroupVersion::V1,
+        cpu_limit: None,
+        memory_limit: None,
+        memory_usage: 0,
+        nr_periods: 0,
+        nr_throttled: 0,
+        throttled_usec: 0,
+    };
+
+    if let Some(cpu) = cpu {
+        let quota = read_optional(&cpu.join("cpu.cfs_quota_us"))?;
+        let period = read_optional(&cpu.join("cpu.cfs_period_us"))?;
+        if let (Some(quota), Some(period)) = (quota, period) {
+            // a quota of -1 means there is no limit
+            if let Ok(quota) = quota.trim().parse::<u64>() {
+                let period = parse_u64("cpu.cfs_period_us", &period)?;
+                if period > 0 {
+                    stats.cpu_limit = Some(quota as f64 / period as f64);
+                }
+            }
+        }
+
+        let cpu_stat = read_optional(&cpu.join("cpu.stat"))?.unwrap_or_default();
+        let cpu_stat = parse_keyed(&cpu_stat);
+        stats.nr_periods = cpu_stat.get("nr_periods").cloned().unwrap_or(0);
+        stats.nr_throttled = cpu_stat.get("nr_throttled").cloned().unwrap_or(0);
+        // v1 reports the throttled time in nanoseconds
+        stats.throttled_usec = cpu_stat.get("throttled_time").cloned().unwrap_or(0) / 1000;
+    }
+
+    if let Some(memory) = memory {
+        if let Some(limit) = read_optional(&memory.join("memory.limit_in_bytes"))? {
+            let limit = parse_u64("memory.limit_in_bytes", &limit)?;
+            stats.memory_limit = if limit >= V1_UNLIMITED { None } else { Some(limit) };
+        }
+        if let Some(usage) = read_optional(&memory.join("memory.usage_in_bytes"))? {
+            stats.memory_usage = parse_u64("memory.usage_in_bytes", &usage)?;
+        }
+    }
+
+    Ok(stats)
+}
+
+pub fn read_cgroup_stats(dirs: &CgroupDirs) -> Result<CgroupStats, SporkError> {
+    match dirs {
+        CgroupDirs::V1 { cpu, memory } => read_v1(cpu.as_deref(), memory.as_deref()),
+        CgroupDirs::V2 { mount_point, dir } => read_v2(mount_point, dir),
+    }
+}
+
+/// Read the stats of the cgroup in `cgroups`, failing with `SporkErrorKind::Unimplemented` if none of its hierarchies
+/// are mounted, such as on kernels without cgroup support.
+pub fn find_cgroup_stats(cgroups: &[ProcCgroup], mounts: &[Mount]) -> Result<CgroupStats, SporkError> {
+    match find_cgroup_dirs(cgroups, mounts) {
+        Some(dirs) => read_cgroup_stats(&dirs),
+        None => Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "No mounted cgroup hierarchy found.",
+        )),
+    }
+}
+
+pub fn get_cgroup_stats() -> Result<CgroupStats, SporkError> {
+    let cgroups = parse_proc_cgroup(&procfs::read_proc_file(None, "cgroup")?)?;
+    let mounts = parse_mountinfo(&procfs::read_proc_file(None, "mountinfo")?);
+
+    find_cgroup_stats(&cgroups, &mounts)
+}
+
+// -----------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::TempDir;
+
+    const MOUNTINFO_V2: &str = "24 1 0:22 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw,nsdelegate\n25 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n";
+    const MOUNTINFO_V1: &str = "30 25 0:26 / /sys/fs/cgroup/unified rw,nosuid shared:10 - cgroup2 cgroup2 rw\n31 25 0:27 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid shared:13 - cgroup cgroup rw,cpu,cpuacct\n32 25 0:28 /docker/abc /sys/fs/cgroup/memory rw,nosuid shared:14 - cgroup cgroup rw,memory\n";
+
+    #[test]
+    fn should_parse_mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO_V1);
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mounts[1].mount_point, PathBuf::from("/sys/fs/cgroup/cpu,cpuacct"));
+        assert_eq!(mounts[1].fs_type, "cgroup");
+        assert_eq!(mounts[1].super_options, vec!["rw", "cpu", "cpuacct"]);
+        assert_eq!(mounts[2].root, "/docker/abc");
+    }
+
+    #[test]
+    fn should_parse_proc_cgroup() {
+        let cgroups = parse_proc_cgroup("12:cpu,cpuacct:/docker/abc\n0::/user.slice\n").unwrap();
+        assert_eq!(cgroups[0].hierarchy, 12);
+        assert_eq!(cgroups[0].controllers, vec!["cpu", "cpuacct"]);
+        assert_eq!(cgroups[0].path, "/docker/abc");
+        assert_eq!(cgroups[1].hierarchy, 0);
+        assert!(cgroups[1].controllers.is_empty());
+        assert!(parse_proc_cgroup("foo\n").is_err());
+    }
+
+    #[test]
+    fn should_find_v2_dirs() {
+        let cgroups = parse_proc_cgroup("0::/kubepods/pod1\n").unwrap();
+        let dirs = find_cgroup_dirs(&cgroups, &parse_mountinfo(MOUNTINFO_V2)).unwrap();
+
+        assert_eq!(
+            dirs,
+            CgroupDirs::V2 {
+                mount_point: PathBuf::from("/sys/fs/cgroup"),
+                dir: PathBuf::from("/sys/fs/cgroup/kubepods/pod1"),
+            }
+        );
+    }
+
+    #[test]
+    fn should_prefer_v1_controllers_on_hybrid_systems() {
+        let cgroups = parse_proc_cgroup("4:memory:/docker/abc\n3:cpu,cpuacct:/docker/abc\n0::/docker/abc\n").unwrap();
+        let dirs = find_cgroup_dirs(&cgroups, &parse_mountinfo(MOUNTINFO_V1)).unwrap();
+
+        assert_eq!(
+            dirs,
+            CgroupDirs::V1 {
+                cpu: Some(PathBuf::from("/sys/fs/cgroup/cpu,cpuacct/docker/abc")),
+                // the memory hierarchy is mounted from the container's own cgroup
+                memory: Some(PathBuf::from("/sys/fs/cgroup/memory")),
+            }
+        );
+    }
+
+    #[test]
+    fn should_err_unimplemented_without_mounted_hierarchy() {
+        let cgroups = parse_proc_cgroup("0::/user.slice\n").unwrap();
+        let mounts = parse_mountinfo("25 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n");
+
+        let err = find_cgroup_stats(&cgroups, &mounts).unwrap_err();
+        assert_eq!(err.kind(), &SporkErrorKind::Unimplemented);
+    }
+
+    #[test]
+    fn should_parse_cpu_max() {
+        assert_eq!(parse_cpu_max("max 100000\n").unwrap(), None);
+        assert_eq!(parse_cpu_max("150000 100000\n").unwrap(), Some(1.5));
+        assert_eq!(parse_cpu_max("50000\n").unwrap(), Some(0.5));
+        assert!(parse_cpu_max("").is_err());
+        assert!(parse_cpu_max("100 0").is_err());
+    }
+
+    #[test]
+    fn should_parse_limits_and_keyed_files() {
+        assert_eq!(parse_limit("memory.max", "max\n").unwrap(), None);
+        assert_eq!(parse_limit("memory.max", "1048576\n").unwrap(), Some(1048576));
+        assert!(parse_limit("memory.max", "foo").is_err());
+
+        let stat = parse_keyed("usage_usec 100\nnr_periods 10\nnr_throttled 2\nthrottled_usec 5000\n");
+        assert_eq!(stat.get("nr_throttled"), Some(&2));
+        assert_eq!(stat.get("throttled_usec"), Some(&5000));
+    }
+
+    #[test]
+    fn should_read_v2_stats_with_ancestor_limits() {
+        let root = TempDir::new("cgroup-v2");
+        root.write("pod/cpu.max", "max 100000\n");
+        root.write("pod/memory.max", "1073741824\n");
+        root.write("pod/app/cpu.max", "250000 100000\n");
+        root.write("pod/app/memory.max", "max\n");
+        root.write("pod/app/memory.current", "4096\n");
+        root.write(
+            "pod/app/cpu.stat",
+            "usage_usec 100\nnr_periods 10\nnr_throttled 3\nthrottled_usec 7000\n",
+        );
+
+        let stats = read_cgroup_stats(&CgroupDirs::V2 {
+            mount_point: root.0.clone(),
+            dir: root.0.join("pod/app"),
+        })
+        .unwrap();
+
+        assert_eq!(stats.version, CgroupVersion::V2);
+        assert_eq!(stats.cpu_limit, Some(2.5));
+        assert_eq!(stats.memory_limit, Some(1073741824));
+        assert_eq!(stats.memory_usage, 4096);
+        assert_eq!(stats.nr_periods, 10);
+        assert_eq!(stats.nr_throttled, 3);
+        assert_eq!(stats.throttled_usec, 7000);
+    }
+
+    #[test]
+    fn should_read_v1_stats() {
+        let root = TempDir::new("cgroup-v1");
+        root.write("cpu/cpu.cfs_quota_us", "50000\n");
+        root.write("cpu/cpu.cfs_period_us", "100000\n");
+        root.write(
+            "cpu/cpu.stat",
+            "nr_periods 20\nnr_throttled 4\nthrottled_time 9000000\n",
+        );
+        root.write("memory/memory.limit_in_bytes", "9223372036854771712\n");
+        root.write("memory/memory.usage_in_bytes", "8192\n");
+
+        let stats = read_cgroup_stats(&CgroupDirs::V1 {
+            cpu: Some(root.0.join("cpu")),
+            memory: Some(root.0.join("memory")),
+        })
+        .unwrap();
+
+        assert_eq!(stats.version, CgroupVersion::V1);
+        assert_eq!(stats.cpu_limit, Some(0.5));
+        assert_eq!(stats.memory_limit, None);
+        assert_eq!(stats.memory_usage, 8192);
+        assert_eq!(stats.nr_periods, 20);
+        assert_eq!(stats.nr_throttled, 4);
+        assert_eq!(stats.throttled_usec, 9000);
+    }
+
+    #[test]
+    fn should_read_unlimited_v1_cpu() {
+        let root = TempDir::new("cgroup-v1-unlimited");
+        root.write("cpu/cpu.cfs_quota_us", "-1\n");
+        root.write("cpu/cpu.cfs_period_us", "100000\n");
+
+        let stats = read_cgroup_stats(&CgroupDirs::V1 {
+            cpu: Some(root.0.join("cpu")),
+            memory: None,
+        })
+        .unwrap();
+        assert_eq!(stats.cpu_limit, None);
+        assert_eq!(stats.memory_usage, 0);
+    }
+}
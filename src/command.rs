@@ -0,0 +1,249 @@
+//! Spawn child processes and measure the resources used by each one, like `/usr/bin/time`.
+//!
+//! `StatType::Children` reports the combined usage of every child the process has reaped. The functions here instead
+//! reap a single child with [wait4](http://man7.org/linux/man-pages/man2/wait4.2.html), which returns the rusage of
+//! only that child and its own descendants.
+//!
+//! ```
+//! use spork::command;
+//! use std::process::Command;
+//! use std::time::Duration;
+//!
+//! let stats = command::run(Command::new("ls").arg("-l")).unwrap();
+//! println!("Exited with {} after {:?}, CPU: {}s, Max RSS: {} bytes",
+//!   stats.status, stats.wall_time, stats.user_time + stats.system_time, stats.max_rss);
+//!
+//! // sample the live child through /proc/<pid> every 100ms
+//! let mut child = command::spawn(&mut Command::new("sleep").arg("1")).unwrap();
+//! child.sample(Duration::from_millis(100), |stats| println!("Live stats: {:?}", stats)).unwrap();
+//! let stats = child.wait().unwrap();
+//! println!("Took {} samples", stats.samples.len());
+//! ```
+
+use std::process::{Child, Command, ExitStatus};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::*;
+use utils::{lock, Ticker};
+
+/// The resources used by a single child process, as returned by `run` and `MeasuredChild::wait`.
+#[derive(Clone, Debug)]
+pub struct CommandStats {
+    /// The child's process ID.
+    pub pid: u32,
+    /// The child's exit status.
+    pub status: ExitStatus,
+    /// Elapsed monotonic time between spawning the child and its exit.
+    pub wall_time: Duration,
+    /// CPU time spent in user mode, in seconds.
+    pub user_time: f64,
+    /// CPU time spent in kernel mode, in seconds.
+    pub system_time: f64,
+    /// Average CPU load (percentage) over `wall_time`, or `None` if the child exited within `MIN_CPU_INTERVAL`.
+    pub cpu: Option<f64>,
+    /// Peak resident set size, in bytes.
+    pub max_rss: u64,
+    /// Page fault, context switch and other counters.
+    pub extended: ExtendedStats,
+    /// The stats sampled while the child was running, if enabled with `MeasuredChild::sample`.
+    pub samples: Vec<Stats>,
+}
+
+struct LiveSampler {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    handle: JoinHandle<Vec<Stats>>,
+}
+
+impl LiveSampler {
+    fn stop(self) -> Vec<Stats> {
+        {
+            let (stopped, signal) = &*self.stopped;
+            *lock(stopped) = true;
+            signal.notify_all();
+        }
+
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// A running child process spawned with `spawn`.
+pub struct MeasuredChild {
+    child: Child,
+    started: Instant,
+    sampler: Option<LiveSampler>,
+}
+
+impl MeasuredChild {
+    /// Read the child's process ID.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Access the underlying `Child`, such as to write to its stdin. The child must be reaped with `wait` rather than
+    /// with `Child::wait`, otherwise its resource usage is lost.
+    pub fn child(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Poll `Spork::stats` with `StatType::Pid` for the child every `interval`, rounded up to `MIN_POLL_INTERVAL`, from a
+    /// background thread until it exits, calling `callback` with every sample. The samples are also returned in
+    /// `CommandStats::samples`. Calling this again replaces the previous sampler.
+    ///
+    /// This is currently only implemented on Linux.
+    pub fn sample<F>(&mut self, interval: Duration, callback: F) -> Result<(), SporkError>
+    where
+        F: Fn(&Stats) + Send + 'static,
+    {
+        if !cfg!(target_os = "linux") {
+            return Err(SporkError::new_borrowed(
+                SporkErrorKind::Unimplemented,
+                "Live command sampling is only implemented on Linux.",
+            ));
+        }
+
+        let previous = self.sampler.take().map(LiveSampler::stop).unwrap_or_default();
+        let spork = Spork::new()?;
+        let pid = self.id();
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stopped = stopped.clone();
+
+        let handle = thread::Builder::new().name("spork-command".into()).spawn(move || {
+            let mut samples = previous;
+            let (stopped, signal) = &*thread_stopped;
+            let mut ticker = Ticker::new(interval);
+
+            loop {
+                if *ticker.wait(signal, lock(stopped), |stopped| *stopped) {
+                    return samples;
+                }
+
+                // the child is not reaped until the sampler stops, so the pid can't be reused in the meantime
+                match spork.stats(StatType::Pid(pid)) {
+                    Ok(stats) => {
+                        callback(&stats);
+                        samples.push(stats);
+                    }
+                    Err(_) => return samples,
+                };
+                ticker.advance();
+            }
+        })?;
+
+        self.sampler = Some(LiveSampler { stopped, handle });
+        Ok(())
+    }
+
+    /// Wait for the child to exit and reap it, returning its exit status and resource usage. As with `Child::wait`, the
+    /// child's stdin is closed before waiting.
+    ///
+    /// This is currently only implemented on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn wait(mut self) -> Result<CommandStats, SporkError> {
+        use std::os::unix::process::ExitStatusExt;
+
+        drop(self.child.stdin.take());
+        let pid = self.id();
+
+        // wait for the exit without reaping, so any sampler never reads a reused pid
+        wait_for_exit(pid)?;
+        let wall_time = self.started.elapsed();
+        let samples = self.sampler.take().map(LiveSampler::stop).unwrap_or_default();
+
+        let (status, usage) = reap(pid)?;
+        let user_time = timeval_to_secs(&usage.ru_utime);
+        let system_time = timeval_to_secs(&usage.ru_stime);
+
+        Ok(CommandStats {
+            pid,
+            status: ExitStatus::from_raw(status),
+            wall_time,
+            user_time,
+            system_time,
+            cpu: utils::cpu_percent(user_time + system_time, wall_time),
+            max_rss: (usage.ru_maxrss as u64) * 1024,
+            extended: posix::get_extended_stats(&usage),
+            samples,
+        })
+    }
+
+    /// Wait for the child to exit and reap it, returning its exit status and resource usage.
+    ///
+    /// This is currently only implemented on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(mut self) -> Result<CommandStats, SporkError> {
+        let _ = self.child.wait();
+        Err(SporkError::new_borrowed(
+            SporkErrorKind::Unimplemented,
+            "Command measurement is only implemented on Linux.",
+        ))
+    }
+}
+
+impl Drop for MeasuredChild {
+    fn drop(&mut self) {
+        if let Some(sampler) = self.sampler.take() {
+            sampler.stop();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn timeval_to_secs(val: &libc::timeval) -> f64 {
+    val.tv_sec as f64 + (val.tv_usec as f64 / 1000000_f64)
+}
+
+#[cfg(target_os = "linux")]
+fn wait_for_exit(pid: u32) -> Result<(), SporkError> {
+    loop {
+        // SAFETY: siginfo_t is a plain C struct for which all zeroes is a valid value.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOWAIT) };
+
+        if ret == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error.into());
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn reap(pid: u32) -> Result<(i32, libc::rusage), SporkError> {
+    loop {
+        let mut status = 0;
+        // SAFETY: rusage is a plain C struct for which all zeroes is a valid value.
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut usage) };
+
+        if ret == pid as libc::pid_t {
+            return Ok((status, usage));
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error.into());
+        }
+    }
+}
+
+/// Spawn `command` so that its resource usage can be measured once it exits.
+pub fn spawn(command: &mut Command) -> Result<MeasuredChild, SporkError> {
+    let started = Instant::now();
+    let child = command.spawn()?;
+
+    Ok(MeasuredChild {
+        child,
+        started,
+        sampler: None,
+    })
+}
+
+/// Run `command` to completion and return its exit status and resource usage.
+///
+/// This is currently only implemented on Linux.
+pub fn run(command: &mut Command) -> Result<CommandStats, SporkError> {
+    spawn(command)?.wait()
+}
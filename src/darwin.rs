@@ -1,7 +1,7 @@
 use libc::{
     integer_t, kern_return_t, mach_task_basic_info, mach_task_self, rusage, task_thread_times_info, time_value_t,
-    timespec, timeval, EFAULT, EINVAL, EPERM, KERN_INVALID_ARGUMENT, KERN_SUCCESS, MACH_TASK_BASIC_INFO,
-    MACH_TASK_BASIC_INFO_COUNT, RUSAGE_CHILDREN, RUSAGE_SELF, TASK_THREAD_TIMES_INFO, TASK_THREAD_TIMES_INFO_COUNT,
+    timespec, timeval, KERN_INVALID_ARGUMENT, KERN_SUCCESS, MACH_TASK_BASIC_INFO, MACH_TASK_BASIC_INFO_COUNT,
+    RUSAGE_CHILDREN, RUSAGE_SELF, TASK_THREAD_TIMES_INFO, TASK_THREAD_TIMES_INFO_COUNT,
 };
 use std::mem::MaybeUninit;
 use std::time::Duration;
@@ -11,20 +11,15 @@ pub const MIG_ARRAY_TOO_LARGE: kern_return_t = -307;
 
 use super::*;
 
+use backend::{RawStats, StatsBackend};
 use utils::CpuTime;
 
+// the POSIX functions used here return -1 and set errno on failure
 fn map_posix_resp(code: i32) -> Result<i32, SporkError> {
-    match code {
-        EFAULT => Err(SporkError::new_borrowed(
-            SporkErrorKind::Unknown,
-            "Invalid timespec address space.",
-        )),
-        EINVAL => Err(SporkError::new_borrowed(SporkErrorKind::Unknown, "Invalid clock ID.")),
-        EPERM => Err(SporkError::new_borrowed(
-            SporkErrorKind::Unknown,
-            "Invalid clock permissions.",
-        )),
-        _ => Ok(code),
+    if code == -1 {
+        Err(SporkError::last_os_error())
+    } else {
+        Ok(code)
     }
 }
 
@@ -32,7 +27,7 @@ fn map_mach_resp(code: libc::c_int) -> Result<i32, SporkError> {
     match code {
         KERN_SUCCESS => Ok(code),
         KERN_INVALID_ARGUMENT => Err(SporkError::new_borrowed(
-            SporkErrorKind::Unknown,
+            SporkErrorKind::InvalidArgument,
             "Target task is not a thread or flavor not recognized",
         )),
         MIG_ARRAY_TOO_LARGE => Err(SporkError::new_borrowed(
@@ -98,7 +93,8 @@ pub fn get_rusage_from_mach(usage: &mut rusage) -> Result<i32, SporkError> {
 
     let basic_info = unsafe { basic_info.assume_init() };
 
-    usage.ru_maxrss = basic_info.resident_size as i64 / 1024;
+    // ru_maxrss is in bytes on OS X, unlike the KiB used on Linux
+    usage.ru_maxrss = basic_info.resident_size as i64;
     usage.ru_stime = time_value_t_to_timeval(basic_info.system_time);
 
     Ok(KERN_SUCCESS)
@@ -130,6 +126,12 @@ pub fn get_stats(kind: &StatType) -> Result<rusage, SporkError> {
         StatType::Process => (None, Some(RUSAGE_SELF)),
         StatType::Children => (None, Some(RUSAGE_CHILDREN)),
         StatType::Thread => (Some(get_thread_cpu_time()?), None),
+        StatType::Pid(_) => {
+            return Err(SporkError::new_borrowed(
+                SporkErrorKind::Unimplemented,
+                "OS X PID stats not yet implemented!",
+            ))
+        }
     };
 
     let mut usage = MaybeUninit::zeroed();
@@ -158,6 +160,34 @@ pub fn get_cpu_time(val: &rusage) -> f64 {
     (times.sec as f64) + (times.usec as f64 / 1000000_f64)
 }
 
+pub fn get_extended_stats(val: &rusage) -> ExtendedStats {
+    ExtendedStats {
+        minor_faults: val.ru_minflt.wrapping_abs() as u64,
+        major_faults: val.ru_majflt.wrapping_abs() as u64,
+        voluntary_ctx_switches: val.ru_nvcsw.wrapping_abs() as u64,
+        involuntary_ctx_switches: val.ru_nivcsw.wrapping_abs() as u64,
+        block_input_ops: val.ru_inblock.wrapping_abs() as u64,
+        block_output_ops: val.ru_oublock.wrapping_abs() as u64,
+        signals: val.ru_nsignals.wrapping_abs() as u64,
+    }
+}
+
+/// The native backend on OS X, which reads from `getrusage` and the Mach task APIs.
+#[derive(Clone, Debug, Default)]
+pub struct DarwinBackend;
+
+impl StatsBackend for DarwinBackend {
+    fn stats(&self, kind: &StatType) -> Result<RawStats, SporkError> {
+        let usage = get_stats(kind)?;
+
+        Ok(RawStats {
+            cpu_time: get_cpu_time(&usage),
+            memory: usage.ru_maxrss as u64,
+            extended: get_extended_stats(&usage),
+        })
+    }
+}
+
 /// Poke the maximum CPU frequency from IOReg on Apple Silicon systems in Hz.
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 pub fn poke_apple_silicon_cpu_freq() -> Result<u32, SporkError> {
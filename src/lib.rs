@@ -59,7 +59,24 @@ pub type CLong = i64;
 
 mod utils;
 
+pub mod backend;
+pub mod command;
+pub mod measure;
+pub mod prometheus;
+pub mod sampler;
+pub mod thread;
+pub mod topology;
+pub mod watchdog;
+pub mod window;
+
+#[cfg(feature = "serde")]
+pub mod serialization;
+
+use backend::{NativeBackend, StatsBackend};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
 use utils::History;
 
 use std::io::Error as IoError;
@@ -70,21 +87,55 @@ mod windows;
 #[cfg(target_os = "linux")]
 mod posix;
 
+#[cfg(target_os = "linux")]
+mod procfs;
+
+#[cfg(target_os = "linux")]
+mod cgroup;
+
+#[cfg(target_os = "linux")]
+mod sysfs;
+
 #[cfg(target_os = "macos")]
 mod darwin;
 
+/// The shortest interval over which CPU load is calculated. Polls closer together than this report `None` for the CPU
+/// load, since the CPU time counters don't have enough resolution to give a meaningful percentage.
+pub const MIN_CPU_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The shortest interval the background threads of a `Sampler`, `Watchdog` or `MeasuredChild::sample` poll at. Shorter
+/// intervals, including zero, are rounded up to this.
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 /// The kind of SporkError
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum SporkErrorKind {
     InvalidStatType,
     Unimplemented,
     Unknown,
+    /// The OS denied access, such as when reading `/proc/<pid>` for a process owned by another user.
+    PermissionDenied,
+    /// The process or thread does not exist, usually because it already exited.
+    ProcessNotFound,
+    /// A file or value read from the OS was not in the expected format.
+    Parse,
+    /// An argument was out of range, such as a CPU count larger than the number of cores.
+    InvalidArgument,
+    /// Any other I/O or OS error. The OS error code is available from `SporkError::errno` where there is one.
+    Io,
 }
 
 /// A Spork error struct capturing information about errors coming from Spork
 /// if compiled with the feature `compile_unimplemented
 /// certain functions will always return `Unimplemented` errors at runtime
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// Errors from the OS keep the raw OS error code in `errno`, and the original error is available from
+/// `std::error::Error::source`. Errors are compared by their kind, details and `errno`, ignoring the source.
+///
+/// With the `serde` feature enabled errors are serialized in the `SerializedError` format.
+#[derive(Clone, Debug)]
 pub struct SporkError {
     /// Representation of the ErrorKind. "Invalid Stat Type", "Unknown Error', etc
     desc: &'static str,
@@ -92,14 +143,34 @@ pub struct SporkError {
     details: String,
     /// The kind of spork Error
     kind: SporkErrorKind,
+    /// The raw OS error code, if any
+    errno: Option<i32>,
+    /// The underlying error, if any
+    source: Option<Arc<dyn std::error::Error + Send + Sync + 'static>>,
 }
 
+impl PartialEq for SporkError {
+    fn eq(&self, other: &SporkError) -> bool {
+        self.kind == other.kind && self.details == other.details && self.errno == other.errno
+    }
+}
+
+impl Eq for SporkError {}
+
 impl Display for SporkError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("{}: {}", &self.desc, &self.details))
     }
 }
 
+impl std::error::Error for SporkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
 impl SporkError {
     /// Create a new SporkError instance
     pub fn new<T: Into<String>>(kind: SporkErrorKind, details: T) -> SporkError {
@@ -107,12 +178,19 @@ impl SporkError {
             SporkErrorKind::InvalidStatType => "Invalid Stat Type",
             SporkErrorKind::Unimplemented => "Unimplemented",
             SporkErrorKind::Unknown => "Unknown Error",
+            SporkErrorKind::PermissionDenied => "Permission Denied",
+            SporkErrorKind::ProcessNotFound => "Process Not Found",
+            SporkErrorKind::Parse => "Parse Error",
+            SporkErrorKind::InvalidArgument => "Invalid Argument",
+            SporkErrorKind::Io => "IO Error",
         };
 
         SporkError {
             desc,
             details: details.into(),
             kind,
+            errno: None,
+            source: None,
         }
     }
 
@@ -126,6 +204,26 @@ impl SporkError {
         &self.kind
     }
 
+    /// Read the raw OS error code (`errno` on POSIX systems), if the error came from the OS.
+    pub fn errno(&self) -> Option<i32> {
+        self.errno
+    }
+
+    /// Set the raw OS error code.
+    pub fn with_errno(mut self, errno: i32) -> SporkError {
+        self.errno = Some(errno);
+        self
+    }
+
+    /// Set the underlying error returned by `std::error::Error::source`.
+    pub fn with_source<E>(mut self, source: E) -> SporkError
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
     /// Create a new `Error` instance from a borrowed str.
     pub fn new_borrowed(kind: SporkErrorKind, details: &str) -> SporkError {
         SporkError::new(kind, details.to_owned())
@@ -135,6 +233,11 @@ impl SporkError {
     pub fn unimplemented() -> SporkError {
         SporkError::new(SporkErrorKind::Unimplemented, String::new())
     }
+
+    /// Create an error from the current value of `errno` (or `GetLastError` on Windows).
+    pub fn last_os_error() -> SporkError {
+        IoError::last_os_error().into()
+    }
 }
 
 impl From<sys_info::Error> for SporkError {
@@ -143,8 +246,8 @@ impl From<sys_info::Error> for SporkError {
             sys_info::Error::UnsupportedSystem => {
                 SporkError::new(SporkErrorKind::Unimplemented, "Unsupported system.".to_owned())
             }
-            sys_info::Error::ExecFailed(e) => SporkError::new(SporkErrorKind::Unknown, e.to_string()),
-            sys_info::Error::IO(e) => SporkError::new(SporkErrorKind::Unknown, e.to_string()),
+            sys_info::Error::ExecFailed(e) => e.into(),
+            sys_info::Error::IO(e) => e.into(),
             sys_info::Error::SystemTime(e) => SporkError::new(SporkErrorKind::Unknown, e.to_string()),
             sys_info::Error::General(e) => SporkError::new(SporkErrorKind::Unknown, e),
             sys_info::Error::Unknown => {
@@ -156,7 +259,19 @@ impl From<sys_info::Error> for SporkError {
 
 impl From<IoError> for SporkError {
     fn from(error: IoError) -> Self {
-        SporkError::new(SporkErrorKind::Unknown, format!("{}", error))
+        let errno = error.raw_os_error();
+        let kind = match error.kind() {
+            std::io::ErrorKind::PermissionDenied => SporkErrorKind::PermissionDenied,
+            std::io::ErrorKind::InvalidInput => SporkErrorKind::InvalidArgument,
+            std::io::ErrorKind::InvalidData => SporkErrorKind::Parse,
+            #[cfg(unix)]
+            _ if errno == Some(libc::ESRCH) => SporkErrorKind::ProcessNotFound,
+            _ => SporkErrorKind::Io,
+        };
+
+        let mut spork_error = SporkError::new(kind, error.to_string());
+        spork_error.errno = errno;
+        spork_error.with_source(error)
     }
 }
 
@@ -169,7 +284,9 @@ impl From<IoError> for SporkError {
 /// [GetProcessTimes](https://msdn.microsoft.com/en-us/library/windows/desktop/ms683223(v=vs.85).aspx).
 /// For more information about OSX Thread usage stats see:
 /// [TaskBasicInfo](http://web.mit.edu/darwin/src/modules/xnu/osfmk/man/task_basic_info.html)
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum StatType {
     /// Read usage across the entire process.
     Process,
@@ -178,9 +295,16 @@ pub enum StatType {
     /// Read usage across all child threads of the calling thread. This can mean different things on different platforms,
     /// and usually doesn't do what you want it to do. Use with caution.
     Children,
+    /// Read usage across another process by its PID. On Linux this reads
+    /// [/proc/&lt;pid&gt;/stat and /proc/&lt;pid&gt;/status](http://man7.org/linux/man-pages/man5/proc.5.html), and the
+    /// memory reported is the peak resident set size of that process. Block I/O and signal counters are not available
+    /// and will be 0. This is currently unimplemented on OS X and Windows.
+    Pid(u32),
 }
 
 /// The current system's platform, such as `Linux`, `Windows`, etc.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Platform {
     Linux,
@@ -190,24 +314,46 @@ pub enum Platform {
 }
 
 /// A struct holding CPU and memory usage information.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq)]
 pub struct Stats {
-    /// Time at which the stats were polled, in milliseconds since epoch.
+    /// Wall-clock time at which the stats were polled, in milliseconds since epoch. This is informational only and is
+    /// never used to calculate durations, since the system clock can be stepped.
+    #[cfg_attr(feature = "serde", serde(rename = "polled_ms"))]
     pub polled: i64,
-    /// Duration over which CPU usage was calculated, in milliseconds.
-    pub duration: u64,
-    /// Total CPU time spent on application.
+    /// Monotonic time at which the stats were polled, relative to an arbitrary origin shared by the whole process.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "monotonic_ns", with = "serialization::duration_ns")
+    )]
+    pub monotonic: Duration,
+    /// Duration over which CPU usage was calculated, measured with the monotonic clock.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ns", with = "serialization::duration_ns")
+    )]
+    pub duration: Duration,
+    /// Total CPU time spent on application, in seconds.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_time_secs"))]
     pub cpu_time: f64,
-    /// Average CPU load (percentage) since the last poll.
-    pub cpu: f64,
+    /// Average CPU load (percentage) since the last poll, or `None` if the interval was shorter than
+    /// `MIN_CPU_INTERVAL`.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_percent"))]
+    pub cpu: Option<f64>,
     /// Total working set size, in bytes. This can mean different things depending on the `StatType` used.
+    #[cfg_attr(feature = "serde", serde(rename = "memory_bytes"))]
     pub memory: u64,
     /// Process uptime, in ms.
+    #[cfg_attr(feature = "serde", serde(rename = "uptime_ms"))]
     pub uptime: u64,
     /// The type of statistic.
     pub kind: StatType,
     /// The number of CPU cores considered when measuring the CPU usage.
     pub cores: usize,
+    /// Cumulative resource counters (page faults, context switches, etc) for the `StatType`.
+    pub extended: ExtendedStats,
+    /// The change in `extended` since the previous poll of the same `StatType`.
+    pub extended_delta: ExtendedStats,
 }
 
 impl Stats {
@@ -215,21 +361,609 @@ impl Stats {
         Stats {
             kind,
             polled: 0,
-            duration: 0,
+            monotonic: Duration::ZERO,
+            duration: Duration::ZERO,
             cpu_time: 0_f64,
-            cpu: 0_f64,
+            cpu: None,
             memory: 0,
             uptime: 0,
             cores: 1,
+            extended: ExtendedStats::default(),
+            extended_delta: ExtendedStats::default(),
+        }
+    }
+}
+
+/// A struct holding the resource counters reported by `getrusage` beyond CPU time and memory.
+///
+/// On Linux and OS X these are read from the same `rusage` record used for `Stats`, see
+/// [getrusage](http://man7.org/linux/man-pages/man2/getrusage.2.html). On OS X `StatType::Thread` is read
+/// from the Mach task info instead, so every counter will be 0. On Windows only `minor_faults` is populated, from
+/// the `PageFaultCount` of [GetProcessMemoryInfo](https://msdn.microsoft.com/en-us/library/windows/desktop/ms683219(v=vs.85).aspx).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExtendedStats {
+    /// Page faults serviced without any I/O activity (`ru_minflt`).
+    pub minor_faults: u64,
+    /// Page faults serviced that required I/O activity (`ru_majflt`).
+    pub major_faults: u64,
+    /// Context switches due to waiting on a resource (`ru_nvcsw`).
+    pub voluntary_ctx_switches: u64,
+    /// Context switches due to preemption (`ru_nivcsw`).
+    pub involuntary_ctx_switches: u64,
+    /// Block input operations performed by the file system (`ru_inblock`).
+    pub block_input_ops: u64,
+    /// Block output operations performed by the file system (`ru_oublock`).
+    pub block_output_ops: u64,
+    /// Signals received (`ru_nsignals`).
+    pub signals: u64,
+}
+
+impl ExtendedStats {
+    /// Compute the change in each counter since `prev`. Counters that went backwards, such as after the kernel
+    /// reused a thread, are reported as 0.
+    pub fn delta(&self, prev: &ExtendedStats) -> ExtendedStats {
+        ExtendedStats {
+            minor_faults: self.minor_faults.saturating_sub(prev.minor_faults),
+            major_faults: self.major_faults.saturating_sub(prev.major_faults),
+            voluntary_ctx_switches: self.voluntary_ctx_switches.saturating_sub(prev.voluntary_ctx_switches),
+            involuntary_ctx_switches: self
+                .involuntary_ctx_switches
+                .saturating_sub(prev.involuntary_ctx_switches),
+            block_input_ops: self.block_input_ops.saturating_sub(prev.block_input_ops),
+            block_output_ops: self.block_output_ops.saturating_sub(prev.block_output_ops),
+            signals: self.signals.saturating_sub(prev.signals),
+        }
+    }
+}
+
+/// A struct holding a breakdown of the memory used by a process, in bytes.
+///
+/// Unlike `Stats.memory`, which is the peak resident set size, `rss` reflects the memory resident right now and will go
+/// down as memory is returned to the OS. On Linux this is read from
+/// [/proc/self/status and /proc/self/statm](http://man7.org/linux/man-pages/man5/proc.5.html).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryStats {
+    /// Current resident set size.
+    #[cfg_attr(feature = "serde", serde(rename = "rss_bytes"))]
+    pub rss: u64,
+    /// Peak resident set size (`VmHWM`).
+    #[cfg_attr(feature = "serde", serde(rename = "peak_rss_bytes"))]
+    pub peak_rss: u64,
+    /// Total virtual memory size.
+    #[cfg_attr(feature = "serde", serde(rename = "virtual_size_bytes"))]
+    pub virtual_size: u64,
+    /// Memory swapped out to disk (`VmSwap`).
+    #[cfg_attr(feature = "serde", serde(rename = "swap_bytes"))]
+    pub swap: u64,
+    /// Size of the data segment (`VmData`).
+    #[cfg_attr(feature = "serde", serde(rename = "data_bytes"))]
+    pub data: u64,
+    /// Size of the main thread's stack (`VmStk`).
+    #[cfg_attr(feature = "serde", serde(rename = "stack_bytes"))]
+    pub stack: u64,
+}
+
+/// A struct holding a breakdown of the memory mapped by a process, in bytes, as read from
+/// [/proc/self/smaps_rollup](http://man7.org/linux/man-pages/man5/proc.5.html).
+///
+/// Unlike the resident set size, which counts every page shared with another process in full, `pss` divides each
+/// shared page between the processes that map it. Summing the `pss` of every process in a pre-fork worker pool gives
+/// the memory used by the whole pool.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryBreakdown {
+    /// Resident set size.
+    #[cfg_attr(feature = "serde", serde(rename = "rss_bytes"))]
+    pub rss: u64,
+    /// Proportional set size: private pages plus each shared page divided by the number of processes mapping it.
+    #[cfg_attr(feature = "serde", serde(rename = "pss_bytes"))]
+    pub pss: u64,
+    /// Unique set size: the pages mapped only by this process (`private_clean` plus `private_dirty`).
+    #[cfg_attr(feature = "serde", serde(rename = "uss_bytes"))]
+    pub uss: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "shared_clean_bytes"))]
+    pub shared_clean: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "shared_dirty_bytes"))]
+    pub shared_dirty: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "private_clean_bytes"))]
+    pub private_clean: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "private_dirty_bytes"))]
+    pub private_dirty: u64,
+    /// Resident pages not backed by a file, such as the heap and stacks.
+    #[cfg_attr(feature = "serde", serde(rename = "anonymous_bytes"))]
+    pub anonymous: u64,
+    /// Resident pages backed by a file, such as code and memory mapped files (`rss` minus `anonymous`).
+    #[cfg_attr(feature = "serde", serde(rename = "file_backed_bytes"))]
+    pub file_backed: u64,
+    /// Anonymous memory swapped out to disk.
+    #[cfg_attr(feature = "serde", serde(rename = "swap_bytes"))]
+    pub swap: u64,
+    /// Swap divided between the processes sharing it, like `pss`.
+    #[cfg_attr(feature = "serde", serde(rename = "swap_pss_bytes"))]
+    pub swap_pss: u64,
+    /// Anonymous memory backed by transparent huge pages.
+    #[cfg_attr(feature = "serde", serde(rename = "anon_huge_pages_bytes"))]
+    pub anon_huge_pages: u64,
+}
+
+/// A single memory mapping of a process, as read from [/proc/self/smaps](http://man7.org/linux/man-pages/man5/proc.5.html).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryMapping {
+    /// The first address of the mapping.
+    pub start: u64,
+    /// The address after the end of the mapping.
+    pub end: u64,
+    /// The permissions, such as `r-xp`.
+    pub permissions: String,
+    /// The offset into the mapped file.
+    pub offset: u64,
+    /// The mapped file, or a pseudo-path such as `[heap]` or `[stack]`. `None` for anonymous mappings.
+    pub path: Option<String>,
+    /// The size of the mapping, in bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "size_bytes"))]
+    pub size: u64,
+    /// The memory used by the mapping.
+    pub memory: MemoryBreakdown,
+}
+
+/// A struct holding CPU usage information for a single thread of the current process, as returned by
+/// `Spork::thread_snapshot`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadStats {
+    /// The kernel thread ID.
+    pub tid: u32,
+    /// The thread's name (`comm`), truncated by the kernel to 15 bytes.
+    pub name: String,
+    /// The thread's scheduler state, such as `R` (running), `S` (sleeping), or `D` (uninterruptible wait). See
+    /// [proc(5)](http://man7.org/linux/man-pages/man5/proc.5.html) for the full list.
+    pub state: char,
+    /// Total time spent in user mode, in seconds.
+    #[cfg_attr(feature = "serde", serde(rename = "user_time_secs"))]
+    pub user_time: f64,
+    /// Total time spent in kernel mode, in seconds.
+    #[cfg_attr(feature = "serde", serde(rename = "system_time_secs"))]
+    pub system_time: f64,
+    /// Average CPU load (percentage) since the previous snapshot, or `None` if the interval was shorter than
+    /// `MIN_CPU_INTERVAL`.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_percent"))]
+    pub cpu: Option<f64>,
+    /// Wall-clock time at which the thread was polled, in milliseconds since epoch.
+    #[cfg_attr(feature = "serde", serde(rename = "polled_ms"))]
+    pub polled: i64,
+    /// Monotonic time at which the thread was polled, see `Stats.monotonic`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "monotonic_ns", with = "serialization::duration_ns")
+    )]
+    pub monotonic: Duration,
+    /// Duration over which CPU usage was calculated, measured with the monotonic clock.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ns", with = "serialization::duration_ns")
+    )]
+    pub duration: Duration,
+}
+
+impl ThreadStats {
+    pub fn new_empty(tid: u32) -> ThreadStats {
+        ThreadStats {
+            tid,
+            name: String::new(),
+            state: '?',
+            user_time: 0_f64,
+            system_time: 0_f64,
+            cpu: None,
+            polled: 0,
+            monotonic: Duration::ZERO,
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+/// A handle to a thread registered with `Spork::register_current_thread`, which lets any other thread read the
+/// registered thread's CPU usage with `Spork::stats_for_thread`.
+///
+/// The handle is only valid while the thread is alive. Once it exits, `stats_for_thread` returns a `ProcessNotFound`
+/// error, unless the kernel has already reused its tid for a new thread in this process, in which case the new thread
+/// is read instead.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ThreadHandle {
+    name: String,
+    clock: backend::ThreadClock,
+}
+
+impl ThreadHandle {
+    /// Read the name the thread was registered with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the kernel thread ID of the registered thread.
+    pub fn tid(&self) -> u32 {
+        self.clock.tid
+    }
+}
+
+/// A single process in a `ProcessTree`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeProcess {
+    pub pid: u32,
+    /// The pid of the parent process.
+    pub ppid: u32,
+    /// The process' name (`comm`), truncated by the kernel to 15 bytes.
+    pub name: String,
+    /// The number of generations between the process and the root of the tree, which is 0 for the root itself.
+    pub depth: usize,
+    /// Total user and system CPU time, in seconds. This doesn't include the time of children the process has waited on.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_time_secs"))]
+    pub cpu_time: f64,
+    /// Total user and system CPU time of the children the process has waited on, in seconds.
+    #[cfg_attr(feature = "serde", serde(rename = "children_cpu_time_secs"))]
+    pub children_cpu_time: f64,
+    /// Current resident set size, in bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "rss_bytes"))]
+    pub rss: u64,
+}
+
+/// The live totals for a process and all of its descendants, as returned by `Spork::process_tree`. Unlike
+/// `StatType::Children`, this includes descendants that are still running.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessTree {
+    /// The pid of the process at the root of the tree.
+    pub root: u32,
+    /// Total CPU time of every process in the tree, including the root, in seconds. This also includes the children
+    /// the descendants have waited on, so the total doesn't drop when a shell in the tree reaps a compiler, for example.
+    /// The children waited on by the root itself are not included, see `StatType::Children`.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_time_secs"))]
+    pub cpu_time: f64,
+    /// Total resident set size of every process in the tree, including the root, in bytes. Memory shared between
+    /// processes is counted once for each of them.
+    #[cfg_attr(feature = "serde", serde(rename = "rss_bytes"))]
+    pub rss: u64,
+    /// Every process in the tree, with the root first and the descendants in breadth-first order.
+    pub processes: Vec<TreeProcess>,
+}
+
+impl ProcessTree {
+    /// Build the tree rooted at `root` from every process on the system by following each process' parent pid.
+    pub fn from_processes(root: u32, processes: Vec<TreeProcess>) -> Option<ProcessTree> {
+        let mut children: HashMap<u32, Vec<TreeProcess>> = HashMap::new();
+        let mut tree = Vec::new();
+
+        for process in processes {
+            if process.pid == root {
+                tree.push(TreeProcess { depth: 0, ..process });
+            } else {
+                children.entry(process.ppid).or_default().push(process);
+            }
+        }
+        if tree.is_empty() {
+            return None;
+        }
+
+        let mut idx = 0;
+        while idx < tree.len() {
+            let (pid, depth) = (tree[idx].pid, tree[idx].depth);
+            if let Some(mut found) = children.remove(&pid) {
+                found.sort_by_key(|p| p.pid);
+                tree.extend(found.into_iter().map(|p| TreeProcess { depth: depth + 1, ..p }));
+            }
+            idx += 1;
+        }
+
+        Some(ProcessTree {
+            root,
+            cpu_time: tree.iter().map(|p| p.cpu_time).sum::<f64>()
+                + tree.iter().skip(1).map(|p| p.children_cpu_time).sum::<f64>(),
+            rss: tree.iter().map(|p| p.rss).sum(),
+            processes: tree,
+        })
+    }
+
+    /// Iterate over every process in the tree except the root.
+    pub fn descendants(&self) -> impl Iterator<Item = &TreeProcess> {
+        self.processes.iter().skip(1)
+    }
+}
+
+/// The number of entries in the stats history of a `Spork` instance, as returned by `Spork::history_size`. Each entry
+/// holds the recent polls of one key, such as one thread's `StatType::Thread` polls.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HistorySize {
+    /// 1 if the process has been polled, otherwise 0.
+    pub process: usize,
+    /// The number of threads with `StatType::Thread` history.
+    pub thread: usize,
+    /// The number of threads with `StatType::Children` history.
+    pub children: usize,
+    /// The number of processes with `StatType::Pid` history.
+    pub pids: usize,
+    /// The number of registered threads with history from `Spork::stats_for_thread`.
+    pub handles: usize,
+}
+
+impl HistorySize {
+    /// Count the entries across every kind of history.
+    pub fn total(&self) -> usize {
+        self.process + self.thread + self.children + self.pids + self.handles
+    }
+}
+
+/// A struct holding the cumulative time the CPU has spent in each state since boot, in clock ticks (`USER_HZ`). On
+/// Linux these are read from [/proc/stat](http://man7.org/linux/man-pages/man5/proc.5.html).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CpuTimes {
+    /// The logical core these times are for, or `None` for the total across all cores.
+    pub core: Option<usize>,
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTimes {
+    /// The total time across every state.
+    pub fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle + self.iowait + self.irq + self.softirq + self.steal
+    }
+
+    /// Compute the change in each state since `prev`. Counters that went backwards, such as after a core was taken
+    /// offline and brought back, are reported as 0.
+    pub fn delta(&self, prev: &CpuTimes) -> CpuTimes {
+        CpuTimes {
+            core: self.core,
+            user: self.user.saturating_sub(prev.user),
+            nice: self.nice.saturating_sub(prev.nice),
+            system: self.system.saturating_sub(prev.system),
+            idle: self.idle.saturating_sub(prev.idle),
+            iowait: self.iowait.saturating_sub(prev.iowait),
+            irq: self.irq.saturating_sub(prev.irq),
+            softirq: self.softirq.saturating_sub(prev.softirq),
+            steal: self.steal.saturating_sub(prev.steal),
+        }
+    }
+}
+
+/// A struct holding the percentage of time the CPU spent in each state over an interval. The states add up to 100, or
+/// are all 0 if no time was accounted over the interval.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuUsage {
+    /// The logical core this usage is for, or `None` for the total across all cores.
+    pub core: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(rename = "user_percent"))]
+    pub user: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "nice_percent"))]
+    pub nice: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "system_percent"))]
+    pub system: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "idle_percent"))]
+    pub idle: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "iowait_percent"))]
+    pub iowait: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "irq_percent"))]
+    pub irq: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "softirq_percent"))]
+    pub softirq: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "steal_percent"))]
+    pub steal: f64,
+}
+
+impl CpuUsage {
+    /// Calculate the usage from the change in CPU times over an interval.
+    pub fn from_delta(delta: &CpuTimes) -> CpuUsage {
+        let total = delta.total();
+        let percent = |ticks: u64| {
+            if total == 0 {
+                0_f64
+            } else {
+                (ticks as f64 / total as f64) * 100_f64
+            }
+        };
+
+        CpuUsage {
+            core: delta.core,
+            user: percent(delta.user),
+            nice: percent(delta.nice),
+            system: percent(delta.system),
+            idle: percent(delta.idle),
+            iowait: percent(delta.iowait),
+            irq: percent(delta.irq),
+            softirq: percent(delta.softirq),
+            steal: percent(delta.steal),
         }
     }
+
+    /// The percentage of time the CPU was doing work, i.e. not idle or waiting on I/O.
+    pub fn busy(&self) -> f64 {
+        self.user + self.nice + self.system + self.irq + self.softirq + self.steal
+    }
+}
+
+/// A struct holding system-wide CPU utilization, as returned by `Spork::system_cpu_stats`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SystemCpuStats {
+    /// Wall-clock time at which the stats were polled, in milliseconds since epoch.
+    #[cfg_attr(feature = "serde", serde(rename = "polled_ms"))]
+    pub polled: i64,
+    /// Monotonic time at which the stats were polled, relative to an arbitrary origin shared by the whole process.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "monotonic_ns", with = "serialization::duration_ns")
+    )]
+    pub monotonic: Duration,
+    /// Duration over which the usage was calculated, measured with the monotonic clock. The first poll reports usage
+    /// since boot, and this is the time since the `Spork` instance was created.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ns", with = "serialization::duration_ns")
+    )]
+    pub duration: Duration,
+    /// The usage across all cores.
+    pub total: CpuUsage,
+    /// The usage of each online logical core.
+    pub cores: Vec<CpuUsage>,
+}
+
+/// A struct holding the I/O counters of a process, as returned by `Spork::io_stats`. On Linux these are read from
+/// [/proc/&lt;pid&gt;/io](http://man7.org/linux/man-pages/man5/proc.5.html).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IoStats {
+    /// The process the counters are for, or `None` for the current process.
+    pub pid: Option<u32>,
+    /// Wall-clock time at which the stats were polled, in milliseconds since epoch.
+    #[cfg_attr(feature = "serde", serde(rename = "polled_ms"))]
+    pub polled: i64,
+    /// Monotonic time at which the stats were polled, relative to an arbitrary origin shared by the whole process.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "monotonic_ns", with = "serialization::duration_ns")
+    )]
+    pub monotonic: Duration,
+    /// Duration over which the rates were calculated, measured with the monotonic clock.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ns", with = "serialization::duration_ns")
+    )]
+    pub duration: Duration,
+    /// Bytes read by any read syscall, including reads served from the page cache.
+    #[cfg_attr(feature = "serde", serde(rename = "rchar_bytes"))]
+    pub rchar: u64,
+    /// Bytes written by any write syscall, including writes that have not reached the disk yet.
+    #[cfg_attr(feature = "serde", serde(rename = "wchar_bytes"))]
+    pub wchar: u64,
+    /// Number of read syscalls.
+    pub syscr: u64,
+    /// Number of write syscalls.
+    pub syscw: u64,
+    /// Bytes actually fetched from the storage layer.
+    pub read_bytes: u64,
+    /// Bytes sent to the storage layer.
+    pub write_bytes: u64,
+    /// Bytes that were written to the page cache but never reached the disk, such as a file truncated before writeback.
+    pub cancelled_write_bytes: u64,
+    /// The change in each counter per second since the previous poll, or `None` if the interval was shorter than
+    /// `MIN_CPU_INTERVAL`.
+    pub rates: Option<IoRates>,
+}
+
+impl IoStats {
+    pub fn new_empty(pid: Option<u32>) -> IoStats {
+        IoStats {
+            pid,
+            polled: 0,
+            monotonic: Duration::ZERO,
+            duration: Duration::ZERO,
+            rchar: 0,
+            wchar: 0,
+            syscr: 0,
+            syscw: 0,
+            read_bytes: 0,
+            write_bytes: 0,
+            cancelled_write_bytes: 0,
+            rates: None,
+        }
+    }
+}
+
+/// A struct holding the rate of change of each `IoStats` counter, per second.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IoRates {
+    #[cfg_attr(feature = "serde", serde(rename = "rchar_bytes_per_sec"))]
+    pub rchar: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "wchar_bytes_per_sec"))]
+    pub wchar: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "syscr_per_sec"))]
+    pub syscr: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "syscw_per_sec"))]
+    pub syscw: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "read_bytes_per_sec"))]
+    pub read_bytes: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "write_bytes_per_sec"))]
+    pub write_bytes: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "cancelled_write_bytes_per_sec"))]
+    pub cancelled_write_bytes: f64,
+}
+
+impl IoRates {
+    /// Calculate the rates from the change in counters since `prev` over `duration`, or `None` if `duration` is shorter
+    /// than `MIN_CPU_INTERVAL`. Counters that went backwards are reported as 0.
+    pub fn from_delta(curr: &IoStats, prev: &IoStats, duration: Duration) -> Option<IoRates> {
+        if duration < MIN_CPU_INTERVAL {
+            return None;
+        }
+
+        let secs = duration.as_secs_f64();
+        let rate = |curr: u64, prev: u64| curr.saturating_sub(prev) as f64 / secs;
+
+        Some(IoRates {
+            rchar: rate(curr.rchar, prev.rchar),
+            wchar: rate(curr.wchar, prev.wchar),
+            syscr: rate(curr.syscr, prev.syscr),
+            syscw: rate(curr.syscw, prev.syscw),
+            read_bytes: rate(curr.read_bytes, prev.read_bytes),
+            write_bytes: rate(curr.write_bytes, prev.write_bytes),
+            cancelled_write_bytes: rate(curr.cancelled_write_bytes, prev.cancelled_write_bytes),
+        })
+    }
+}
+
+/// The version of the cgroup hierarchy the process' limits were read from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// A struct holding the resource limits and usage of the process' cgroup, as returned by `Spork::cgroup_stats`.
+///
+/// On cgroup v2 these are read from `cpu.max`, `memory.max`, `memory.current` and `cpu.stat`, taking the lowest limit
+/// set on the cgroup or any of its ancestors. On cgroup v1 they are read from `cpu.cfs_quota_us`,
+/// `cpu.cfs_period_us`, `cpu.stat`, `memory.limit_in_bytes` and `memory.usage_in_bytes`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CgroupStats {
+    /// The cgroup version.
+    pub version: CgroupVersion,
+    /// The CPU quota as a number of cores, such as `1.5`, or `None` if there is no quota.
+    #[cfg_attr(feature = "serde", serde(rename = "cpu_limit_cores"))]
+    pub cpu_limit: Option<f64>,
+    /// The memory limit, in bytes, or `None` if there is no limit.
+    #[cfg_attr(feature = "serde", serde(rename = "memory_limit_bytes"))]
+    pub memory_limit: Option<u64>,
+    /// The memory currently charged to the cgroup, in bytes. This includes the page cache.
+    #[cfg_attr(feature = "serde", serde(rename = "memory_usage_bytes"))]
+    pub memory_usage: u64,
+    /// The number of CPU quota periods that have elapsed.
+    pub nr_periods: u64,
+    /// The number of periods in which the cgroup was throttled for exhausting its quota.
+    pub nr_throttled: u64,
+    /// The total time the cgroup was throttled, in microseconds.
+    pub throttled_usec: u64,
 }
 
 /// A struct to monitor CPU and memory usage.
 ///
 /// ### Important Notes:
 ///
-/// The `stats` and `stats_with_cpus` functions measure CPU usage by the time between calls (in ms), separated by
+/// The `stats` and `stats_with_cpus` functions measure CPU usage by the monotonic time between calls, separated by
 /// the provided `StatType`. For example, a single thread calling `stat` with the `StatType::Thread` option in a loop
 /// every second will correctly measure usage across the previous 1 second interval for only that thread. However, if
 /// for example two threads running in loops with different delays both try to read `stats` with `StatType::Process`
@@ -246,57 +980,78 @@ impl Stats {
 /// As noted above, the memory stat for windows returns 0 on StatType::Thread. This is due to (AFAIK) no way to get memory usage for a thread.
 /// Any information about this is gracious accepted
 ///
+/// ## Sharing
+/// `Spork` is `Send` and `Sync`, so a single instance can be stored in an `Arc` or a `static` and polled from every
+/// thread of a service. The history is internally synchronized and `StatType::Thread` and `StatType::Children` are
+/// still tracked per calling thread.
+///
 /// TLDR: Be careful using `stat` and `stat_with_cpus` with `StatType::Process` across multiple threads running concurrently.
 ///
 #[derive(Clone, Debug)]
 pub struct Spork {
     history: History,
+    backend: Arc<dyn StatsBackend>,
     platform: Platform,
     clock: u64,
     cpus: usize,
+    cpu_limit: Option<f64>,
     // TODO use process uptime
-    started: i64,
+    started: Duration,
 }
 
 impl Spork {
     /// Create a new `Spork` instance.
     pub fn new() -> Result<Spork, SporkError> {
-        Ok(Spork {
-            history: History::default(),
-            platform: utils::get_platform(),
-            clock: utils::get_cpu_speed()?,
-            cpus: utils::get_num_cores(),
-            started: utils::now_ms(),
-        })
+        Spork::with_backend(NativeBackend)
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across only 1 CPU core.
+    /// Create a new `Spork` instance that reads all of its counters and clocks from `backend`. See the `backend` module
+    /// for a `MockBackend` that can be used in tests.
     ///
     /// ```
-    /// let spork = Spork::new().unwrap();
-    /// let stats = spork.stats(StatType::Thread).unwrap();
+    /// let backend = MockBackend::new().with_cores(4);
+    /// let spork = Spork::with_backend(backend.clone()).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// assert_eq!(spork.num_cores(), 4);
     /// ```
-    #[cfg(target_os = "linux")]
-    pub fn stats(&self, kind: StatType) -> Result<Stats, SporkError> {
-        let now = utils::now_ms();
+    pub fn with_backend<B>(backend: B) -> Result<Spork, SporkError>
+    where
+        B: StatsBackend + 'static,
+    {
+        Ok(Spork {
+            history: History::default(),
+            platform: utils::get_platform(),
+            clock: backend.cpu_speed()?,
+            cpus: backend.num_cores(),
+            cpu_limit: backend.cgroup_stats().ok().and_then(|cgroup| cgroup.cpu_limit),
+            started: backend.now(),
+            backend: Arc::new(backend),
+        })
+    }
+
+    // `capacity` is the number of CPUs' worth of time that counts as 100% load
+    fn poll(&self, kind: StatType, cores: usize, capacity: f64) -> Result<Stats, SporkError> {
+        let polled = self.backend.now_ms();
+        let now = self.backend.now();
         let duration = utils::calc_duration(&kind, &self.history, self.started, now);
 
-        let usage = posix::get_stats(&kind)?;
-        let cpu_time = posix::get_cpu_time(&usage);
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
+        let raw = self.backend.stats(&kind)?;
+        utils::reset_reused_pid(&self.history, &kind, raw.cpu_time);
+        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, raw.cpu_time, duration);
+        let extended_delta = utils::calc_extended_delta(&self.history, &kind, &raw.extended);
 
         let stats = Stats {
             kind: kind.clone(),
-            polled: now,
-            duration: duration,
-            cpu_time: cpu_time,
-            cpu: cpu_percent,
-            memory: (usage.ru_maxrss as u64) * 1000,
-            uptime: utils::safe_unsigned_sub(now, self.started),
-            cores: 1,
+            polled,
+            monotonic: now,
+            duration,
+            cpu_time: raw.cpu_time,
+            cpu: cpu_percent.map(|cpu| cpu / capacity),
+            memory: raw.memory,
+            uptime: utils::calc_uptime(self.started, now),
+            cores,
+            extended: raw.extended,
+            extended_delta,
         };
 
         self.history.set_last(&kind, stats.clone());
@@ -309,253 +1064,376 @@ impl Spork {
     /// let spork = Spork::new().unwrap();
     /// let stats = spork.stats(StatType::Thread).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
+    /// println!("CPU: {:?}%, Memory: {} bytes, Cores: {}, Type: {:?}, Polled at: {}",
     ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
     /// ```
-    #[cfg(target_os = "macos")]
     pub fn stats(&self, kind: StatType) -> Result<Stats, SporkError> {
-        let now = utils::now_ms();
-        let duration = utils::calc_duration(&kind, &self.history, self.started, now);
-
-        let usage = darwin::get_stats(&kind)?;
-        let cpu_time = darwin::get_cpu_time(&usage);
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
-
-        let stats = Stats {
-            kind: kind.clone(),
-            polled: now,
-            duration,
-            cpu_time,
-            cpu: cpu_percent,
-            memory: (usage.ru_maxrss as u64) * 1000,
-            uptime: utils::safe_unsigned_sub(now, self.started),
-            cores: 1,
-        };
-
-        self.history.set_last(&kind, stats.clone());
-        Ok(stats)
+        self.poll(kind, 1, 1_f64)
     }
 
     /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across `count` CPU core(s).
-    /// If `None` is provided then all available CPU cores will be considered, where applicable.
+    /// If `None` is provided then all the cores the process can use will be considered, which is the cgroup CPU quota
+    /// where one is set. With a quota, the CPU load is normalized so that 100% means the process used all of
+    /// `cpu_capacity`. Otherwise the CPU load is relative to a single core, as with `stats`.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
     /// // read stats across all available CPU cores
     /// let stats = spork.stats_with_cpus(StatType::Thread, None).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
+    /// println!("CPU: {:?}%, Memory: {} bytes, Cores: {}, Type: {:?}, Polled at: {}",
     ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
     ///
     /// // read stats considering only 2 CPU cores
     /// let stats = spork.stats_with_cpus(StatType::Thread, Some(2));
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
+    /// println!("CPU: {:?}%, Memory: {} bytes, Cores: {}, Type: {:?}, Polled at: {}",
     ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
     /// ```
-    #[cfg(target_os = "linux")]
     pub fn stats_with_cpus(&self, kind: StatType, cores: Option<usize>) -> Result<Stats, SporkError> {
-        let cores = match cores {
-            Some(c) => c,
-            None => self.cpus,
+        let (cores, capacity) = match cores {
+            Some(c) => (c, 1_f64),
+            None => (self.effective_cores(), self.cpu_capacity().unwrap_or(1_f64)),
         };
 
         if cores > self.cpus {
-            return Err(SporkError::new_borrowed(SporkErrorKind::Unknown, "Invalid CPU count."));
+            return Err(SporkError::new_borrowed(
+                SporkErrorKind::InvalidArgument,
+                "Invalid CPU count.",
+            ));
         }
 
-        let now = utils::now_ms();
-        let duration = utils::calc_duration(&kind, &self.history, self.started, now);
-
-        let usage = posix::get_stats(&kind)?;
-        let cpu_time = posix::get_cpu_time(&usage);
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
-
-        let stats = Stats {
-            kind: kind.clone(),
-            polled: now,
-            duration: duration,
-            cpu_time: cpu_time,
-            cpu: cpu_percent,
-            memory: (usage.ru_maxrss as u64) * 1000,
-            uptime: utils::safe_unsigned_sub(now, self.started),
-            cores: cores,
-        };
-
-        self.history.set_last(&kind, stats.clone());
-        Ok(stats)
+        self.poll(kind, cores, capacity)
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across `count` CPU core(s).
-    /// If `None` is provided then all available CPU cores will be considered, where applicable.
+    /// Get a breakdown of the current memory usage of the process. Unlike `stats`, this does not depend on or modify
+    /// the stats history.
+    ///
+    /// This is currently only implemented on Linux.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
-    /// // read stats across all available CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, None).unwrap();
+    /// let memory = spork.memory_stats().unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// println!("RSS: {} bytes, Peak RSS: {} bytes, Swap: {} bytes", memory.rss, memory.peak_rss, memory.swap);
+    /// ```
+    pub fn memory_stats(&self) -> Result<MemoryStats, SporkError> {
+        self.backend.memory_stats()
+    }
+
+    /// Get system-wide CPU utilization, in total and for each logical core, over the interval since the previous call.
+    /// The first call reports the usage since boot. This keeps its own history and does not interfere with `stats`.
     ///
-    /// // read stats considering only 2 CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, Some(2));
+    /// This is currently only implemented on Linux.
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
     /// ```
-    #[cfg(target_os = "macos")]
-    pub fn stats_with_cpus(&self, kind: StatType, cores: Option<usize>) -> Result<Stats, SporkError> {
-        let cores = match cores {
-            Some(c) => c,
-            None => self.cpus,
+    /// let spork = Spork::new().unwrap();
+    /// let system = spork.system_cpu_stats().unwrap();
+    ///
+    /// println!("Busy: {}%, I/O wait: {}%, Steal: {}%", system.total.busy(), system.total.iowait, system.total.steal);
+    /// for core in system.cores.iter() {
+    ///   println!("Core {:?}: {}% busy", core.core, core.busy());
+    /// }
+    /// ```
+    pub fn system_cpu_stats(&self) -> Result<SystemCpuStats, SporkError> {
+        let polled = self.backend.now_ms();
+        let now = self.backend.now();
+        let times = self.backend.system_cpu_times()?;
+
+        let (duration, total, cores) = match self.history.set_system(now, times.clone()) {
+            Some((last, prev)) => (
+                now.saturating_sub(last),
+                CpuUsage::from_delta(&times.total.delta(&prev.total)),
+                utils::calc_core_usage(&times.cores, &prev.cores),
+            ),
+            None => (
+                now.saturating_sub(self.started),
+                CpuUsage::from_delta(&times.total),
+                times.cores.iter().map(CpuUsage::from_delta).collect(),
+            ),
         };
 
-        if cores > self.cpus {
-            return Err(SporkError::new_borrowed(SporkErrorKind::Unknown, "Invalid CPU count."));
-        }
-
-        let now = utils::now_ms();
-        let duration = utils::calc_duration(&kind, &self.history, self.started, now);
-
-        let usage = darwin::get_stats(&kind)?;
-        let cpu_time = darwin::get_cpu_time(&usage);
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
-
-        let stats = Stats {
-            kind: kind.clone(),
-            polled: now,
+        Ok(SystemCpuStats {
+            polled,
+            monotonic: now,
             duration,
-            cpu_time,
-            cpu: cpu_percent,
-            memory: (usage.ru_maxrss as u64) * 1000,
-            uptime: utils::safe_unsigned_sub(now, self.started),
+            total,
             cores,
-        };
-
-        self.history.set_last(&kind, stats.clone());
-        Ok(stats)
+        })
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across only 1 CPU core.
+    /// Get the I/O counters of the current process with `StatType::Process`, or of another process with
+    /// `StatType::Pid`, along with the rate of change of each counter since the previous call for the same process.
+    /// The first call reports the rates since the `Spork` instance was created. This keeps its own history and does not
+    /// interfere with `stats`.
+    ///
+    /// This is currently only implemented on Linux. Reading another process requires the same permissions as `ptrace`.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
-    /// let stats = spork.stats(StatType::Thread).unwrap();
+    /// let io = spork.io_stats(StatType::Process).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// println!("Read: {} bytes, Written: {} bytes, Rates: {:?}", io.read_bytes, io.write_bytes, io.rates);
     /// ```
-    #[cfg(windows)]
-    pub fn stats(&self, kind: StatType) -> Result<Stats, SporkError> {
-        let now = utils::now_ms();
-        let duration = utils::calc_duration(&kind, &self.history, self.started, now);
+    pub fn io_stats(&self, kind: StatType) -> Result<IoStats, SporkError> {
+        let pid = process_pid(&kind, "I/O stats are only available for processes.")?;
 
-        let cpu_times = windows::get_cpu_times(&kind)?;
-        let cpu_time = windows::combine_cpu_times(&cpu_times);
-        let mem = windows::get_mem_stats(&kind)?;
+        let polled = self.backend.now_ms();
+        let now = self.backend.now();
+        let counters = self.backend.io_stats(pid)?;
 
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
+        let (duration, rates) = match self.history.get_io(pid) {
+            Some(last) => {
+                let duration = now.saturating_sub(last.monotonic);
+                (duration, IoRates::from_delta(&counters, &last, duration))
+            }
+            None => {
+                let duration = now.saturating_sub(self.started);
+                (
+                    duration,
+                    IoRates::from_delta(&counters, &IoStats::new_empty(pid), duration),
+                )
+            }
+        };
 
-        let stats = Stats {
-            kind: kind.clone(),
-            polled: now,
-            duration: duration,
-            cpu_time: cpu_time,
-            cpu: cpu_percent,
-            memory: (mem.PeakWorkingSetSize as u64) / 1024,
-            uptime: utils::safe_unsigned_sub(now, self.started),
-            cores: 1,
+        let io = IoStats {
+            pid,
+            polled,
+            monotonic: now,
+            duration,
+            rates,
+            ..counters
         };
 
-        self.history.set_last(&kind, stats.clone());
-        Ok(stats)
+        self.history.set_io(pid, io.clone());
+        Ok(io)
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across `count` CPU core(s).
-    /// If `None` is provided then all available CPU cores will be considered, where applicable.
+    /// Get a breakdown of the memory mapped by the current process with `StatType::Process`, or by another process
+    /// with `StatType::Pid`, including the proportional (PSS) and unique (USS) set sizes. This does not depend on or
+    /// modify the stats history.
+    ///
+    /// This is currently only implemented on Linux. Reading another process requires the same permissions as `ptrace`.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
-    /// // read stats across all available CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, None).unwrap();
+    /// let memory = spork.memory_breakdown(StatType::Process).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// println!("RSS: {} bytes, PSS: {} bytes, USS: {} bytes", memory.rss, memory.pss, memory.uss);
+    /// ```
+    pub fn memory_breakdown(&self, kind: StatType) -> Result<MemoryBreakdown, SporkError> {
+        let pid = process_pid(&kind, "Memory breakdowns are only available for processes.")?;
+        self.backend.memory_breakdown(pid)
+    }
+
+    /// Get the breakdown of every memory mapping of the current process with `StatType::Process`, or of another
+    /// process with `StatType::Pid`. This reads the whole of `/proc/<pid>/smaps`, so it is much slower than
+    /// `memory_breakdown` for processes with many mappings.
     ///
-    /// // read stats considering only 2 CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, Some(2));
+    /// This is currently only implemented on Linux. Reading another process requires the same permissions as `ptrace`.
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
     /// ```
-    #[cfg(windows)]
-    pub fn stats_with_cpus(&self, kind: StatType, cores: Option<usize>) -> Result<Stats, SporkError> {
-        let cores = match cores {
-            Some(c) => c,
-            None => self.cpus,
-        };
+    /// let spork = Spork::new().unwrap();
+    ///
+    /// for mapping in spork.memory_maps(StatType::Process).unwrap() {
+    ///   println!("{:?}: PSS {} bytes", mapping.path, mapping.memory.pss);
+    /// }
+    /// ```
+    pub fn memory_maps(&self, kind: StatType) -> Result<Vec<MemoryMapping>, SporkError> {
+        let pid = process_pid(&kind, "Memory maps are only available for processes.")?;
+        self.backend.memory_maps(pid)
+    }
 
-        if cores > self.cpus {
-            return Err(SporkError::new_borrowed(SporkErrorKind::Unknown, "Invalid CPU count."));
-        }
-        let now = utils::now_ms();
-        let duration = utils::calc_duration(&kind, &self.history, self.started, now);
+    /// Get CPU usage for every thread in the current process, without needing to call `stats` from each thread. CPU
+    /// load is calculated over the interval since the previous snapshot, or since the `Spork` instance was created for
+    /// threads seen for the first time. Snapshots keep their own history and do not interfere with `stats`.
+    ///
+    /// This is currently only implemented on Linux.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    ///
+    /// for thread in spork.thread_snapshot().unwrap() {
+    ///   println!("{} ({}): {:?}% CPU, state {}", thread.name, thread.tid, thread.cpu, thread.state);
+    /// }
+    /// ```
+    pub fn thread_snapshot(&self) -> Result<Vec<ThreadStats>, SporkError> {
+        let polled = self.backend.now_ms();
+        let now = self.backend.now();
+
+        let threads: Vec<ThreadStats> = self
+            .backend
+            .thread_times()?
+            .into_iter()
+            .map(|thread| {
+                let duration = match self.history.get_task(thread.tid) {
+                    Some(last) => now.saturating_sub(last.monotonic),
+                    None => now.saturating_sub(self.started),
+                };
+                let cpu_time = thread.user_time + thread.system_time;
+
+                ThreadStats {
+                    cpu: utils::calc_task_cpu_percent(&self.history, thread.tid, cpu_time, duration),
+                    polled,
+                    monotonic: now,
+                    duration,
+                    ..thread
+                }
+            })
+            .collect();
+
+        self.history.set_tasks(&threads);
+        Ok(threads)
+    }
+
+    /// Get the live CPU time and memory usage of the process `pid`, or of the current process if `None`, along with
+    /// every one of its descendants, found by following each process' parent pid. This does not depend on or modify
+    /// the stats history.
+    ///
+    /// This is currently only implemented on Linux.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    /// let tree = spork.process_tree(None).unwrap();
+    ///
+    /// println!("Tree CPU time: {}s, RSS: {} bytes", tree.cpu_time, tree.rss);
+    /// for process in tree.descendants() {
+    ///   println!("{}{} ({}): {}s, {} bytes", "  ".repeat(process.depth), process.name, process.pid,
+    ///     process.cpu_time, process.rss);
+    /// }
+    /// ```
+    pub fn process_tree(&self, pid: Option<u32>) -> Result<ProcessTree, SporkError> {
+        let root = pid.unwrap_or_else(std::process::id);
+
+        ProcessTree::from_processes(root, self.backend.processes()?).ok_or_else(|| {
+            SporkError::new(
+                SporkErrorKind::ProcessNotFound,
+                format!("No process with pid {}.", root),
+            )
+        })
+    }
+
+    /// Register the calling thread so that its CPU usage can be read from any other thread with `stats_for_thread`, such
+    /// as from a scheduler checking whether a worker is spinning. See `ThreadHandle` for how long the handle is valid.
+    ///
+    /// This is currently only implemented on Linux.
+    ///
+    /// ```
+    /// let spork = Arc::new(Spork::new().unwrap());
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let worker = spork.clone();
+    /// thread::spawn(move || {
+    ///   tx.send(worker.register_current_thread("worker").unwrap()).unwrap();
+    ///   // ...
+    /// });
+    ///
+    /// let handle = rx.recv().unwrap();
+    /// println!("{}: {:?}% CPU", handle.name(), spork.stats_for_thread(&handle).unwrap().cpu);
+    /// ```
+    pub fn register_current_thread<T: Into<String>>(&self, name: T) -> Result<ThreadHandle, SporkError> {
+        Ok(ThreadHandle {
+            name: name.into(),
+            clock: self.backend.register_thread()?,
+        })
+    }
 
-        let cpu_times = windows::get_cpu_times(&kind)?;
-        let cpu_time = windows::combine_cpu_times(&cpu_times);
-        let mem = windows::get_mem_stats(&kind)?;
+    /// Get CPU and memory statistics for a thread registered with `register_current_thread`, assuming usage across only
+    /// 1 CPU core. This can be called from any thread, and the history is kept per registered thread rather than per
+    /// calling thread. The returned `Stats` have the kind `StatType::Thread`.
+    ///
+    /// This is currently only implemented on Linux.
+    pub fn stats_for_thread(&self, handle: &ThreadHandle) -> Result<Stats, SporkError> {
+        let polled = self.backend.now_ms();
+        let now = self.backend.now();
+        let raw = match self.backend.thread_clock_stats(&handle.clock) {
+            Ok(raw) => raw,
+            Err(e) => {
+                if *e.kind() == SporkErrorKind::ProcessNotFound {
+                    self.history.clear_handle(&handle.clock);
+                }
+                return Err(e);
+            }
+        };
 
-        let cpu_percent = utils::calc_cpu_percent(&self.history, &kind, cpu_time, duration);
+        let last = self.history.get_handle(&handle.clock);
+        let duration = match last {
+            Some(ref last) => now.saturating_sub(last.monotonic),
+            None => now.saturating_sub(self.started),
+        };
+        let (prev_cpu_time, extended_delta) = match last {
+            Some(ref last) => (last.cpu_time, raw.extended.delta(&last.extended)),
+            None => (0_f64, raw.extended.clone()),
+        };
 
         let stats = Stats {
-            kind: kind.clone(),
-            polled: now,
-            duration: duration,
-            cpu_time: cpu_time,
-            cpu: cpu_percent,
-            memory: (mem.PeakWorkingSetSize as u64) / 1024,
-            uptime: utils::safe_unsigned_sub(now, self.started),
-            cores: cores,
+            kind: StatType::Thread,
+            polled,
+            monotonic: now,
+            duration,
+            cpu_time: raw.cpu_time,
+            cpu: utils::cpu_percent(raw.cpu_time - prev_cpu_time, duration),
+            memory: raw.memory,
+            uptime: utils::calc_uptime(self.started, now),
+            cores: 1,
+            extended: raw.extended,
+            extended_delta,
         };
 
-        self.history.set_last(&kind, stats.clone());
+        self.history.set_handle(&handle.clock, stats.clone());
         Ok(stats)
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across only 1 CPU core.
+    /// Run `func` on the current thread and measure the resources it used. See `measure::MeasureGuard` for how the
+    /// returned `Stats` are calculated. This does not depend on or modify the stats history.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
-    /// let stats = spork.stats(StatType::Thread).unwrap();
+    /// let (sum, stats) = spork.measure(|| (0..1000_u64).sum::<u64>()).unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// println!("Sum: {}, CPU time: {}s, Wall time: {:?}, Memory growth: {} bytes",
+    ///   sum, stats.cpu_time, stats.duration, stats.memory);
     /// ```
-    #[cfg(all(feature = "compile_unimplemented", not(any(unix, windows, target_os = "macos"))))]
-    pub fn stats(&self, kind: StatType) -> Result<Stats, SporkError> {
-        Err(SporkError::unimplemented())
+    pub fn measure<F, R>(&self, func: F) -> Result<(R, Stats), SporkError>
+    where
+        F: FnOnce() -> R,
+    {
+        let guard = self.measure_guard()?;
+        let result = func();
+        Ok((result, guard.finish()?))
     }
 
-    /// Get CPU and memory statistics in a `Stats` instance for the provided `StatType` assuming usage across `count` CPU core(s).
-    /// If `None` is provided then all available CPU cores will be considered, where applicable.
+    /// Start measuring the resources used by the current thread until the returned guard is finished or dropped. This
+    /// does not depend on or modify the stats history.
     ///
     /// ```
     /// let spork = Spork::new().unwrap();
-    /// // read stats across all available CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, None).unwrap();
-    ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// let guard = spork.measure_guard().unwrap();
+    /// // ...
+    /// let stats = guard.finish().unwrap();
+    /// ```
+    pub fn measure_guard(&self) -> Result<measure::MeasureGuard<'_>, SporkError> {
+        measure::MeasureGuard::new(self)
+    }
+
+    /// Spawn a thread named `name` that runs `func` and reports its lifetime resource usage when joined, reading from
+    /// this instance's backend. See `thread::spawn` for how the returned `Stats` are calculated. This does not depend on
+    /// or modify the stats history.
     ///
-    /// // read stats considering only 2 CPU cores
-    /// let stats = spork.stats_with_cpus(StatType::Thread, Some(2));
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    /// let handle = spork.spawn("worker", || (0..1000_u64).sum::<u64>()).unwrap();
+    /// let (sum, stats) = handle.join().unwrap();
     ///
-    /// println!("CPU: {}%, Memory: {} bytes, Cores: {}, Type: {}, Polled at: {}",
-    ///   stats.cpu, stats.memory, stats.cores, stats.kind, stats.polled);
+    /// println!("Sum: {}, CPU time: {}s, Wall time: {:?}", sum, stats.cpu_time, stats.duration);
     /// ```
-    #[cfg(all(feature = "compile_unimplemented", not(any(unix, windows, target_os = "macos"))))]
-    pub fn stats_with_cpus(&self, kind: StatType, cores: Option<usize>) -> Result<Stats, SporkError> {
-        Err(SporkError::unimplemented())
+    pub fn spawn<N, F, T>(&self, name: N, func: F) -> Result<thread::JoinHandle<T>, SporkError>
+    where
+        N: Into<String>,
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        thread::spawn_with(self.backend.clone(), self.started, name.into(), func)
     }
 
     /// Get the system type.
@@ -573,10 +1451,71 @@ impl Spork {
         self.cpus
     }
 
+    /// Get the layout of the system's logical CPUs: their packages, physical cores, SMT siblings, caches, NUMA nodes and
+    /// frequencies. This is read on every call, so the current frequencies and online CPUs are up to date. See
+    /// `topology::CpuTopology::normalize_cpu` to normalize CPU loads by physical cores instead of logical ones.
+    ///
+    /// This is currently only implemented on Linux.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    /// let topology = spork.cpu_topology().unwrap();
+    ///
+    /// for cpu in topology.online() {
+    ///   println!("CPU {}: package {:?}, core {:?}, siblings {:?}, {:?} Hz", cpu.id, cpu.package, cpu.core,
+    ///     cpu.siblings, cpu.frequency.as_ref().and_then(|f| f.current));
+    /// }
+    /// ```
+    pub fn cpu_topology(&self) -> Result<topology::CpuTopology, SporkError> {
+        self.backend.cpu_topology()
+    }
+
+    /// Get the CPU quota of the process' cgroup as a number of cores, or `None` if there is no quota. This is read once
+    /// when the `Spork` instance is created.
+    pub fn cpu_limit(&self) -> Option<f64> {
+        self.cpu_limit
+    }
+
+    /// Get the number of CPU cores the process can run on at once: the cgroup CPU quota rounded up to a whole core, if
+    /// one is set, or `num_cores` otherwise. This is the `Stats.cores` reported by `stats_with_cpus` when `None` is
+    /// provided. See `cpu_capacity` for the fractional quota the CPU load is normalized by.
+    pub fn effective_cores(&self) -> usize {
+        match self.cpu_limit {
+            Some(limit) => (limit.ceil() as usize).clamp(1, self.cpus.max(1)),
+            None => self.cpus,
+        }
+    }
+
+    /// Get the CPU time the process can use per second of wall time under its cgroup CPU quota, in cores, such as 1.5,
+    /// or `None` if there is no quota. Quotas above `num_cores` are capped to it. `stats_with_cpus` divides the CPU load
+    /// by this when `None` is provided.
+    pub fn cpu_capacity(&self) -> Option<f64> {
+        match self.cpu_limit {
+            Some(limit) if limit > 0_f64 => Some(limit.min(self.cpus.max(1) as f64)),
+            _ => None,
+        }
+    }
+
+    /// Get the resource limits, memory usage and CPU throttling counters of the process' cgroup. Unlike
+    /// `cpu_limit`, this reads the current values on every call.
+    ///
+    /// This is currently only implemented on Linux.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    /// let cgroup = spork.cgroup_stats().unwrap();
+    ///
+    /// println!("CPU limit: {:?} cores, Memory: {} of {:?} bytes, Throttled: {} periods",
+    ///   cgroup.cpu_limit, cgroup.memory_usage, cgroup.memory_limit, cgroup.nr_throttled);
+    /// ```
+    pub fn cgroup_stats(&self) -> Result<CgroupStats, SporkError> {
+        self.backend.cgroup_stats()
+    }
+
     /// Clear the stats history for the process or calling thread. This library works by tracking the timestamp of the last stats poll, per thread, such that polls from different threads do not interfere with each other.
     /// However, the downside to this approach is that some extra data has to stick around. This function will delete the timestamp of the previous poll for the process or calling thread, and if the same thread
     /// decides to call `stats` or `stats_with_cpus` again it will use the process' uptime as the duration over which to calculate CPU usage for the next call.
-    /// If a `Spork` instance is shared among multiple threads with short lifespans then it's a good idea to call this when those threads exit.
+    /// The history of threads that have exited is removed automatically, see `prune_history`.
     pub fn drop_history(&self, kind: StatType) -> Option<Stats> {
         self.history.clear_last(&kind)
     }
@@ -585,6 +1524,93 @@ impl Spork {
     pub fn read_history(&self, kind: StatType) -> Option<Stats> {
         self.history.get_last(&kind)
     }
+
+    /// Clear the stats history kept for a registered thread by `stats_for_thread`. Call this once the thread has exited.
+    pub fn drop_thread_history(&self, handle: &ThreadHandle) -> Option<Stats> {
+        self.history.clear_handle(&handle.clock)
+    }
+
+    /// Remove the history of every thread that has exited, returning the number of entries removed. This covers the
+    /// `StatType::Thread` and `StatType::Children` history of exited threads, and the history of registered threads
+    /// that `stats_for_thread` can no longer read. The history of `StatType::Pid` is kept until it is dropped with
+    /// `drop_history`.
+    ///
+    /// Exited threads are also pruned automatically whenever a new thread is polled, and a registered thread's history
+    /// is dropped when `stats_for_thread` finds that it has exited, so this is only needed to reclaim the memory
+    /// sooner, such as after a burst of short-lived threads.
+    pub fn prune_history(&self) -> usize {
+        let handles = self
+            .history
+            .get_handle_clocks()
+            .into_iter()
+            .filter(|clock| match self.backend.thread_clock_stats(clock) {
+                Err(e) => *e.kind() == SporkErrorKind::ProcessNotFound,
+                Ok(_) => false,
+            })
+            .filter(|clock| self.history.clear_handle(clock).is_some())
+            .count();
+
+        self.history.prune_threads() + handles
+    }
+
+    /// Count the entries in the stats history, which can be used to check that the history isn't growing without bound.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap();
+    /// spork.stats(StatType::Thread).unwrap();
+    ///
+    /// println!("History entries: {}", spork.history_size().total());
+    /// ```
+    pub fn history_size(&self) -> HistorySize {
+        self.history.size()
+    }
+
+    /// Keep a rolling window of recent polls for each `StatType` (and thread, for `StatType::Thread` and
+    /// `StatType::Children`) according to `config`, rather than only the most recent poll. Polls already in the history
+    /// are kept if they fit in the new window.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap().with_window(WindowConfig::new(120).with_max_age(Duration::from_secs(60)));
+    /// ```
+    pub fn with_window(mut self, config: window::WindowConfig) -> Spork {
+        self.history.set_window(config);
+        self
+    }
+
+    /// Read a copy of the rolling window of polls for `kind`, which can be summarized with the mean, min, max, EWMA and
+    /// percentiles of the CPU load and memory usage. Without `with_window` this only holds the most recent poll.
+    ///
+    /// ```
+    /// let spork = Spork::new().unwrap().with_window(WindowConfig::new(60));
+    /// // ... poll `stats` periodically
+    /// let window = spork.window(StatType::Process);
+    ///
+    /// println!("Smoothed CPU: {:?}%, peak memory: {:?} bytes", window.ewma(Metric::Cpu), window.max(Metric::Memory));
+    /// ```
+    pub fn window(&self, kind: StatType) -> window::Window {
+        window::Window::new(
+            self.history.get_window(&kind),
+            self.history.window_config().ewma_alpha(),
+        )
+    }
+
+    /// Read a copy of the rolling window of polls for a thread registered with `register_current_thread`, as polled by
+    /// `stats_for_thread`.
+    pub fn thread_window(&self, handle: &ThreadHandle) -> window::Window {
+        window::Window::new(
+            self.history.get_handle_window(&handle.clock),
+            self.history.window_config().ewma_alpha(),
+        )
+    }
+}
+
+/// Read the pid of a process `StatType`, or `None` for the current process.
+fn process_pid(kind: &StatType, details: &'static str) -> Result<Option<u32>, SporkError> {
+    match *kind {
+        StatType::Process => Ok(None),
+        StatType::Pid(pid) => Ok(Some(pid)),
+        _ => Err(SporkError::new_borrowed(SporkErrorKind::InvalidStatType, details)),
+    }
 }
 
 // ---------------------
@@ -672,16 +1698,48 @@ mod tests {
 
     #[test]
     fn should_create_error_from_io_error() {
-        let err = io::Error::last_os_error();
+        let err = io::Error::from_raw_os_error(libc::ENOENT);
         let error: SporkError = err.into();
         match *error.kind() {
-            SporkErrorKind::Unknown => {
-                assert!(true);
+            SporkErrorKind::Io => {
+                assert_eq!(error.errno(), Some(libc::ENOENT));
+                assert!(std::error::Error::source(&error).is_some());
             }
-            _ => panic!("Invalid unimplemented error! {:?}. Expected Unimplemented", error),
+            _ => panic!("Invalid io error! {:?}. Expected Io", error),
         };
     }
 
+    #[test]
+    fn should_map_io_error_kinds() {
+        let error: SporkError = io::Error::from_raw_os_error(libc::EACCES).into();
+        assert_eq!(error.kind(), &SporkErrorKind::PermissionDenied);
+        assert_eq!(
+            error.to_string(),
+            format!("Permission Denied: {}", io::Error::from_raw_os_error(libc::EACCES))
+        );
+
+        let error: SporkError = io::Error::new(io::ErrorKind::InvalidData, "bad").into();
+        assert_eq!(error.kind(), &SporkErrorKind::Parse);
+        assert_eq!(error.errno(), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_map_esrch_to_process_not_found() {
+        let error: SporkError = io::Error::from_raw_os_error(libc::ESRCH).into();
+        assert_eq!(error.kind(), &SporkErrorKind::ProcessNotFound);
+        assert_eq!(error.errno(), Some(libc::ESRCH));
+    }
+
+    #[test]
+    fn should_compare_errors_without_source() {
+        let error = SporkError::new_borrowed(SporkErrorKind::Io, "foo").with_errno(5);
+        let with_source = error.clone().with_source(io::Error::from_raw_os_error(5));
+
+        assert_eq!(error, with_source);
+        assert_ne!(error, SporkError::new_borrowed(SporkErrorKind::Io, "foo"));
+    }
+
     #[test]
     fn should_create_spork() {
         if let Err(e) = Spork::new() {
@@ -689,6 +1747,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_be_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Spork>();
+    }
+
+    #[test]
+    fn should_calc_cpu_usage_from_times() {
+        let prev = CpuTimes {
+            user: 100,
+            idle: 100,
+            ..Default::default()
+        };
+        let curr = CpuTimes {
+            user: 130,
+            system: 10,
+            idle: 150,
+            iowait: 10,
+            ..Default::default()
+        };
+
+        let usage = CpuUsage::from_delta(&curr.delta(&prev));
+        assert_eq!(usage.user, 30.0);
+        assert_eq!(usage.system, 10.0);
+        assert_eq!(usage.idle, 50.0);
+        assert_eq!(usage.iowait, 10.0);
+        assert_eq!(usage.busy(), 40.0);
+        assert_eq!(CpuUsage::from_delta(&prev.delta(&prev)), CpuUsage::default());
+    }
+
+    #[test]
+    fn should_calc_io_rates() {
+        let prev = IoStats {
+            rchar: 1000,
+            write_bytes: 500,
+            ..IoStats::new_empty(None)
+        };
+        let curr = IoStats {
+            rchar: 3000,
+            write_bytes: 100,
+            ..IoStats::new_empty(None)
+        };
+
+        let rates = IoRates::from_delta(&curr, &prev, Duration::from_secs(2)).unwrap();
+        assert_eq!(rates.rchar, 1000.0);
+        assert_eq!(rates.write_bytes, 0.0);
+        assert!(IoRates::from_delta(&curr, &prev, Duration::ZERO).is_none());
+    }
+
     #[test]
     fn should_get_cpu_cores() {
         let spork = Spork::new().unwrap();
@@ -745,6 +1852,45 @@ mod tests {
         assert_eq!(spork.drop_history(StatType::Children), None);
     }
 
+    #[test]
+    fn should_build_process_tree() {
+        let process = |pid, ppid, cpu_time, rss| TreeProcess {
+            pid,
+            ppid,
+            name: format!("proc-{}", pid),
+            depth: 0,
+            cpu_time,
+            children_cpu_time: 0.0,
+            rss,
+        };
+        let processes = vec![
+            process(1, 0, 100.0, 1000),
+            process(30, 20, 1.5, 30),
+            process(10, 1, 2.0, 100),
+            process(21, 10, 0.25, 21),
+            process(20, 10, 0.5, 20),
+            process(40, 1, 9.0, 400),
+        ];
+
+        let tree = ProcessTree::from_processes(10, processes.clone()).unwrap();
+        let pids: Vec<(u32, usize)> = tree.processes.iter().map(|p| (p.pid, p.depth)).collect();
+        assert_eq!(pids, vec![(10, 0), (20, 1), (21, 1), (30, 2)]);
+        assert_eq!(tree.cpu_time, 4.25);
+        assert_eq!(tree.rss, 171);
+        assert_eq!(tree.descendants().count(), 3);
+
+        // only the children reaped by descendants count towards the total
+        let mut reaped = processes.clone();
+        reaped[0].children_cpu_time = 50.0;
+        reaped[2].children_cpu_time = 8.0;
+        reaped[1].children_cpu_time = 1.0;
+        assert_eq!(ProcessTree::from_processes(10, reaped).unwrap().cpu_time, 5.25);
+
+        let leaf = ProcessTree::from_processes(30, processes.clone()).unwrap();
+        assert_eq!(leaf.processes.len(), 1);
+        assert_eq!(ProcessTree::from_processes(50, processes), None);
+    }
+
     #[test]
     #[cfg(windows)]
     fn should_get_windows_stats_with_cpus() {}
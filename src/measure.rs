@@ -0,0 +1,198 @@
+//! Measure the resources used by the current thread while running a region of code.
+//!
+//! Measurements are taken from the thread's own counters and never read or write the `Spork` history, so they can be
+//! freely mixed with periodic calls to `Spork::stats`.
+//!
+//! ```
+//! use spork::Spork;
+//!
+//! let spork = Spork::new().unwrap();
+//!
+//! let (sum, stats) = spork.measure(|| (0..1_000_000_u64).sum::<u64>()).unwrap();
+//! println!("Sum {} took {:?} and {}s of CPU time", sum, stats.duration, stats.cpu_time);
+//!
+//! {
+//!   let _guard = spork.measure_guard().unwrap().on_drop(|stats| println!("Block stats: {:?}", stats));
+//!   // ...
+//! }
+//! ```
+
+use std::marker::PhantomData;
+
+use super::*;
+
+/// The thread counters read at the start and end of a measurement.
+#[derive(Clone, Debug)]
+struct Checkpoint {
+    monotonic: Duration,
+    cpu_time: f64,
+    memory: u64,
+    extended: ExtendedStats,
+}
+
+fn checkpoint(spork: &Spork) -> Result<Checkpoint, SporkError> {
+    let raw = spork.backend.stats(&StatType::Thread)?;
+    // fall back to the peak memory where the current resident set size isn't available
+    let memory = spork.backend.rss().unwrap_or(raw.memory);
+
+    Ok(Checkpoint {
+        monotonic: spork.backend.now(),
+        cpu_time: raw.cpu_time,
+        memory,
+        extended: raw.extended,
+    })
+}
+
+/// Measures the resources used by the current thread between the guard's creation and when it is finished or dropped.
+///
+/// The resulting `Stats` have the `StatType::Thread` kind and describe only the measured region:
+///
+/// * `duration` is the elapsed monotonic time.
+/// * `cpu_time` is the CPU time used by the thread, in seconds, and `cpu` is the load over `duration`.
+/// * `memory` is the growth of the process resident set size, in bytes, or 0 if it shrank. Where the backend can't
+///   read the current resident set size, such as on OS X, this is the growth of the peak memory instead.
+/// * `extended` and `extended_delta` both hold the change in the thread's counters.
+///
+/// CPU time is read from the thread that created the guard, so a guard can not be sent to another thread.
+pub struct MeasureGuard<'a> {
+    spork: &'a Spork,
+    start: Checkpoint,
+    on_drop: Option<Box<dyn FnOnce(Stats) + 'a>>,
+    // thread CPU clocks are only meaningful on the thread that read them
+    _thread: PhantomData<*const ()>,
+}
+
+impl<'a> MeasureGuard<'a> {
+    pub(crate) fn new(spork: &'a Spork) -> Result<MeasureGuard<'a>, SporkError> {
+        Ok(MeasureGuard {
+            spork,
+            start: checkpoint(spork)?,
+            on_drop: None,
+            _thread: PhantomData,
+        })
+    }
+
+    /// Call `callback` with the measured stats when the guard is dropped. Errors reading the stats on drop are ignored,
+    /// and the callback is not called if the guard is finished with `finish` instead.
+    pub fn on_drop<F>(mut self, callback: F) -> MeasureGuard<'a>
+    where
+        F: FnOnce(Stats) + 'a,
+    {
+        self.on_drop = Some(Box::new(callback));
+        self
+    }
+
+    /// Read the stats for the region measured so far without finishing the measurement.
+    pub fn elapsed(&self) -> Result<Stats, SporkError> {
+        Ok(calc_stats(self.spork, &self.start, &checkpoint(self.spork)?))
+    }
+
+    /// Finish the measurement and return the stats for the region, skipping any `on_drop` callback.
+    pub fn finish(mut self) -> Result<Stats, SporkError> {
+        self.on_drop = None;
+        self.elapsed()
+    }
+}
+
+impl Drop for MeasureGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(callback) = self.on_drop.take() {
+            if let Ok(stats) = self.elapsed() {
+                callback(stats);
+            }
+        }
+    }
+}
+
+fn calc_stats(spork: &Spork, start: &Checkpoint, end: &Checkpoint) -> Stats {
+    let duration = end.monotonic.saturating_sub(start.monotonic);
+    let cpu_time = (end.cpu_time - start.cpu_time).max(0_f64);
+    let extended = end.extended.delta(&start.extended);
+
+    Stats {
+        kind: StatType::Thread,
+        polled: spork.backend.now_ms(),
+        monotonic: end.monotonic,
+        duration,
+        cpu_time,
+        cpu: utils::cpu_percent(cpu_time, duration),
+        memory: end.memory.saturating_sub(start.memory),
+        uptime: utils::calc_uptime(spork.started, end.monotonic),
+        cores: 1,
+        extended: extended.clone(),
+        extended_delta: extended,
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint_at(monotonic: Duration, cpu_time: f64, memory: u64, minor_faults: u64) -> Checkpoint {
+        Checkpoint {
+            monotonic,
+            cpu_time,
+            memory,
+            extended: ExtendedStats {
+                minor_faults,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn should_calc_region_stats() {
+        let spork = Spork::new().unwrap();
+        let start = checkpoint_at(Duration::from_secs(1), 2.0, 4096, 10);
+        let end = checkpoint_at(Duration::from_secs(3), 3.0, 8192, 15);
+
+        let stats = calc_stats(&spork, &start, &end);
+        assert_eq!(stats.kind, StatType::Thread);
+        assert_eq!(stats.duration, Duration::from_secs(2));
+        assert_eq!(stats.cpu_time, 1.0);
+        assert_eq!(stats.cpu, Some(50.0));
+        assert_eq!(stats.memory, 4096);
+        assert_eq!(stats.extended.minor_faults, 5);
+        assert_eq!(stats.extended, stats.extended_delta);
+    }
+
+    #[test]
+    fn should_not_underflow_when_memory_shrinks() {
+        let spork = Spork::new().unwrap();
+        let start = checkpoint_at(Duration::from_secs(1), 2.0, 8192, 10);
+        let end = checkpoint_at(Duration::from_secs(1), 2.0, 4096, 10);
+
+        let stats = calc_stats(&spork, &start, &end);
+        assert_eq!(stats.memory, 0);
+        assert_eq!(stats.cpu, None);
+    }
+
+    #[test]
+    fn should_measure_with_mock_backend() {
+        let backend = backend::MockBackend::new();
+        let spork = Spork::with_backend(backend.clone()).unwrap();
+        backend.set_memory_stats(Ok(MemoryStats {
+            rss: 1024,
+            ..Default::default()
+        }));
+
+        let (_, stats) = spork
+            .measure(|| {
+                backend.clock().advance(Duration::from_secs(2));
+                backend.add_cpu_time(&StatType::Thread, 0.5);
+                backend.set_memory_stats(Ok(MemoryStats {
+                    rss: 4096,
+                    ..Default::default()
+                }));
+            })
+            .unwrap();
+
+        assert_eq!(stats.duration, Duration::from_secs(2));
+        assert_eq!(stats.cpu_time, 0.5);
+        assert_eq!(stats.cpu, Some(25.0));
+        assert_eq!(stats.memory, 3072);
+        assert_eq!(stats.uptime, 2000);
+    }
+}
@@ -1,30 +1,26 @@
+#[allow(clippy::single_component_path_imports)]
 use libc;
 use libc::rusage;
 use libc::timespec;
 use libc::timeval;
-use libc::{CLOCK_THREAD_CPUTIME_ID, EFAULT, EINVAL, EPERM, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD};
+use libc::{CLOCK_THREAD_CPUTIME_ID, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD};
 
 use super::*;
 
+use backend::{RawStats, StatsBackend, SystemCpuTimes, ThreadClock};
+use topology::CpuTopology;
 use utils::empty_timespec;
 use utils::CpuTime;
 
+// the POSIX functions used here return -1 and set errno on failure
 fn map_posix_resp(code: i32) -> Result<i32, SporkError> {
-    match code {
-        EFAULT => Err(SporkError::new_borrowed(
-            SporkErrorKind::Unknown,
-            "Invalid timespec address space.",
-        )),
-        EINVAL => Err(SporkError::new_borrowed(SporkErrorKind::Unknown, "Invalid clock ID.")),
-        EPERM => Err(SporkError::new_borrowed(
-            SporkErrorKind::Unknown,
-            "Invalid clock permissions.",
-        )),
-        _ => Ok(code),
+    if code == -1 {
+        Err(SporkError::last_os_error())
+    } else {
+        Ok(code)
     }
 }
 
-#[allow(dead_code)]
 pub fn get_clock_ticks() -> Result<CLong, SporkError> {
     Ok(unsafe { libc::sysconf(libc::_SC_CLK_TCK) })
 }
@@ -47,17 +43,107 @@ pub fn timespec_to_timeval(times: &timespec) -> timeval {
 // this should always be called before get_stats since they both consume the clock
 pub fn get_thread_cpu_time() -> Result<timespec, SporkError> {
     let mut times = empty_timespec();
-    let _ = map_posix_resp(unsafe {
-        libc::clock_gettime(CLOCK_THREAD_CPUTIME_ID, &mut times) })?;
+    let _ = map_posix_resp(unsafe { libc::clock_gettime(CLOCK_THREAD_CPUTIME_ID, &mut times) })?;
 
     Ok(times)
 }
 
+// the tid identifies the thread in /proc while the clock is only valid for as long as the thread is alive
+pub fn register_thread() -> Result<ThreadClock, SporkError> {
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as u32;
+    let mut clock_id: libc::clockid_t = 0;
+
+    // pthread functions return the error number rather than setting errno
+    let ret = unsafe { libc::pthread_getcpuclockid(libc::pthread_self(), &mut clock_id) };
+    if ret != 0 {
+        return Err(IoError::from_raw_os_error(ret).into());
+    }
+
+    Ok(ThreadClock {
+        tid,
+        clock_id: clock_id as i64,
+    })
+}
+
+fn thread_exited(tid: u32, error: IoError) -> SporkError {
+    let errno = error.raw_os_error().unwrap_or(libc::ESRCH);
+    SporkError::new(SporkErrorKind::ProcessNotFound, format!("Thread {} has exited.", tid))
+        .with_errno(errno)
+        .with_source(error)
+}
+
+fn read_task_file(tid: u32, file: &str) -> Result<String, SporkError> {
+    match std::fs::read_to_string(procfs::proc_path(None, &format!("task/{}/{}", tid, file))) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESRCH) => {
+            Err(thread_exited(tid, e))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// getrusage can't read other threads, so read their CPU clock directly and the remaining counters from /proc
+pub fn get_thread_clock_stats(clock: &ThreadClock) -> Result<RawStats, SporkError> {
+    let mut times = empty_timespec();
+    if unsafe { libc::clock_gettime(clock.clock_id as libc::clockid_t, &mut times) } == -1 {
+        let error = IoError::last_os_error();
+        return Err(match error.raw_os_error() {
+            Some(libc::EINVAL) | Some(libc::ESRCH) => thread_exited(clock.tid, error),
+            _ => error.into(),
+        });
+    }
+
+    let stat = procfs::parse_stat(&read_task_file(clock.tid, "stat")?)?;
+    let status = read_task_file(clock.tid, "status")?;
+    let status = procfs::parse_status(&status);
+
+    Ok(RawStats {
+        cpu_time: times.tv_sec as f64 + (times.tv_nsec as f64 / 1_000_000_000_f64),
+        // VmHWM is shared by every thread, so this matches the peak RSS reported for StatType::Thread
+        memory: procfs::status_kb_to_bytes(&status, "VmHWM")?,
+        extended: ExtendedStats {
+            minor_faults: stat.minflt,
+            major_faults: stat.majflt,
+            voluntary_ctx_switches: procfs::status_u64(&status, "voluntary_ctxt_switches")?,
+            involuntary_ctx_switches: procfs::status_u64(&status, "nonvoluntary_ctxt_switches")?,
+            ..ExtendedStats::default()
+        },
+    })
+}
+
+pub fn ticks_to_timeval(ticks: u64, ticks_per_sec: u64) -> timeval {
+    timeval {
+        tv_sec: (ticks / ticks_per_sec) as libc::time_t,
+        tv_usec: ((ticks % ticks_per_sec) * 1000000 / ticks_per_sec) as libc::suseconds_t,
+    }
+}
+
+// getrusage only works on the calling process, so build the same record from /proc for other processes
+pub fn get_pid_stats(pid: u32) -> Result<rusage, SporkError> {
+    let stat = procfs::get_stat(Some(pid))?;
+    let status = procfs::read_proc_file(Some(pid), "status")?;
+    let status = procfs::parse_status(&status);
+    let ticks = get_clock_ticks()?.max(1) as u64;
+
+    // SAFETY: rusage is a plain C struct for which all zeroes is a valid value.
+    let mut usage: rusage = unsafe { std::mem::zeroed() };
+    usage.ru_utime = ticks_to_timeval(stat.utime, ticks);
+    usage.ru_stime = ticks_to_timeval(stat.stime, ticks);
+    usage.ru_maxrss = (procfs::status_kb_to_bytes(&status, "VmHWM")? / 1024) as _;
+    usage.ru_minflt = stat.minflt as _;
+    usage.ru_majflt = stat.majflt as _;
+    usage.ru_nvcsw = procfs::status_u64(&status, "voluntary_ctxt_switches")? as _;
+    usage.ru_nivcsw = procfs::status_u64(&status, "nonvoluntary_ctxt_switches")? as _;
+
+    Ok(usage)
+}
+
 pub fn get_stats(kind: &StatType) -> Result<rusage, SporkError> {
     let (t_times, code): (Option<timespec>, i32) = match *kind {
         StatType::Process => (None, RUSAGE_SELF),
         StatType::Children => (None, RUSAGE_CHILDREN),
         StatType::Thread => (Some(get_thread_cpu_time()?), RUSAGE_THREAD),
+        StatType::Pid(pid) => return get_pid_stats(pid),
     };
 
     let (getrusage_ret, usage) = unsafe {
@@ -70,6 +156,7 @@ pub fn get_stats(kind: &StatType) -> Result<rusage, SporkError> {
 
     // SAFETY: ret was checked to not be an error.
     let mut usage = unsafe { usage.assume_init() };
+    #[allow(clippy::unnecessary_unwrap)]
     if t_times.is_some() {
         // use clock_gettime results for threads
         usage.ru_utime = timespec_to_timeval(&t_times.unwrap());
@@ -87,6 +174,107 @@ pub fn get_cpu_time(val: &rusage) -> f64 {
     (times.sec as f64) + (times.usec as f64 / 1000000_f64)
 }
 
+pub fn get_extended_stats(val: &rusage) -> ExtendedStats {
+    ExtendedStats {
+        minor_faults: val.ru_minflt.wrapping_abs() as u64,
+        major_faults: val.ru_majflt.wrapping_abs() as u64,
+        voluntary_ctx_switches: val.ru_nvcsw.wrapping_abs() as u64,
+        involuntary_ctx_switches: val.ru_nivcsw.wrapping_abs() as u64,
+        block_input_ops: val.ru_inblock.wrapping_abs() as u64,
+        block_output_ops: val.ru_oublock.wrapping_abs() as u64,
+        signals: val.ru_nsignals.wrapping_abs() as u64,
+    }
+}
+
+/// The native backend on Linux, which reads from `getrusage`, `clock_gettime` and `/proc`.
+#[derive(Clone, Debug, Default)]
+pub struct PosixBackend;
+
+impl StatsBackend for PosixBackend {
+    fn stats(&self, kind: &StatType) -> Result<RawStats, SporkError> {
+        let usage = get_stats(kind)?;
+
+        Ok(RawStats {
+            cpu_time: get_cpu_time(&usage),
+            memory: (usage.ru_maxrss as u64) * 1024,
+            extended: get_extended_stats(&usage),
+        })
+    }
+
+    fn memory_stats(&self) -> Result<MemoryStats, SporkError> {
+        procfs::get_memory_stats(None)
+    }
+
+    fn rss(&self) -> Result<u64, SporkError> {
+        procfs::get_rss(None)
+    }
+
+    fn processes(&self) -> Result<Vec<TreeProcess>, SporkError> {
+        let ticks = get_clock_ticks()?.max(1) as f64;
+        let page_size = procfs::get_page_size();
+
+        Ok(procfs::get_all_stats()?
+            .into_iter()
+            .map(|process| TreeProcess {
+                pid: process.pid,
+                ppid: process.ppid,
+                name: process.comm,
+                depth: 0,
+                cpu_time: (process.utime + process.stime) as f64 / ticks,
+                children_cpu_time: (process.cutime + process.cstime) as f64 / ticks,
+                rss: process.rss * page_size,
+            })
+            .collect())
+    }
+
+    fn register_thread(&self) -> Result<ThreadClock, SporkError> {
+        register_thread()
+    }
+
+    fn thread_clock_stats(&self, clock: &ThreadClock) -> Result<RawStats, SporkError> {
+        get_thread_clock_stats(clock)
+    }
+
+    fn system_cpu_times(&self) -> Result<SystemCpuTimes, SporkError> {
+        procfs::get_system_cpu_times()
+    }
+
+    fn io_stats(&self, pid: Option<u32>) -> Result<IoStats, SporkError> {
+        procfs::get_io_stats(pid)
+    }
+
+    fn memory_breakdown(&self, pid: Option<u32>) -> Result<MemoryBreakdown, SporkError> {
+        procfs::get_memory_breakdown(pid)
+    }
+
+    fn memory_maps(&self, pid: Option<u32>) -> Result<Vec<MemoryMapping>, SporkError> {
+        procfs::get_memory_maps(pid)
+    }
+
+    fn cpu_topology(&self) -> Result<CpuTopology, SporkError> {
+        sysfs::get_cpu_topology()
+    }
+
+    fn cgroup_stats(&self) -> Result<CgroupStats, SporkError> {
+        cgroup::get_cgroup_stats()
+    }
+
+    fn thread_times(&self) -> Result<Vec<ThreadStats>, SporkError> {
+        let ticks = get_clock_ticks()?.max(1) as f64;
+
+        Ok(procfs::get_task_stats(None)?
+            .into_iter()
+            .map(|task| ThreadStats {
+                name: task.comm,
+                state: task.state,
+                user_time: task.utime as f64 / ticks,
+                system_time: task.stime as f64 / ticks,
+                ..ThreadStats::new_empty(task.pid)
+            })
+            .collect())
+    }
+}
+
 // -----------------------------------------
 
 #[cfg(test)]
@@ -183,6 +371,30 @@ mod tests {
         print_rusage(&usage.unwrap());
     }
 
+    #[test]
+    fn should_get_extended_stats() {
+        let usage = get_stats(&StatType::Process).unwrap();
+        let extended = get_extended_stats(&usage);
+
+        assert_eq!(extended.minor_faults, usage.ru_minflt as u64);
+        assert_eq!(extended.voluntary_ctx_switches, usage.ru_nvcsw as u64);
+        assert_eq!(extended.involuntary_ctx_switches, usage.ru_nivcsw as u64);
+    }
+
+    #[test]
+    fn should_convert_ticks_to_timeval() {
+        let val = ticks_to_timeval(250, 100);
+        assert_eq!(val.tv_sec, 2);
+        assert_eq!(val.tv_usec, 500000);
+    }
+
+    #[test]
+    fn should_poll_pid_stats() {
+        let usage = get_pid_stats(std::process::id()).unwrap();
+        print_rusage(&usage);
+        assert!(usage.ru_maxrss > 0);
+    }
+
     #[test]
     fn should_get_thread_cpu_times() {
         let times = match get_thread_cpu_time() {
@@ -193,4 +405,22 @@ mod tests {
         assert!(times.tv_sec >= 0);
         assert!(times.tv_nsec >= 0);
     }
+
+    #[test]
+    fn should_read_registered_thread_clock() {
+        let clock = register_thread().unwrap();
+        assert_eq!(clock.tid, unsafe { libc::syscall(libc::SYS_gettid) } as u32);
+
+        fib(20);
+        let stats = get_thread_clock_stats(&clock).unwrap();
+        assert!(stats.cpu_time > 0_f64);
+        assert!(stats.memory > 0);
+    }
+
+    #[test]
+    fn should_err_on_exited_thread_clock() {
+        let clock = std::thread::spawn(|| register_thread().unwrap()).join().unwrap();
+        let err = get_thread_clock_stats(&clock).unwrap_err();
+        assert_eq!(err.kind(), &SporkErrorKind::ProcessNotFound);
+    }
 }
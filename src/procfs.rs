@@ -0,0 +1,713 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::*;
+use backend::SystemCpuTimes;
+
+/// Build the path to a file under `/proc/self` or `/proc/<pid>`.
+pub fn proc_path(pid: Option<u32>, file: &str) -> PathBuf {
+    match pid {
+        Some(pid) => PathBuf::from(format!("/proc/{}/{}", pid, file)),
+        None => PathBuf::from(format!("/proc/self/{}", file)),
+    }
+}
+
+/// Read a file under `/proc/self` or `/proc/<pid>`, returning a `ProcessNotFound` error if the process doesn't exist.
+pub fn read_proc_file(pid: Option<u32>, file: &str) -> Result<String, SporkError> {
+    match fs::read_to_string(proc_path(pid, file)) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && pid.is_some() && !proc_path(pid, "").exists() => {
+            let details = format!("No process with pid {}.", pid.unwrap_or_default());
+            let errno = e.raw_os_error().unwrap_or(libc::ENOENT);
+            Err(SporkError::new(SporkErrorKind::ProcessNotFound, details)
+                .with_errno(errno)
+                .with_source(e))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn get_page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+fn parse_error(file: &str, details: &str) -> SporkError {
+    SporkError::new(SporkErrorKind::Parse, format!("Invalid {} format: {}", file, details))
+}
+
+/// Parse the `Key:   value` lines of a `/proc/<pid>/status` file.
+pub fn parse_status(contents: &str) -> HashMap<&str, &str> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Read a `status` value such as `VmHWM: 1024 kB`, in bytes. Missing keys are read as 0 since kernel threads and
+/// zombie processes do not report any `Vm*` fields.
+pub fn status_kb_to_bytes(status: &HashMap<&str, &str>, key: &str) -> Result<u64, SporkError> {
+    let value = match status.get(key) {
+        Some(v) => v,
+        None => return Ok(0),
+    };
+
+    let kb = value.trim_end_matches("kB").trim();
+    match kb.parse::<u64>() {
+        Ok(kb) => Ok(kb * 1024),
+        Err(_) => Err(parse_error("status", key)),
+    }
+}
+
+/// Parse the page counts from a `/proc/<pid>/statm` file, returning the total program size and the resident set size.
+pub fn parse_statm(contents: &str) -> Result<(u64, u64), SporkError> {
+    let mut fields = contents.split_whitespace().map(|f| f.parse::<u64>());
+
+    match (fields.next(), fields.next()) {
+        (Some(Ok(size)), Some(Ok(resident))) => Ok((size, resident)),
+        _ => Err(parse_error("statm", contents.trim())),
+    }
+}
+
+/// The fields of a `/proc/<pid>/stat` or `/proc/<pid>/task/<tid>/stat` file used by spork. Times are in clock ticks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProcStat {
+    pub pid: u32,
+    pub comm: String,
+    pub state: char,
+    pub ppid: u32,
+    pub minflt: u64,
+    pub majflt: u64,
+    pub utime: u64,
+    pub stime: u64,
+    // times of the children that have been waited on
+    pub cutime: u64,
+    pub cstime: u64,
+    pub num_threads: u64,
+    pub starttime: u64,
+    // resident set size, in pages
+    pub rss: u64,
+}
+
+/// Parse a `stat` file. The `comm` field is wrapped in parens and may itself contain spaces or parens, so the
+/// remaining fields are read after the last `)`.
+pub fn parse_stat(contents: &str) -> Result<ProcStat, SporkError> {
+    let (open, close) = match (contents.find('('), contents.rfind(')')) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return Err(parse_error("stat", contents.trim())),
+    };
+
+    let pid = contents[..open].trim();
+    let comm = &contents[open + 1..close];
+    // fields[0] is field 3 (state) in proc(5)
+    let fields: Vec<&str> = contents[close + 1..].split_whitespace().collect();
+    if fields.len() < 22 {
+        return Err(parse_error("stat", contents.trim()));
+    }
+
+    let field = |idx: usize| -> Result<u64, SporkError> {
+        fields[idx - 3]
+            .parse::<u64>()
+            .map_err(|_| parse_error("stat", fields[idx - 3]))
+    };
+
+    Ok(ProcStat {
+        pid: pid.parse::<u32>().map_err(|_| parse_error("stat", pid))?,
+        comm: comm.to_owned(),
+        state: fields[0].chars().next().unwrap_or('?'),
+        ppid: field(4)? as u32,
+        minflt: field(10)?,
+        majflt: field(12)?,
+        utime: field(14)?,
+        stime: field(15)?,
+        cutime: field(16)?,
+        cstime: field(17)?,
+        num_threads: field(20)?,
+        starttime: field(22)?,
+        rss: field(24)?,
+    })
+}
+
+pub fn get_stat(pid: Option<u32>) -> Result<ProcStat, SporkError> {
+    parse_stat(&read_proc_file(pid, "stat")?)
+}
+
+/// Read the `stat` file of every process in a `/proc` directory, or every thread in a `task` directory. Tasks that
+/// exit while it is being walked are skipped, including those whose `stat` is empty or truncated as they exit.
+pub fn read_all_stats(root: &Path) -> Result<Vec<ProcStat>, SporkError> {
+    let mut processes = Vec::new();
+
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_str()
+            .map(|name| name.bytes().all(|b| b.is_ascii_digit()))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        match fs::read_to_string(entry.path().join("stat")) {
+            Ok(contents) => {
+                if let Ok(process) = parse_stat(&contents) {
+                    processes.push(process);
+                }
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESRCH) => continue,
+            Err(e) => return Err(e.into()),
+        };
+    }
+
+    Ok(processes)
+}
+
+/// Read the `stat` file for every thread of a process. Threads that exit while the task directory is being walked are
+/// skipped.
+pub fn get_task_stats(pid: Option<u32>) -> Result<Vec<ProcStat>, SporkError> {
+    read_all_stats(&proc_path(pid, "task"))
+}
+
+/// Read the `stat` file of every process on the system.
+pub fn get_all_stats() -> Result<Vec<ProcStat>, SporkError> {
+    read_all_stats(Path::new("/proc"))
+}
+
+/// Read an unsigned counter such as `voluntary_ctxt_switches` from a parsed `status` file, defaulting to 0.
+pub fn status_u64(status: &HashMap<&str, &str>, key: &str) -> Result<u64, SporkError> {
+    match status.get(key) {
+        Some(v) => v.parse::<u64>().map_err(|_| parse_error("status", key)),
+        None => Ok(0),
+    }
+}
+
+/// Read the current resident set size of a process, in bytes. This only reads `statm`, so it is cheaper than
+/// `get_memory_stats`.
+pub fn get_rss(pid: Option<u32>) -> Result<u64, SporkError> {
+    let (_, resident) = parse_statm(&read_proc_file(pid, "statm")?)?;
+    Ok(resident * get_page_size())
+}
+
+pub fn get_memory_stats(pid: Option<u32>) -> Result<MemoryStats, SporkError> {
+    let status = read_proc_file(pid, "status")?;
+    let statm = read_proc_file(pid, "statm")?;
+
+    let status = parse_status(&status);
+    let (size, resident) = parse_statm(&statm)?;
+    let page_size = get_page_size();
+
+    Ok(MemoryStats {
+        rss: resident * page_size,
+        peak_rss: status_kb_to_bytes(&status, "VmHWM")?,
+        virtual_size: size * page_size,
+        swap: status_kb_to_bytes(&status, "VmSwap")?,
+        data: status_kb_to_bytes(&status, "VmData")?,
+        stack: status_kb_to_bytes(&status, "VmStk")?,
+    })
+}
+
+/// Parse a `/proc/<pid>/io` file. Missing counters are read as 0, since `read_bytes`, `write_bytes` and
+/// `cancelled_write_bytes` depend on the kernel being built with task I/O accounting.
+pub fn parse_io(pid: Option<u32>, contents: &str) -> Result<IoStats, SporkError> {
+    let io = parse_status(contents);
+
+    Ok(IoStats {
+        rchar: status_u64(&io, "rchar")?,
+        wchar: status_u64(&io, "wchar")?,
+        syscr: status_u64(&io, "syscr")?,
+        syscw: status_u64(&io, "syscw")?,
+        read_bytes: status_u64(&io, "read_bytes")?,
+        write_bytes: status_u64(&io, "write_bytes")?,
+        cancelled_write_bytes: status_u64(&io, "cancelled_write_bytes")?,
+        ..IoStats::new_empty(pid)
+    })
+}
+
+pub fn get_io_stats(pid: Option<u32>) -> Result<IoStats, SporkError> {
+    parse_io(pid, &read_proc_file(pid, "io")?)
+}
+
+/// Parse the `cpu` and `cpuN` lines of `/proc/stat`. Kernels older than 2.6.11 don't report `steal`, so missing
+/// trailing fields are read as 0. The `guest` times are already included in `user` and `nice`, so they are ignored.
+pub fn parse_system_cpu_times(contents: &str) -> Result<SystemCpuTimes, SporkError> {
+    let mut total = None;
+    let mut cores = Vec::new();
+
+    for line in contents.lines().filter(|l| l.starts_with("cpu")) {
+        let mut fields = line.split_whitespace();
+        let core = match fields.next() {
+            Some("cpu") => None,
+            Some(name) => match name[3..].parse::<usize>() {
+                Ok(core) => Some(core),
+                Err(_) => return Err(parse_error("stat", name)),
+            },
+            None => continue,
+        };
+
+        let values = fields
+            .take(8)
+            .map(|f| f.parse::<u64>().map_err(|_| parse_error("stat", line)))
+            .collect::<Result<Vec<u64>, SporkError>>()?;
+        if values.len() < 4 {
+            return Err(parse_error("stat", line));
+        }
+
+        let field = |idx: usize| values.get(idx).cloned().unwrap_or(0);
+        let times = CpuTimes {
+            core,
+            user: field(0),
+            nice: field(1),
+            system: field(2),
+            idle: field(3),
+            iowait: field(4),
+            irq: field(5),
+            softirq: field(6),
+            steal: field(7),
+        };
+
+        match core {
+            None => total = Some(times),
+            Some(_) => cores.push(times),
+        };
+    }
+
+    match total {
+        Some(total) => Ok(SystemCpuTimes { total, cores }),
+        None => Err(parse_error("stat", "missing cpu line")),
+    }
+}
+
+pub fn get_system_cpu_times() -> Result<SystemCpuTimes, SporkError> {
+    parse_system_cpu_times(&fs::read_to_string("/proc/stat")?)
+}
+
+/// Read a `smaps` field such as `Pss:  1024 kB`, in bytes. Missing fields are read as 0, since older kernels don't
+/// report `SwapPss` or `Anonymous`.
+fn smaps_kb_to_bytes(fields: &HashMap<&str, &str>, key: &str) -> Result<u64, SporkError> {
+    match fields.get(key) {
+        Some(value) => value
+            .split_whitespace()
+            .next()
+            .and_then(|kb| kb.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+            .ok_or_else(|| parse_error("smaps", key)),
+        None => Ok(0),
+    }
+}
+
+fn parse_breakdown(fields: &HashMap<&str, &str>) -> Result<MemoryBreakdown, SporkError> {
+    let rss = smaps_kb_to_bytes(fields, "Rss")?;
+    let anonymous = smaps_kb_to_bytes(fields, "Anonymous")?;
+    let private_clean = smaps_kb_to_bytes(fields, "Private_Clean")?;
+    let private_dirty = smaps_kb_to_bytes(fields, "Private_Dirty")?;
+
+    Ok(MemoryBreakdown {
+        rss,
+        pss: smaps_kb_to_bytes(fields, "Pss")?,
+        uss: private_clean + private_dirty,
+        shared_clean: smaps_kb_to_bytes(fields, "Shared_Clean")?,
+        shared_dirty: smaps_kb_to_bytes(fields, "Shared_Dirty")?,
+        private_clean,
+        private_dirty,
+        anonymous,
+        file_backed: rss.saturating_sub(anonymous),
+        swap: smaps_kb_to_bytes(fields, "Swap")?,
+        swap_pss: smaps_kb_to_bytes(fields, "SwapPss")?,
+        anon_huge_pages: smaps_kb_to_bytes(fields, "AnonHugePages")?,
+    })
+}
+
+fn add_breakdown(total: &mut MemoryBreakdown, memory: &MemoryBreakdown) {
+    total.rss += memory.rss;
+    total.pss += memory.pss;
+    total.uss += memory.uss;
+    total.shared_clean += memory.shared_clean;
+    total.shared_dirty += memory.shared_dirty;
+    total.private_clean += memory.private_clean;
+    total.private_dirty += memory.private_dirty;
+    total.anonymous += memory.anonymous;
+    total.file_backed += memory.file_backed;
+    total.swap += memory.swap;
+    total.swap_pss += memory.swap_pss;
+    total.anon_huge_pages += memory.anon_huge_pages;
+}
+
+/// Split a `smaps` field line such as `Rss:   4 kB` into its key and value. Mapping header lines return `None`.
+fn smaps_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        None
+    } else {
+        Some((key, value.trim()))
+    }
+}
+
+/// Parse a mapping header line such as `00400000-0040b000 r-xp 00000000 08:01 1234   /bin/cat`.
+fn parse_mapping_header(line: &str) -> Result<MemoryMapping, SporkError> {
+    let mut parts = line.split_whitespace();
+    let range = parts.next().ok_or_else(|| parse_error("smaps", line))?;
+    let permissions = parts.next().ok_or_else(|| parse_error("smaps", line))?;
+    let offset = parts.next().ok_or_else(|| parse_error("smaps", line))?;
+
+    // skip the range, permissions, offset, device and inode, the rest of the line is the path, which can contain spaces
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+    }
+    let path = Some(rest.trim()).filter(|p| !p.is_empty()).map(str::to_owned);
+
+    let hex = |value: &str| u64::from_str_radix(value, 16).map_err(|_| parse_error("smaps", line));
+    let (start, end) = range.split_once('-').ok_or_else(|| parse_error("smaps", line))?;
+    let (start, end) = (hex(start)?, hex(end)?);
+
+    Ok(MemoryMapping {
+        start,
+        end,
+        permissions: permissions.to_owned(),
+        offset: hex(offset)?,
+        path,
+        size: end.saturating_sub(start),
+        memory: MemoryBreakdown::default(),
+    })
+}
+
+/// Parse a `/proc/<pid>/smaps_rollup` file.
+pub fn parse_smaps_rollup(contents: &str) -> Result<MemoryBreakdown, SporkError> {
+    let fields: HashMap<&str, &str> = contents.lines().filter_map(smaps_field).collect();
+    if !fields.contains_key("Rss") {
+        return Err(parse_error("smaps_rollup", "missing Rss"));
+    }
+
+    parse_breakdown(&fields)
+}
+
+/// Parse a `/proc/<pid>/smaps` file into its mappings.
+pub fn parse_smaps(contents: &str) -> Result<Vec<MemoryMapping>, SporkError> {
+    let mut mappings = Vec::new();
+    let mut current: Option<(MemoryMapping, HashMap<&str, &str>)> = None;
+
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        match smaps_field(line) {
+            Some((key, value)) => match current.as_mut() {
+                Some((_, fields)) => {
+                    fields.insert(key, value);
+                }
+                None => return Err(parse_error("smaps", line)),
+            },
+            None => {
+                if let Some((mapping, fields)) = current.take() {
+                    mappings.push(MemoryMapping {
+                        memory: parse_breakdown(&fields)?,
+                        ..mapping
+                    });
+                }
+                current = Some((parse_mapping_header(line)?, HashMap::new()));
+            }
+        }
+    }
+    if let Some((mapping, fields)) = current {
+        mappings.push(MemoryMapping {
+            memory: parse_breakdown(&fields)?,
+            ..mapping
+        });
+    }
+
+    Ok(mappings)
+}
+
+pub fn get_memory_maps(pid: Option<u32>) -> Result<Vec<MemoryMapping>, SporkError> {
+    parse_smaps(&read_proc_file(pid, "smaps")?)
+}
+
+/// Read the memory breakdown from `smaps_rollup`, falling back to summing `smaps` on kernels older than 4.14.
+pub fn get_memory_breakdown(pid: Option<u32>) -> Result<MemoryBreakdown, SporkError> {
+    match fs::read_to_string(proc_path(pid, "smaps_rollup")) {
+        Ok(contents) => parse_smaps_rollup(&contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut total = MemoryBreakdown::default();
+            for mapping in get_memory_maps(pid)? {
+                add_breakdown(&mut total, &mapping.memory);
+            }
+            Ok(total)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// -----------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::TempDir;
+
+    const STATUS: &str = "Name:\tcat\nState:\tR (running)\nVmPeak:\t    8752 kB\nVmSize:\t    8752 kB\nVmHWM:\t     980 kB\nVmRSS:\t     980 kB\nVmData:\t     360 kB\nVmStk:\t     132 kB\nVmSwap:\t       4 kB\nThreads:\t1\n";
+
+    #[test]
+    fn should_build_proc_paths() {
+        assert_eq!(proc_path(None, "status"), PathBuf::from("/proc/self/status"));
+        assert_eq!(proc_path(Some(42), "statm"), PathBuf::from("/proc/42/statm"));
+    }
+
+    #[test]
+    fn should_parse_status() {
+        let status = parse_status(STATUS);
+        assert_eq!(status.get("Name"), Some(&"cat"));
+        assert_eq!(status.get("State"), Some(&"R (running)"));
+        assert_eq!(status.get("Threads"), Some(&"1"));
+    }
+
+    #[test]
+    fn should_read_status_kb_as_bytes() {
+        let status = parse_status(STATUS);
+        assert_eq!(status_kb_to_bytes(&status, "VmHWM").unwrap(), 980 * 1024);
+        assert_eq!(status_kb_to_bytes(&status, "VmSwap").unwrap(), 4 * 1024);
+        assert_eq!(status_kb_to_bytes(&status, "VmPTE").unwrap(), 0);
+        assert!(status_kb_to_bytes(&status, "Name").is_err());
+    }
+
+    #[test]
+    fn should_parse_statm() {
+        assert_eq!(parse_statm("2188 245 210 5 0 110 0\n").unwrap(), (2188, 245));
+        assert!(parse_statm("").is_err());
+        assert!(parse_statm("foo bar").is_err());
+    }
+
+    #[test]
+    fn should_parse_stat() {
+        let contents = "1234 (my (weird) proc) S 1 1234 1234 0 -1 4194560 150 0 2 0 37 12 5 3 20 0 3 0 9876 8962048 245 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0\n";
+        let stat = parse_stat(contents).unwrap();
+
+        assert_eq!(stat.pid, 1234);
+        assert_eq!(stat.comm, "my (weird) proc");
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.minflt, 150);
+        assert_eq!(stat.majflt, 2);
+        assert_eq!(stat.utime, 37);
+        assert_eq!(stat.stime, 12);
+        assert_eq!(stat.cutime, 5);
+        assert_eq!(stat.cstime, 3);
+        assert_eq!(stat.num_threads, 3);
+        assert_eq!(stat.starttime, 9876);
+        assert_eq!(stat.rss, 245);
+    }
+
+    #[test]
+    fn should_err_on_invalid_stat() {
+        assert!(parse_stat("").is_err());
+        assert!(parse_stat("1234 (foo) S 1 2 3").is_err());
+        assert!(parse_stat("foo (bar) S 1 1234 1234 0 -1 4194560 150 0 2 0 37 12 0 0 20 0 3 0 9876 8962048").is_err());
+    }
+
+    #[test]
+    fn should_get_self_stat() {
+        let stat = get_stat(None).unwrap();
+        assert_eq!(stat.pid, std::process::id());
+        assert!(stat.num_threads > 0);
+    }
+
+    #[test]
+    fn should_get_self_task_stats() {
+        let tasks = get_task_stats(None).unwrap();
+        assert!(!tasks.is_empty());
+        assert!(tasks.iter().any(|t| t.pid == std::process::id()));
+    }
+
+    #[test]
+    fn should_skip_exiting_threads() {
+        let dir = TempDir::new("procfs-task");
+        dir.write(
+            "100/stat",
+            "100 (main) S 1 100 100 0 -1 4194560 150 0 2 0 37 12 0 0 20 0 2 0 9876 8962048 245\n",
+        );
+        dir.write("101/stat", "");
+        dir.write("102/stat", "102 (worker) R 1 100");
+
+        let tasks = read_all_stats(&dir.0).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].pid, 100);
+    }
+
+    #[test]
+    fn should_get_all_stats() {
+        let processes = get_all_stats().unwrap();
+        let current = processes.iter().find(|p| p.pid == std::process::id()).unwrap();
+
+        assert!(current.rss > 0);
+        assert!(processes.iter().any(|p| p.pid == current.ppid));
+    }
+
+    #[test]
+    fn should_skip_exiting_processes() {
+        let dir = TempDir::new("procfs-all");
+        dir.write(
+            "1/stat",
+            "1 (init) S 0 1 1 0 -1 4194560 150 0 2 0 37 12 0 0 20 0 1 0 9876 8962048 245\n",
+        );
+        // a process that exited between listing /proc and reading its stat
+        dir.write("42/stat", "");
+        dir.write("43/stat", "43 (cc) R 1 43");
+        dir.write("self/stat", "");
+
+        let processes = read_all_stats(&dir.0).unwrap();
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].comm, "init");
+    }
+
+    #[test]
+    fn should_parse_io() {
+        let contents = "rchar: 323934931\nwchar: 323929600\nsyscr: 632687\nsyscw: 632675\nread_bytes: 4096\nwrite_bytes: 323932160\ncancelled_write_bytes: 1024\n";
+        let io = parse_io(Some(42), contents).unwrap();
+
+        assert_eq!(io.pid, Some(42));
+        assert_eq!(io.rchar, 323934931);
+        assert_eq!(io.wchar, 323929600);
+        assert_eq!(io.syscr, 632687);
+        assert_eq!(io.syscw, 632675);
+        assert_eq!(io.read_bytes, 4096);
+        assert_eq!(io.write_bytes, 323932160);
+        assert_eq!(io.cancelled_write_bytes, 1024);
+        assert!(io.rates.is_none());
+
+        let io = parse_io(None, "rchar: 1\nwchar: 2\n").unwrap();
+        assert_eq!(io.read_bytes, 0);
+        assert!(parse_io(None, "rchar: foo\n").is_err());
+    }
+
+    #[test]
+    fn should_get_self_io_stats() {
+        let io = get_io_stats(None).unwrap();
+        assert!(io.syscr > 0);
+    }
+
+    #[test]
+    fn should_parse_system_cpu_times() {
+        let contents = "cpu  100 5 50 1000 20 1 2 3 0 0\ncpu0 60 5 30 500 10 1 1 2 0 0\ncpu2 40 0 20 500 10 0 1 1 0 0\nintr 12345\nctxt 678\n";
+        let times = parse_system_cpu_times(contents).unwrap();
+
+        assert_eq!(times.total.core, None);
+        assert_eq!(times.total.user, 100);
+        assert_eq!(times.total.steal, 3);
+        assert_eq!(times.total.total(), 1181);
+        assert_eq!(times.cores.len(), 2);
+        assert_eq!(times.cores[0].core, Some(0));
+        assert_eq!(times.cores[1].core, Some(2));
+        assert_eq!(times.cores[1].idle, 500);
+    }
+
+    #[test]
+    fn should_parse_system_cpu_times_without_steal() {
+        let times = parse_system_cpu_times("cpu 1 2 3 4\n").unwrap();
+        assert_eq!(times.total.idle, 4);
+        assert_eq!(times.total.iowait, 0);
+        assert_eq!(times.total.steal, 0);
+        assert!(times.cores.is_empty());
+    }
+
+    #[test]
+    fn should_err_on_invalid_system_cpu_times() {
+        assert!(parse_system_cpu_times("").is_err());
+        assert!(parse_system_cpu_times("cpu 1 2\n").is_err());
+        assert!(parse_system_cpu_times("cpu 1 2 3 4\ncpuX 1 2 3 4\n").is_err());
+        assert!(parse_system_cpu_times("cpu 1 2 foo 4\n").is_err());
+    }
+
+    #[test]
+    fn should_get_system_cpu_times() {
+        let times = get_system_cpu_times().unwrap();
+        assert!(times.total.total() > 0);
+        assert!(!times.cores.is_empty());
+    }
+
+    #[test]
+    fn should_get_self_rss() {
+        assert!(get_rss(None).unwrap() > 0);
+    }
+
+    #[test]
+    fn should_get_self_memory_stats() {
+        let stats = get_memory_stats(None).unwrap();
+        assert!(stats.rss > 0);
+        assert!(stats.peak_rss > 0);
+        assert!(stats.virtual_size >= stats.rss);
+        assert!(stats.stack > 0);
+    }
+
+    const SMAPS: &str = "00400000-0040b000 r-xp 00000000 08:01 1234                       /usr/bin/my app
+Size:                 44 kB
+Rss:                  40 kB
+Pss:                  20 kB
+Shared_Clean:         32 kB
+Shared_Dirty:          0 kB
+Private_Clean:         8 kB
+Private_Dirty:         0 kB
+Anonymous:             0 kB
+AnonHugePages:         0 kB
+Swap:                  0 kB
+SwapPss:               0 kB
+VmFlags: rd ex mr mw me dw
+7f0000000000-7f0000021000 rw-p 00000000 00:00 0
+Size:                132 kB
+Rss:                 100 kB
+Pss:                 100 kB
+Shared_Clean:          0 kB
+Shared_Dirty:          0 kB
+Private_Clean:         0 kB
+Private_Dirty:       100 kB
+Anonymous:           100 kB
+AnonHugePages:         0 kB
+Swap:                 12 kB
+SwapPss:              12 kB
+VmFlags: rd wr mr mw me ac
+";
+
+    #[test]
+    fn should_parse_smaps_rollup() {
+        let rollup = "00400000-7ffd5a5fe000 ---p 00000000 00:00 0                          [rollup]\nRss:                 884 kB\nPss:                 412 kB\nPss_Anon:            120 kB\nShared_Clean:        580 kB\nShared_Dirty:          0 kB\nPrivate_Clean:       184 kB\nPrivate_Dirty:       120 kB\nReferenced:          884 kB\nAnonymous:           120 kB\nAnonHugePages:         0 kB\nSwap:                  8 kB\nSwapPss:               4 kB\n";
+
+        let memory = parse_smaps_rollup(rollup).unwrap();
+        assert_eq!(memory.rss, 884 * 1024);
+        assert_eq!(memory.pss, 412 * 1024);
+        assert_eq!(memory.uss, 304 * 1024);
+        assert_eq!(memory.shared_clean, 580 * 1024);
+        assert_eq!(memory.anonymous, 120 * 1024);
+        assert_eq!(memory.file_backed, 764 * 1024);
+        assert_eq!(memory.swap, 8 * 1024);
+        assert_eq!(memory.swap_pss, 4 * 1024);
+        assert!(parse_smaps_rollup("").is_err());
+    }
+
+    #[test]
+    fn should_parse_smaps() {
+        let maps = parse_smaps(SMAPS).unwrap();
+        assert_eq!(maps.len(), 2);
+
+        assert_eq!(maps[0].start, 0x400000);
+        assert_eq!(maps[0].size, 44 * 1024);
+        assert_eq!(maps[0].permissions, "r-xp");
+        assert_eq!(maps[0].path, Some("/usr/bin/my app".to_owned()));
+        assert_eq!(maps[0].memory.pss, 20 * 1024);
+        assert_eq!(maps[0].memory.file_backed, 40 * 1024);
+
+        assert_eq!(maps[1].path, None);
+        assert_eq!(maps[1].memory.uss, 100 * 1024);
+        assert_eq!(maps[1].memory.swap, 12 * 1024);
+        assert!(parse_smaps("Rss: 4 kB\n").is_err());
+    }
+
+    #[test]
+    fn should_read_own_memory_breakdown() {
+        let memory = get_memory_breakdown(None).unwrap();
+        assert!(memory.rss > 0);
+        assert!(memory.pss > 0 && memory.pss <= memory.rss);
+        assert!(!get_memory_maps(None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,629 @@
+//! Render spork metrics in the [Prometheus text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+//! and optionally serve them over HTTP.
+//!
+//! ```
+//! use spork::prometheus::{Metrics, MetricsServer};
+//! use spork::{Spork, StatType};
+//! use std::sync::Arc;
+//!
+//! let spork = Arc::new(Spork::new().unwrap());
+//!
+//! let mut metrics = Metrics::new();
+//! metrics.add_stats(&spork.stats(StatType::Process).unwrap());
+//! metrics.add_memory(&spork.memory_stats().unwrap());
+//! println!("{}", metrics.render());
+//!
+//! // serve process metrics on http://127.0.0.1:9898/metrics until `server` is dropped
+//! let server = MetricsServer::start(9898, spork).unwrap();
+//! ```
+//!
+//! Every metric carries a `kind` label with the `StatType` it was read from (`process`, `thread`, `children`, or `pid`,
+//! along with a `pid` label), and per-thread metrics carry `tid` and `thread` (name) labels. The CPU time of the current
+//! process is exported as the standard `process_cpu_seconds_total`, while other kinds use `spork_cpu_seconds_total`.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::*;
+use sampler::Sample;
+
+/// The content type of the text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: MetricType,
+    samples: Vec<(Vec<(String, String)>, f64)>,
+}
+
+/// A collection of metric families that can be rendered in the text exposition format. Samples are grouped by metric
+/// name regardless of the order they were added in.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    families: Vec<Family>,
+}
+
+/// Read the labels describing a `StatType`.
+pub fn kind_labels(kind: &StatType) -> Vec<(String, String)> {
+    match *kind {
+        StatType::Process => vec![("kind".into(), "process".into())],
+        StatType::Thread => vec![("kind".into(), "thread".into())],
+        StatType::Children => vec![("kind".into(), "children".into())],
+        StatType::Pid(pid) => vec![("kind".into(), "pid".into()), ("pid".into(), pid.to_string())],
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        };
+    }
+    out
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0_f64 { "+Inf" } else { "-Inf" }.into()
+    } else {
+        value.to_string()
+    }
+}
+
+impl Metrics {
+    /// Create an empty set of metrics.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        kind: MetricType,
+        labels: Vec<(String, String)>,
+        value: f64,
+    ) {
+        let idx = match self.families.iter().position(|f| f.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.families.push(Family {
+                    name,
+                    help,
+                    kind,
+                    samples: Vec::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+
+        self.families[idx].samples.push((labels, value));
+    }
+
+    /// Add the metrics from a `Stats` instance, labeled with its `StatType`.
+    pub fn add_stats(&mut self, stats: &Stats) -> &mut Metrics {
+        self.add_labeled_stats(stats, &[])
+    }
+
+    /// Add the metrics from a `Stats` instance, labeled with its `StatType` and the provided labels. Use this to add a
+    /// `thread` label to `StatType::Thread` stats, for example.
+    pub fn add_labeled_stats(&mut self, stats: &Stats, labels: &[(&str, &str)]) -> &mut Metrics {
+        let mut base = kind_labels(&stats.kind);
+        base.extend(labels.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        let ext = &stats.extended;
+
+        let (name, help) = match stats.kind {
+            StatType::Process => (
+                "process_cpu_seconds_total",
+                "Total user and system CPU time spent in seconds.",
+            ),
+            _ => (
+                "spork_cpu_seconds_total",
+                "Total user and system CPU time spent by a thread, child processes, or another process in seconds.",
+            ),
+        };
+        self.push(name, help, MetricType::Counter, base.clone(), stats.cpu_time);
+        if let Some(cpu) = stats.cpu {
+            self.push(
+                "spork_cpu_usage_percent",
+                "Average CPU load since the previous poll, as a percentage.",
+                MetricType::Gauge,
+                base.clone(),
+                cpu,
+            );
+        }
+        self.push(
+            "spork_peak_resident_memory_bytes",
+            "Peak resident memory size in bytes.",
+            MetricType::Gauge,
+            base.clone(),
+            stats.memory as f64,
+        );
+        self.push(
+            "spork_uptime_seconds",
+            "Time since the spork instance was created in seconds.",
+            MetricType::Gauge,
+            base.clone(),
+            stats.uptime as f64 / 1000_f64,
+        );
+
+        let counters: [(&'static str, &'static str, u64); 7] = [
+            (
+                "spork_minor_page_faults_total",
+                "Page faults serviced without any I/O activity.",
+                ext.minor_faults,
+            ),
+            (
+                "spork_major_page_faults_total",
+                "Page faults serviced that required I/O activity.",
+                ext.major_faults,
+            ),
+            (
+                "spork_voluntary_context_switches_total",
+                "Context switches due to waiting on a resource.",
+                ext.voluntary_ctx_switches,
+            ),
+            (
+                "spork_involuntary_context_switches_total",
+                "Context switches due to preemption.",
+                ext.involuntary_ctx_switches,
+            ),
+            (
+                "spork_block_input_operations_total",
+                "Block input operations performed by the file system.",
+                ext.block_input_ops,
+            ),
+            (
+                "spork_block_output_operations_total",
+                "Block output operations performed by the file system.",
+                ext.block_output_ops,
+            ),
+            ("spork_signals_received_total", "Signals received.", ext.signals),
+        ];
+        for (name, help, value) in counters.iter() {
+            self.push(name, help, MetricType::Counter, base.clone(), *value as f64);
+        }
+
+        self
+    }
+
+    /// Add the metrics from a process memory breakdown. The peak resident memory is already exported by `add_stats`.
+    pub fn add_memory(&mut self, memory: &MemoryStats) -> &mut Metrics {
+        let gauges: [(&'static str, &'static str, u64); 5] = [
+            (
+                "process_resident_memory_bytes",
+                "Resident memory size in bytes.",
+                memory.rss,
+            ),
+            (
+                "process_virtual_memory_bytes",
+                "Virtual memory size in bytes.",
+                memory.virtual_size,
+            ),
+            (
+                "spork_swap_memory_bytes",
+                "Memory swapped out to disk in bytes.",
+                memory.swap,
+            ),
+            ("spork_data_memory_bytes", "Data segment size in bytes.", memory.data),
+            (
+                "spork_stack_memory_bytes",
+                "Main thread stack size in bytes.",
+                memory.stack,
+            ),
+        ];
+        for (name, help, value) in gauges.iter() {
+            self.push(
+                name,
+                help,
+                MetricType::Gauge,
+                kind_labels(&StatType::Process),
+                *value as f64,
+            );
+        }
+
+        self
+    }
+
+    /// Add the per-thread metrics from a thread snapshot.
+    pub fn add_threads(&mut self, threads: &[ThreadStats]) -> &mut Metrics {
+        for thread in threads.iter() {
+            let labels = vec![
+                ("tid".to_string(), thread.tid.to_string()),
+                ("thread".to_string(), thread.name.clone()),
+            ];
+            let mut user = labels.clone();
+            user.push(("mode".into(), "user".into()));
+            let mut system = labels.clone();
+            system.push(("mode".into(), "system".into()));
+
+            self.push(
+                "spork_thread_cpu_seconds_total",
+                "Total CPU time spent by the thread in seconds.",
+                MetricType::Counter,
+                user,
+                thread.user_time,
+            );
+            self.push(
+                "spork_thread_cpu_seconds_total",
+                "Total CPU time spent by the thread in seconds.",
+                MetricType::Counter,
+                system,
+                thread.system_time,
+            );
+            if let Some(cpu) = thread.cpu {
+                self.push(
+                    "spork_thread_cpu_usage_percent",
+                    "Average CPU load of the thread since the previous snapshot, as a percentage.",
+                    MetricType::Gauge,
+                    labels,
+                    cpu,
+                );
+            }
+        }
+
+        self
+    }
+
+    /// Add the number of entries in the stats history of a `Spork` instance.
+    pub fn add_history(&mut self, size: &HistorySize) -> &mut Metrics {
+        let entries = [
+            ("process", size.process),
+            ("thread", size.thread),
+            ("children", size.children),
+            ("pid", size.pids),
+            ("handle", size.handles),
+        ];
+        for (kind, value) in entries.iter() {
+            self.push(
+                "spork_history_entries",
+                "Number of entries in the stats history.",
+                MetricType::Gauge,
+                vec![("kind".into(), kind.to_string())],
+                *value as f64,
+            );
+        }
+
+        self
+    }
+
+    /// Add every metric from a `Sample` taken by a `Sampler`.
+    pub fn add_sample(&mut self, sample: &Sample) -> &mut Metrics {
+        for stats in sample.stats.iter() {
+            self.add_stats(stats);
+        }
+        if let Some(ref memory) = sample.memory {
+            self.add_memory(memory);
+        }
+        if let Some(ref threads) = sample.threads {
+            self.add_threads(threads);
+        }
+
+        self
+    }
+
+    /// Render the metrics in the text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        for family in self.families.iter() {
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind.as_str());
+
+            for (labels, value) in family.samples.iter() {
+                out.push_str(family.name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+                        .collect();
+                    let _ = write!(out, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(out, " {}", format_value(*value));
+            }
+        }
+
+        out
+    }
+}
+
+/// Collect the default set of metrics served by `MetricsServer::start`: process stats, the memory breakdown, and a
+/// thread snapshot, where supported, along with the size of the stats history.
+///
+/// Like any poll this records the stats in the history of `spork`, so the CPU load of the next poll is measured from this
+/// one. Pass a clone of the `Spork` instance the application polls, as `MetricsServer::start` does, to keep the two
+/// apart.
+pub fn collect_process_metrics(spork: &Spork) -> Metrics {
+    collect_metrics(spork, spork)
+}
+
+// poll with `scraper`, reporting the history size of `spork`
+fn collect_metrics(scraper: &Spork, spork: &Spork) -> Metrics {
+    let mut metrics = Metrics::new();
+
+    if let Ok(stats) = scraper.stats(StatType::Process) {
+        metrics.add_stats(&stats);
+    }
+    if let Ok(memory) = scraper.memory_stats() {
+        metrics.add_memory(&memory);
+    }
+    if let Ok(threads) = scraper.thread_snapshot() {
+        metrics.add_threads(&threads);
+    }
+    metrics.add_history(&spork.history_size());
+
+    metrics
+}
+
+/// A minimal HTTP/1.0 server that serves the output of a collector function on `GET /metrics`. Connections are accepted
+/// on a dedicated thread, which is stopped when the server is dropped, and each one is handled on its own thread.
+pub struct MetricsServer {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Serve the metrics from `collect_process_metrics` on `127.0.0.1:port`. Use port 0 to pick any free port.
+    ///
+    /// Scrapes are polled with a private clone of `spork`, so they don't change the history the application's own polls
+    /// are measured against. The history size is still read from `spork`.
+    pub fn start(port: u16, spork: Arc<Spork>) -> Result<MetricsServer, SporkError> {
+        let scraper = Spork::clone(&spork);
+        MetricsServer::bind(("127.0.0.1", port), move || collect_metrics(&scraper, &spork).render())
+    }
+
+    /// Serve the text returned by `collect` on every scrape of `GET /metrics` at `addr`.
+    pub fn bind<A, F>(addr: A, collect: F) -> Result<MetricsServer, SporkError>
+    where
+        A: ToSocketAddrs,
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let collect = Arc::new(collect);
+
+        let server_shutdown = shutdown.clone();
+        let handle = thread::Builder::new().name("spork-metrics".into()).spawn(move || {
+            for stream in listener.incoming() {
+                if server_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    // a slow client only holds up its own thread, not other scrapes or `stop`
+                    let collect = collect.clone();
+                    let _ = thread::Builder::new()
+                        .name("spork-metrics".into())
+                        .spawn(move || handle_connection(stream, &*collect));
+                }
+            }
+        })?;
+
+        Ok(MetricsServer {
+            addr,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// Read the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting connections and wait for the server thread to exit. Connections that were already accepted are
+    /// still served.
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.shutdown.store(true, Ordering::SeqCst);
+            // wake up the blocking accept call
+            let _ = TcpStream::connect(self.addr);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn handle_connection<F: Fn() -> String>(mut stream: TcpStream, collect: &F) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // drain the headers so clients don't see a reset before reading the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", CONTENT_TYPE, collect()),
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "Not Found\n".to_owned()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n".to_owned(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_escape_label_values() {
+        assert_eq!(escape_label_value("foo"), "foo");
+        assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn should_format_values() {
+        assert_eq!(format_value(1.5_f64), "1.5");
+        assert_eq!(format_value(3_f64), "3");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+    }
+
+    #[test]
+    fn should_label_stat_types() {
+        assert_eq!(kind_labels(&StatType::Process), vec![("kind".into(), "process".into())]);
+        assert_eq!(
+            kind_labels(&StatType::Pid(42)),
+            vec![("kind".to_string(), "pid".to_string()), ("pid".into(), "42".into())]
+        );
+    }
+
+    #[test]
+    fn should_render_stats() {
+        let mut stats = Stats::new_empty(StatType::Process);
+        stats.cpu_time = 1.25_f64;
+        stats.cpu = Some(50_f64);
+        stats.memory = 2048;
+        stats.extended.minor_faults = 7;
+
+        let mut metrics = Metrics::new();
+        metrics.add_stats(&stats);
+        let out = metrics.render();
+
+        assert!(out.contains("# HELP process_cpu_seconds_total Total user and system CPU time spent in seconds.\n"));
+        assert!(out.contains("# TYPE process_cpu_seconds_total counter\n"));
+        assert!(out.contains("process_cpu_seconds_total{kind=\"process\"} 1.25\n"));
+        assert!(out.contains("spork_cpu_usage_percent{kind=\"process\"} 50\n"));
+        assert!(out.contains("spork_peak_resident_memory_bytes{kind=\"process\"} 2048\n"));
+        assert!(out.contains("spork_minor_page_faults_total{kind=\"process\"} 7\n"));
+    }
+
+    #[test]
+    fn should_skip_missing_cpu_load() {
+        let stats = Stats::new_empty(StatType::Thread);
+        let mut metrics = Metrics::new();
+        metrics.add_stats(&stats);
+
+        assert!(!metrics.render().contains("spork_cpu_usage_percent"));
+    }
+
+    #[test]
+    fn should_group_samples_by_family() {
+        let mut metrics = Metrics::new();
+        metrics.add_stats(&Stats::new_empty(StatType::Process));
+        metrics.add_labeled_stats(&Stats::new_empty(StatType::Thread), &[("thread", "main")]);
+        let out = metrics.render();
+
+        assert_eq!(out.matches("# TYPE spork_peak_resident_memory_bytes gauge").count(), 1);
+        let family = out.find("# TYPE spork_peak_resident_memory_bytes").unwrap();
+        let process = out
+            .find("spork_peak_resident_memory_bytes{kind=\"process\"} 0")
+            .unwrap();
+        let thread = out
+            .find("spork_peak_resident_memory_bytes{kind=\"thread\",thread=\"main\"} 0")
+            .unwrap();
+        let next = out.find("# HELP spork_uptime_seconds").unwrap();
+        assert!(family < process && process < thread && thread < next);
+    }
+
+    #[test]
+    fn should_export_other_kinds_as_spork_cpu_seconds() {
+        let mut stats = Stats::new_empty(StatType::Pid(42));
+        stats.cpu_time = 2_f64;
+        let mut metrics = Metrics::new();
+        metrics
+            .add_stats(&stats)
+            .add_stats(&Stats::new_empty(StatType::Thread))
+            .add_stats(&Stats::new_empty(StatType::Children));
+        let out = metrics.render();
+
+        assert!(!out.contains("process_cpu_seconds_total"));
+        assert!(out.contains("# TYPE spork_cpu_seconds_total counter\n"));
+        assert!(out.contains("spork_cpu_seconds_total{kind=\"pid\",pid=\"42\"} 2\n"));
+        assert!(out.contains("spork_cpu_seconds_total{kind=\"thread\"} 0\n"));
+        assert!(out.contains("spork_cpu_seconds_total{kind=\"children\"} 0\n"));
+    }
+
+    #[test]
+    fn should_render_memory_and_threads() {
+        let memory = MemoryStats {
+            rss: 100,
+            virtual_size: 200,
+            ..Default::default()
+        };
+        let mut thread = ThreadStats::new_empty(12);
+        thread.name = "worker \"1\"".into();
+        thread.user_time = 0.5_f64;
+        thread.cpu = Some(10_f64);
+
+        let mut metrics = Metrics::new();
+        metrics.add_memory(&memory).add_threads(&[thread]);
+        let out = metrics.render();
+
+        assert!(out.contains("process_resident_memory_bytes{kind=\"process\"} 100\n"));
+        assert!(out.contains("process_virtual_memory_bytes{kind=\"process\"} 200\n"));
+        assert!(
+            out.contains("spork_thread_cpu_seconds_total{tid=\"12\",thread=\"worker \\\"1\\\"\",mode=\"user\"} 0.5\n")
+        );
+        assert!(out.contains("spork_thread_cpu_usage_percent{tid=\"12\",thread=\"worker \\\"1\\\"\"} 10\n"));
+    }
+
+    #[test]
+    fn should_render_history_size() {
+        let size = HistorySize {
+            thread: 3,
+            pids: 1,
+            ..Default::default()
+        };
+        let out = Metrics::new().add_history(&size).render();
+
+        assert!(out.contains("# TYPE spork_history_entries gauge\n"));
+        assert!(out.contains("spork_history_entries{kind=\"thread\"} 3\n"));
+        assert!(out.contains("spork_history_entries{kind=\"pid\"} 1\n"));
+        assert!(out.contains("spork_history_entries{kind=\"handle\"} 0\n"));
+    }
+}
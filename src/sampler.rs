@@ -0,0 +1,381 @@
+//! A background sampler that polls a shared `Spork` instance on a fixed interval.
+//!
+//! ```
+//! use spork::sampler::Sampler;
+//! use spork::{Spork, StatType};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let spork = Arc::new(Spork::new().unwrap());
+//! let mut sampler = Sampler::new(spork, Duration::from_secs(1))
+//!   .with_kind(StatType::Process)
+//!   .with_memory(true);
+//!
+//! sampler.subscribe(|sample| println!("Sample: {:?}", sample));
+//! let samples = sampler.channel();
+//!
+//! sampler.start().unwrap();
+//! let sample = samples.recv().unwrap();
+//! println!("Process stats: {:?}", sample.stats);
+//! // the sampler thread is stopped and joined when `sampler` is dropped
+//! ```
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::*;
+use utils::{lock, Ticker};
+
+/// A callback invoked with every sample taken by a `Sampler`.
+pub type Callback = Arc<dyn Fn(&Sample) + Send + Sync + 'static>;
+
+/// The metrics collected by a `Sampler` on each tick.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// Time at which the sample was taken, in milliseconds since epoch.
+    #[cfg_attr(feature = "serde", serde(rename = "polled_ms"))]
+    pub polled: i64,
+    /// The stats for each configured `StatType` that was polled successfully, in the order they were added.
+    pub stats: Vec<Stats>,
+    /// The process memory breakdown, if enabled with `with_memory`.
+    pub memory: Option<MemoryStats>,
+    /// The per-thread snapshot, if enabled with `with_threads`.
+    pub threads: Option<Vec<ThreadStats>>,
+    /// Any errors encountered while taking the sample.
+    pub errors: Vec<SporkError>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Running,
+    Paused,
+    Stopped,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    signal: Condvar,
+    callbacks: Mutex<Vec<Callback>>,
+    senders: Mutex<Vec<Sender<Sample>>>,
+}
+
+#[derive(Clone)]
+struct Config {
+    interval: Duration,
+    kinds: Vec<StatType>,
+    memory: bool,
+    threads: bool,
+}
+
+/// Polls a `Spork` instance from a dedicated thread and delivers each `Sample` to the registered callbacks and channels.
+///
+/// Ticks are scheduled against a monotonic clock from when the sampler started, so slow callbacks do not cause the
+/// interval to drift. If a tick is missed entirely it is skipped rather than sampled twice in a row.
+///
+/// Note that `StatType::Thread` and `StatType::Children` are measured for the sampler's own thread, which is rarely
+/// useful. Use `with_threads` to sample the other threads in the process instead.
+pub struct Sampler {
+    spork: Arc<Spork>,
+    config: Config,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Create a new, stopped `Sampler` that will poll `spork` every `interval`, rounded up to `MIN_POLL_INTERVAL`.
+    pub fn new(spork: Arc<Spork>, interval: Duration) -> Sampler {
+        Sampler {
+            spork,
+            config: Config {
+                interval: utils::poll_interval(interval),
+                kinds: Vec::new(),
+                memory: false,
+                threads: false,
+            },
+            shared: Arc::new(Shared {
+                state: Mutex::new(State::Stopped),
+                signal: Condvar::new(),
+                callbacks: Mutex::new(Vec::new()),
+                senders: Mutex::new(Vec::new()),
+            }),
+            handle: None,
+        }
+    }
+
+    /// Poll `kind` with `Spork::stats` on every tick. Changes take effect the next time the sampler is started.
+    pub fn with_kind(mut self, kind: StatType) -> Sampler {
+        self.config.kinds.push(kind);
+        self
+    }
+
+    /// Include `Spork::memory_stats` in every sample. Changes take effect the next time the sampler is started.
+    pub fn with_memory(mut self, enabled: bool) -> Sampler {
+        self.config.memory = enabled;
+        self
+    }
+
+    /// Include `Spork::thread_snapshot` in every sample. Changes take effect the next time the sampler is started.
+    pub fn with_threads(mut self, enabled: bool) -> Sampler {
+        self.config.threads = enabled;
+        self
+    }
+
+    /// Read the sampling interval.
+    pub fn interval(&self) -> Duration {
+        self.config.interval
+    }
+
+    /// Register a callback to be called from the sampler thread with every sample.
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: Fn(&Sample) + Send + Sync + 'static,
+    {
+        lock(&self.shared.callbacks).push(Arc::new(callback));
+    }
+
+    /// Create a channel that receives every sample. The channel is unsubscribed once the receiver is dropped.
+    pub fn channel(&self) -> Receiver<Sample> {
+        let (tx, rx) = mpsc::channel();
+        lock(&self.shared.senders).push(tx);
+        rx
+    }
+
+    /// Start the sampler thread, taking the first sample immediately. Starting a paused sampler resumes it, and
+    /// starting a running sampler does nothing.
+    pub fn start(&mut self) -> Result<(), SporkError> {
+        {
+            let mut state = lock(&self.shared.state);
+            match *state {
+                State::Running => return Ok(()),
+                State::Paused => {
+                    *state = State::Running;
+                    self.shared.signal.notify_all();
+                    return Ok(());
+                }
+                State::Stopped => *state = State::Running,
+            };
+        }
+
+        let spork = self.spork.clone();
+        let shared = self.shared.clone();
+        let config = self.config.clone();
+        let handle = thread::Builder::new()
+            .name("spork-sampler".into())
+            .spawn(move || run(&spork, &shared, &config));
+
+        match handle {
+            Ok(handle) => {
+                self.handle = Some(handle);
+                Ok(())
+            }
+            Err(e) => {
+                *lock(&self.shared.state) = State::Stopped;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Pause sampling without stopping the sampler thread.
+    pub fn pause(&self) {
+        let mut state = lock(&self.shared.state);
+        if *state == State::Running {
+            *state = State::Paused;
+            self.shared.signal.notify_all();
+        }
+    }
+
+    /// Resume a paused sampler, taking a sample immediately.
+    pub fn resume(&self) {
+        let mut state = lock(&self.shared.state);
+        if *state == State::Paused {
+            *state = State::Running;
+            self.shared.signal.notify_all();
+        }
+    }
+
+    /// Stop the sampler thread and wait for it to exit. Callbacks and channels stay registered, so the sampler can be
+    /// started again later.
+    pub fn stop(&mut self) {
+        {
+            let mut state = lock(&self.shared.state);
+            *state = State::Stopped;
+            self.shared.signal.notify_all();
+        }
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Whether the sampler thread is running and not paused.
+    pub fn is_running(&self) -> bool {
+        *lock(&self.shared.state) == State::Running
+    }
+
+    /// Whether the sampler is paused.
+    pub fn is_paused(&self) -> bool {
+        *lock(&self.shared.state) == State::Paused
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn take_sample(spork: &Spork, config: &Config) -> Sample {
+    let mut sample = Sample {
+        polled: spork.backend.now_ms(),
+        stats: Vec::with_capacity(config.kinds.len()),
+        memory: None,
+        threads: None,
+        errors: Vec::new(),
+    };
+
+    for kind in config.kinds.iter() {
+        match spork.stats(kind.clone()) {
+            Ok(stats) => sample.stats.push(stats),
+            Err(e) => sample.errors.push(e),
+        };
+    }
+    if config.memory {
+        match spork.memory_stats() {
+            Ok(memory) => sample.memory = Some(memory),
+            Err(e) => sample.errors.push(e),
+        };
+    }
+    if config.threads {
+        match spork.thread_snapshot() {
+            Ok(threads) => sample.threads = Some(threads),
+            Err(e) => sample.errors.push(e),
+        };
+    }
+
+    sample
+}
+
+fn deliver(shared: &Shared, sample: Sample) {
+    // call back without holding the callbacks lock, so callbacks can subscribe or open channels
+    let callbacks = lock(&shared.callbacks).clone();
+    for callback in callbacks.iter() {
+        callback(&sample);
+    }
+
+    lock(&shared.senders).retain(|tx| tx.send(sample.clone()).is_ok());
+}
+
+fn run(spork: &Spork, shared: &Shared, config: &Config) {
+    let mut ticker = Ticker::new(config.interval);
+
+    loop {
+        {
+            let mut state = lock(&shared.state);
+            loop {
+                match *state {
+                    State::Stopped => return,
+                    State::Paused => {
+                        state = shared.signal.wait(state).unwrap_or_else(|e| e.into_inner());
+                        // sample right away once resumed
+                        ticker.reset();
+                    }
+                    State::Running => {
+                        state = ticker.wait(&shared.signal, state, |state| *state != State::Running);
+                        if *state == State::Running {
+                            break;
+                        }
+                    }
+                };
+            }
+        }
+
+        deliver(shared, take_sample(spork, config));
+        ticker.advance();
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::{FakeClock, MockBackend};
+
+    #[test]
+    fn should_create_stopped_sampler() {
+        let spork = Arc::new(Spork::new().unwrap());
+        let sampler = Sampler::new(spork, Duration::from_millis(10));
+
+        assert!(!sampler.is_running());
+        assert!(!sampler.is_paused());
+        assert_eq!(sampler.interval(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn should_round_up_zero_interval() {
+        let spork = Arc::new(Spork::new().unwrap());
+        let sampler = Sampler::new(spork, Duration::ZERO);
+
+        assert_eq!(sampler.interval(), MIN_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn should_allow_subscribing_from_callback() {
+        let spork = Arc::new(Spork::new().unwrap());
+        let sampler = Sampler::new(spork, Duration::from_millis(10));
+        let shared = sampler.shared.clone();
+        sampler.subscribe(move |_| lock(&shared.callbacks).push(Arc::new(|_| ())));
+
+        deliver(&sampler.shared, take_sample(&sampler.spork, &sampler.config));
+        assert_eq!(lock(&sampler.shared.callbacks).len(), 2);
+    }
+
+    #[test]
+    fn should_take_sample_for_each_kind() {
+        let spork = Spork::new().unwrap();
+        let config = Config {
+            interval: Duration::from_millis(10),
+            kinds: vec![StatType::Process, StatType::Thread],
+            memory: false,
+            threads: false,
+        };
+
+        let sample = take_sample(&spork, &config);
+        assert!(sample.errors.is_empty());
+        assert_eq!(sample.stats.len(), 2);
+        assert_eq!(sample.stats[0].kind, StatType::Process);
+        assert_eq!(sample.stats[1].kind, StatType::Thread);
+        assert!(sample.memory.is_none());
+        assert!(sample.threads.is_none());
+    }
+
+    #[test]
+    fn should_read_poll_time_from_backend_clock() {
+        let backend = MockBackend::with_clock(FakeClock::with_origin(1_000));
+        let spork = Spork::with_backend(backend.clone()).unwrap();
+        let config = Config {
+            interval: Duration::from_millis(10),
+            kinds: vec![StatType::Process],
+            memory: false,
+            threads: false,
+        };
+
+        backend.clock().advance(Duration::from_millis(250));
+        assert_eq!(take_sample(&spork, &config).polled, 1_250);
+    }
+
+    #[test]
+    fn should_drop_disconnected_channels() {
+        let spork = Arc::new(Spork::new().unwrap());
+        let sampler = Sampler::new(spork, Duration::from_millis(10));
+        let rx = sampler.channel();
+        drop(sampler.channel());
+
+        deliver(&sampler.shared, take_sample(&sampler.spork, &sampler.config));
+        assert_eq!(lock(&sampler.shared.senders).len(), 1);
+        assert!(rx.try_recv().is_ok());
+    }
+}
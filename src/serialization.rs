@@ -0,0 +1,140 @@
+//! Serde support, enabled with the `serde` feature.
+//!
+//! The serialized form of each type is stable across patch releases and is intended for shipping samples to log
+//! pipelines as JSON. Fields that carry a unit include it in their name, and durations are always serialized as whole
+//! nanoseconds.
+//!
+//! ## `Stats`
+//!
+//! | Field            | Type            | Description                                                       |
+//! |------------------|-----------------|-------------------------------------------------------------------|
+//! | `polled_ms`      | integer         | Wall-clock poll time, in milliseconds since epoch.                |
+//! | `monotonic_ns`   | integer         | Monotonic poll time, in nanoseconds since a per-process origin.   |
+//! | `duration_ns`    | integer         | Interval the CPU load was calculated over, in nanoseconds.        |
+//! | `cpu_time_secs`  | float           | Total CPU time, in seconds.                                       |
+//! | `cpu_percent`    | float or `null` | Average CPU load since the last poll, as a percentage.            |
+//! | `memory_bytes`   | integer         | Working set size, in bytes.                                       |
+//! | `uptime_ms`      | integer         | Process uptime, in milliseconds.                                  |
+//! | `kind`           | `StatType`      | The kind of statistic.                                            |
+//! | `cores`          | integer         | The number of cores the CPU load was normalized to.               |
+//! | `extended`       | object          | Cumulative counters, see `ExtendedStats`.                         |
+//! | `extended_delta` | object          | The change in `extended` since the previous poll.                 |
+//!
+//! `ExtendedStats` fields are unitless counts and keep their Rust names. `MemoryStats` and `MemoryBreakdown` fields are
+//! suffixed with `_bytes`, as is the `size` of a `MemoryMapping`, and the `ThreadStats` times are suffixed with
+//! `_secs`. `ProcessTree` and `TreeProcess` rename `cpu_time` and `rss` to `cpu_time_secs` and `rss_bytes`, and
+//! `children_cpu_time` to `children_cpu_time_secs`. `CpuUsage` states are suffixed with `_percent`, while the raw
+//! `CpuTimes` are unitless clock ticks and keep their Rust names. `IoStats` renames `rchar` and `wchar` to
+//! `rchar_bytes` and `wchar_bytes`, and every `IoRates` field is suffixed with `_per_sec`.
+//!
+//! ## Enums
+//!
+//! * `StatType` is `"process"`, `"thread"`, `"children"` or `{"pid": 1234}`.
+//! * `Platform` is `"linux"`, `"macos"`, `"windows"` or `"unknown"`.
+//! * `SporkErrorKind` is `"invalid_stat_type"`, `"unimplemented"`, `"unknown"`, `"permission_denied"`,
+//!   `"process_not_found"`, `"parse"`, `"invalid_argument"` or `"io"`.
+//!
+//! ## `SporkError`
+//!
+//! Errors are serialized as `{"kind": "io", "description": "IO Error", "details": "...", "errno": 5}`. The
+//! `description` is derived from the `kind` and is ignored when deserializing. `errno` is omitted for errors that
+//! didn't come from the OS, and the error's `source` is not serialized.
+//!
+//! ```
+//! use spork::{Spork, StatType};
+//!
+//! let spork = Spork::new().unwrap();
+//! let stats = spork.stats(StatType::Process).unwrap();
+//! println!("{}", serde_json::to_string(&stats).unwrap());
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::*;
+
+/// Serialize a `Duration` as a whole number of nanoseconds.
+pub(crate) mod duration_ns {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_nanos().min(u64::MAX as u128) as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_nanos(u64::deserialize(deserializer)?))
+    }
+}
+
+/// The serialized form of a `SporkError`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SerializedError {
+    pub kind: SporkErrorKind,
+    #[serde(default)]
+    pub description: String,
+    pub details: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+}
+
+impl From<SporkError> for SerializedError {
+    fn from(error: SporkError) -> Self {
+        SerializedError {
+            kind: error.kind().clone(),
+            description: error.desc.to_owned(),
+            details: error.details().to_owned(),
+            errno: error.errno(),
+        }
+    }
+}
+
+impl From<SerializedError> for SporkError {
+    fn from(error: SerializedError) -> Self {
+        let spork_error = SporkError::new(error.kind, error.details);
+        match error.errno {
+            Some(errno) => spork_error.with_errno(errno),
+            None => spork_error,
+        }
+    }
+}
+
+impl Serialize for SporkError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedError::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SporkError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SporkError, D::Error> {
+        SerializedError::deserialize(deserializer).map(SporkError::from)
+    }
+}
+
+// -----------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_errors() {
+        let error = SporkError::new(SporkErrorKind::InvalidStatType, "foo");
+        let serialized = SerializedError::from(error.clone());
+
+        assert_eq!(serialized.kind, SporkErrorKind::InvalidStatType);
+        assert_eq!(serialized.description, "Invalid Stat Type");
+        assert_eq!(serialized.details, "foo");
+        assert_eq!(serialized.errno, None);
+        assert_eq!(SporkError::from(serialized), error);
+    }
+
+    #[test]
+    fn should_round_trip_errno() {
+        let error = SporkError::new(SporkErrorKind::PermissionDenied, "foo").with_errno(13);
+        let serialized = SerializedError::from(error.clone());
+
+        assert_eq!(serialized.errno, Some(13));
+        assert_eq!(serialized.description, "Permission Denied");
+        assert_eq!(SporkError::from(serialized), error);
+    }
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::Path;
+
+use super::*;
+use topology::{parse_cpu_list, CacheType, CpuCache, CpuFrequency, CpuTopology, LogicalCpu};
+
+const CPU_ROOT: &str = "/sys/devices/system/cpu";
+
+fn parse_error(file: &str, details: &str) -> SporkError {
+    SporkError::new(SporkErrorKind::Parse, format!("Invalid {} format: {}", file, details))
+}
+
+/// Read an attribute, or `None` if the kernel doesn't expose it, such as the topology of an offline CPU.
+fn read_optional(path: &Path) -> Result<Option<String>, SporkError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents.trim().to_owned())),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_u64(path: &Path) -> Result<Option<u64>, SporkError> {
+    match read_optional(path)? {
+        Some(value) => {
+            let file = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+            value.parse::<u64>().map(Some).map_err(|_| parse_error(file, &value))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Parse a cache size such as `48K` or `2048K` into bytes.
+pub fn parse_cache_size(size: &str) -> Result<u64, SporkError> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.chars().last() {
+        Some('K') => (&size[..size.len() - 1], 1 << 10),
+        Some('M') => (&size[..size.len() - 1], 1 << 20),
+        Some('G') => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+
+    match digits.parse::<u64>() {
+        Ok(value) => Ok(value * multiplier),
+        Err(_) => Err(parse_error("cache size", size)),
+    }
+}
+
+fn read_caches(cpu_dir: &Path) -> Result<Vec<CpuCache>, SporkError> {
+    let entries = match fs::read_dir(cpu_dir.join("cache")) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut caches = Vec::new();
+    for entry in entries {
+        let dir = entry?.path();
+        if !dir
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or_default()
+            .starts_with("index")
+        {
+            continue;
+        }
+        let (level, size) = match (read_u64(&dir.join("level"))?, read_optional(&dir.join("size"))?) {
+            (Some(level), Some(size)) => (level, size),
+            _ => continue,
+        };
+
+        caches.push(CpuCache {
+            level: level as u32,
+            kind: match read_optional(&dir.join("type"))?.as_deref() {
+                Some("Data") => CacheType::Data,
+                Some("Instruction") => CacheType::Instruction,
+                Some("Unified") => CacheType::Unified,
+                _ => CacheType::Unknown,
+            },
+            size: parse_cache_size(&size)?,
+            shared_cpus: match read_optional(&dir.join("shared_cpu_list"))? {
+                Some(list) => parse_cpu_list(&list)?,
+                None => Vec::new(),
+            },
+        });
+    }
+
+    caches.sort_by_key(|cache| (cache.level, cache.kind));
+    Ok(caches)
+}
+
+// cpufreq reports frequencies in kHz
+fn read_frequency(cpu_dir: &Path) -> Result<Option<CpuFrequency>, SporkError> {
+    let dir = cpu_dir.join("cpufreq");
+    let khz = |file: &str| read_u64(&dir.join(file)).map(|value| value.map(|khz| khz * 1000));
+
+    match (khz("cpuinfo_min_freq")?, khz("cpuinfo_max_freq")?) {
+        (Some(min), Some(max)) => Ok(Some(CpuFrequency {
+            current: khz("scaling_cur_freq")?,
+            min,
+            max,
+        })),
+        _ => Ok(None),
+    }
+}
+
+// the NUMA node is only exposed as a `node<N>` link in the CPU's directory
+fn read_node(cpu_dir: &Path) -> Result<Option<u32>, SporkError> {
+    for entry in fs::read_dir(cpu_dir)? {
+        let name = entry?.file_name();
+        if let Some(node) = name.to_str().and_then(|n| n.strip_prefix("node")) {
+            if let Ok(node) = node.parse::<u32>() {
+                return Ok(Some(node));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_cpu(root: &Path, id: usize, online: bool) -> Result<LogicalCpu, SporkError> {
+    let dir = root.join(format!("cpu{}", id));
+    if !online {
+        return Ok(LogicalCpu {
+            online: false,
+            ..LogicalCpu::new(id)
+        });
+    }
+
+    let topology = dir.join("topology");
+    Ok(LogicalCpu {
+        package: read_u64(&topology.join("physical_package_id"))?.map(|id| id as u32),
+        core: read_u64(&topology.join("core_id"))?.map(|id| id as u32),
+        siblings: match read_optional(&topology.join("thread_siblings_list"))? {
+            Some(list) => parse_cpu_list(&list)?,
+            None => vec![id],
+        },
+        node: read_node(&dir)?,
+        caches: read_caches(&dir)?,
+        frequency: read_frequency(&dir)?,
+        ..LogicalCpu::new(id)
+    })
+}
+
+/// Read the topology of every present CPU from a `/sys/devices/system/cpu` directory.
+pub fn read_cpu_topology(root: &Path) -> Result<CpuTopology, SporkError> {
+    let present = match read_optional(&root.join("present"))? {
+        Some(list) => parse_cpu_list(&list)?,
+        None => {
+            return Err(SporkError::new_borrowed(
+                SporkErrorKind::Unimplemented,
+                "No CPUs found in sysfs.",
+            ))
+        }
+    };
+    // every present CPU is online unless the kernel supports hotplug
+    let online = match read_optional(&root.join("online"))? {
+        Some(list) => parse_cpu_list(&list)?,
+        None => present.clone(),
+    };
+
+    Ok(CpuTopology {
+        cpus: present
+            .into_iter()
+            .map(|id| read_cpu(root, id, online.contains(&id)))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+pub fn get_cpu_topology() -> Result<CpuTopology, SporkError> {
+    read_cpu_topology(Path::new(CPU_ROOT))
+}
+
+// -----------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::TempDir;
+
+    #[test]
+    fn should_parse_cache_sizes() {
+        assert_eq!(parse_cache_size("48K\n").unwrap(), 48 * 1024);
+        assert_eq!(parse_cache_size("32M").unwrap(), 32 << 20);
+        assert_eq!(parse_cache_size("512").unwrap(), 512);
+        assert!(parse_cache_size("K").is_err());
+    }
+
+    #[test]
+    fn should_read_cpu_topology() {
+        let dir = TempDir::new("sysfs-topology");
+        dir.write("present", "0-2\n");
+        dir.write("online", "0-1\n");
+        for cpu in 0..2 {
+            dir.write(&format!("cpu{}/topology/physical_package_id", cpu), "0\n");
+            dir.write(&format!("cpu{}/topology/core_id", cpu), "0\n");
+            dir.write(&format!("cpu{}/topology/thread_siblings_list", cpu), "0-1\n");
+            dir.write(&format!("cpu{}/node0/cpulist", cpu), "0-2\n");
+            dir.write(&format!("cpu{}/cache/index0/level", cpu), "1\n");
+            dir.write(&format!("cpu{}/cache/index0/type", cpu), "Data\n");
+            dir.write(&format!("cpu{}/cache/index0/size", cpu), "48K\n");
+            dir.write(&format!("cpu{}/cache/index0/shared_cpu_list", cpu), "0-1\n");
+        }
+        dir.write("cpu0/cpufreq/cpuinfo_min_freq", "800000\n");
+        dir.write("cpu0/cpufreq/cpuinfo_max_freq", "3600000\n");
+        dir.write("cpu0/cpufreq/scaling_cur_freq", "2400000\n");
+        dir.write("cpu2/uevent", "");
+
+        let topology = read_cpu_topology(&dir.0).unwrap();
+        assert_eq!(topology.cpus.len(), 3);
+        assert_eq!(topology.offline(), vec![2]);
+        assert_eq!(topology.physical_cores(), 1);
+        assert_eq!(topology.nodes(), vec![0]);
+
+        let cpu = &topology.cpus[0];
+        assert_eq!(cpu.siblings, vec![0, 1]);
+        assert_eq!(cpu.caches[0].kind, CacheType::Data);
+        assert_eq!(cpu.caches[0].size, 48 * 1024);
+        assert_eq!(
+            cpu.frequency,
+            Some(CpuFrequency {
+                current: Some(2_400_000_000),
+                min: 800_000_000,
+                max: 3_600_000_000,
+            })
+        );
+        assert_eq!(topology.cpus[1].frequency, None);
+        assert_eq!(topology.cpus[2].package, None);
+    }
+
+    #[test]
+    fn should_err_unimplemented_without_cpus() {
+        let dir = TempDir::new("sysfs-empty");
+
+        let err = read_cpu_topology(&dir.0).unwrap_err();
+        assert_eq!(err.kind(), &SporkErrorKind::Unimplemented);
+    }
+
+    #[test]
+    fn should_read_own_cpu_topology() {
+        let topology = get_cpu_topology().unwrap();
+
+        assert!(topology.logical_cpus() > 0);
+        assert!(topology.physical_cores() > 0);
+        assert!(topology.physical_cores() <= topology.logical_cpus());
+    }
+}
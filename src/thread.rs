@@ -0,0 +1,187 @@
+//! Spawn named threads that report their lifetime resource usage when joined.
+//!
+//! Short-lived worker threads often exit before a poller sees them. Threads spawned here read their own counters just
+//! before they exit, so every task's CPU time, context switches and faults are accounted for.
+//!
+//! ```
+//! let handle = spork::thread::spawn("worker", || (0..1_000_000_u64).sum::<u64>()).unwrap();
+//! let (sum, stats) = handle.join().unwrap();
+//!
+//! println!("Sum {} took {:?} and {}s of CPU time ({:?}%), {} context switches",
+//!   sum, stats.duration, stats.cpu_time, stats.cpu,
+//!   stats.extended.voluntary_ctx_switches + stats.extended.involuntary_ctx_switches);
+//! ```
+
+use std::thread::Thread;
+
+use super::*;
+use backend::RawStats;
+
+/// An owned permission to join on a thread spawned by `spawn`, which yields the thread's result along with its lifetime
+/// `Stats`.
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    inner: std::thread::JoinHandle<(T, Stats)>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Wait for the thread to finish, returning its result and the stats read just before it exited, or the panic
+    /// payload if the thread panicked.
+    pub fn join(self) -> std::thread::Result<(T, Stats)> {
+        self.inner.join()
+    }
+
+    /// Read the underlying `std::thread::Thread`.
+    pub fn thread(&self) -> &Thread {
+        self.inner.thread()
+    }
+
+    /// Whether the thread has finished running.
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+}
+
+/// Spawn a thread named `name` that runs `f` and reads the thread's own counters just before it exits. The native
+/// backend is used, and the `uptime` of the resulting stats is measured from the process' monotonic clock origin.
+///
+/// The `Stats` yielded by the `JoinHandle` have the `StatType::Thread` kind and describe the whole life of the thread:
+///
+/// * `duration` is the wall time the thread ran for, measured with the monotonic clock.
+/// * `cpu_time` is the total CPU time used by the thread, in seconds, and `cpu` is the average load over `duration`.
+/// * `memory` is the peak memory of the process, as with `StatType::Thread`.
+/// * `extended` and `extended_delta` both hold the thread's total counters (`RUSAGE_THREAD` on Linux).
+///
+/// The counters are read once from the calling thread before spawning, so backends that can't read thread counters
+/// return an error here rather than from the spawned thread. If reading them still fails as the thread exits, the stats
+/// only hold the wall time, and `cpu` is `None` rather than an idle 0%.
+pub fn spawn<N, F, T>(name: N, f: F) -> Result<JoinHandle<T>, SporkError>
+where
+    N: Into<String>,
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_with(Arc::new(NativeBackend), Duration::ZERO, name.into(), f)
+}
+
+pub(crate) fn spawn_with<F, T>(
+    backend: Arc<dyn StatsBackend>,
+    started: Duration,
+    name: String,
+    f: F,
+) -> Result<JoinHandle<T>, SporkError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    backend.stats(&StatType::Thread)?;
+
+    let inner = std::thread::Builder::new().name(name).spawn(move || {
+        let start = backend.now();
+        let result = f();
+        let stats = lifetime_stats(&*backend, started, start);
+
+        (result, stats)
+    })?;
+
+    Ok(JoinHandle { inner })
+}
+
+fn lifetime_stats(backend: &dyn StatsBackend, started: Duration, start: Duration) -> Stats {
+    let read = backend.stats(&StatType::Thread);
+    let now = backend.now();
+    let duration = now.saturating_sub(start);
+    let (raw, cpu) = match read {
+        Ok(raw) => {
+            let cpu = utils::cpu_percent(raw.cpu_time, duration);
+            (raw, cpu)
+        }
+        Err(_) => (RawStats::default(), None),
+    };
+
+    Stats {
+        kind: StatType::Thread,
+        polled: backend.now_ms(),
+        monotonic: now,
+        duration,
+        cpu_time: raw.cpu_time,
+        cpu,
+        memory: raw.memory,
+        uptime: utils::calc_uptime(started, now),
+        cores: 1,
+        extended: raw.extended.clone(),
+        extended_delta: raw.extended,
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::MockBackend;
+
+    #[test]
+    fn should_report_lifetime_stats_from_mock_backend() {
+        let backend = MockBackend::new();
+        let spork = Spork::with_backend(backend.clone()).unwrap();
+        backend.clock().advance(Duration::from_secs(1));
+
+        let worker = backend.clone();
+        let handle = spork
+            .spawn("mock-worker", move || {
+                worker.clock().advance(Duration::from_secs(4));
+                worker.set_stats(
+                    &StatType::Thread,
+                    RawStats {
+                        cpu_time: 1.0,
+                        memory: 2048,
+                        extended: ExtendedStats {
+                            voluntary_ctx_switches: 3,
+                            ..Default::default()
+                        },
+                    },
+                );
+                std::thread::current().name().map(String::from)
+            })
+            .unwrap();
+
+        let (name, stats) = handle.join().unwrap();
+        assert_eq!(name.as_deref(), Some("mock-worker"));
+        assert_eq!(stats.duration, Duration::from_secs(4));
+        assert_eq!(stats.cpu_time, 1.0);
+        assert_eq!(stats.cpu, Some(25.0));
+        assert_eq!(stats.memory, 2048);
+        assert_eq!(stats.uptime, 5000);
+        assert_eq!(stats.extended_delta.voluntary_ctx_switches, 3);
+    }
+
+    #[test]
+    fn should_report_unknown_cpu_when_final_read_fails() {
+        let backend = MockBackend::new();
+        let spork = Spork::with_backend(backend.clone()).unwrap();
+
+        let worker = backend.clone();
+        let handle = spork
+            .spawn("failing-worker", move || {
+                worker.clock().advance(Duration::from_secs(2));
+                worker.set_error(&StatType::Thread, SporkError::unimplemented());
+            })
+            .unwrap();
+
+        let (_, stats) = handle.join().unwrap();
+        assert_eq!(stats.duration, Duration::from_secs(2));
+        assert_eq!(stats.cpu_time, 0.0);
+        assert_eq!(stats.cpu, None);
+    }
+
+    #[test]
+    fn should_err_before_spawning_without_thread_stats() {
+        let backend = MockBackend::new();
+        let spork = Spork::with_backend(backend.clone()).unwrap();
+        backend.set_error(&StatType::Thread, SporkError::unimplemented());
+
+        let err = spork.spawn("never", || ()).unwrap_err();
+        assert_eq!(err.kind(), &SporkErrorKind::Unimplemented);
+    }
+}
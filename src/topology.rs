@@ -0,0 +1,328 @@
+//! Describe how the logical CPUs of the system map onto packages, physical cores, caches and NUMA nodes.
+//!
+//! `Spork::num_cores` counts logical CPUs, so on a machine with SMT a process keeping every hardware thread busy can
+//! report far more load than the physical cores can actually deliver. A `CpuTopology` can be used to normalize CPU
+//! loads by the number of physical cores instead.
+//!
+//! ```
+//! use spork::{Spork, StatType};
+//!
+//! let spork = Spork::new().unwrap();
+//! let topology = spork.cpu_topology().unwrap();
+//!
+//! println!("{} packages, {} physical cores, {} logical CPUs online, NUMA nodes {:?}",
+//!   topology.packages(), topology.physical_cores(), topology.logical_cpus(), topology.nodes());
+//!
+//! let stats = spork.stats(StatType::Process).unwrap();
+//! println!("CPU: {:?}% of one logical CPU, {:?}% of the physical cores",
+//!   stats.cpu, stats.cpu.map(|cpu| topology.normalize_cpu(cpu)));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use super::*;
+
+/// The kind of data held by a CPU cache.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CacheType {
+    Data,
+    Instruction,
+    Unified,
+    Unknown,
+}
+
+/// A CPU cache, along with the logical CPUs that share it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CpuCache {
+    /// The cache level, such as 1 for the L1 cache.
+    pub level: u32,
+    pub kind: CacheType,
+    /// The size of the cache, in bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "size_bytes"))]
+    pub size: u64,
+    /// The IDs of the logical CPUs that share the cache.
+    pub shared_cpus: Vec<usize>,
+}
+
+/// The frequency range of a logical CPU, in Hz.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CpuFrequency {
+    /// The frequency the CPU is currently running at, or `None` if the driver doesn't report it.
+    #[cfg_attr(feature = "serde", serde(rename = "current_hz"))]
+    pub current: Option<u64>,
+    /// The lowest frequency the hardware supports.
+    #[cfg_attr(feature = "serde", serde(rename = "min_hz"))]
+    pub min: u64,
+    /// The highest frequency the hardware supports.
+    #[cfg_attr(feature = "serde", serde(rename = "max_hz"))]
+    pub max: u64,
+}
+
+/// A single logical CPU, which is one hardware thread of a physical core.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogicalCpu {
+    /// The logical CPU ID, which matches `CpuUsage.core`.
+    pub id: usize,
+    /// Whether the CPU is online. The kernel hides the topology of offline CPUs, so the remaining fields are empty.
+    pub online: bool,
+    /// The physical package (socket) the CPU belongs to.
+    pub package: Option<u32>,
+    /// The physical core the CPU belongs to, which is only unique within a package.
+    pub core: Option<u32>,
+    /// The IDs of the logical CPUs that share the same physical core, including this one.
+    pub siblings: Vec<usize>,
+    /// The NUMA node the CPU belongs to.
+    pub node: Option<u32>,
+    /// The caches the CPU can use, from the lowest level up.
+    pub caches: Vec<CpuCache>,
+    /// The frequency range of the CPU, or `None` without a cpufreq driver, such as in many virtual machines.
+    pub frequency: Option<CpuFrequency>,
+}
+
+impl LogicalCpu {
+    /// Create an online CPU with no topology information.
+    pub fn new(id: usize) -> LogicalCpu {
+        LogicalCpu {
+            id,
+            online: true,
+            package: None,
+            core: None,
+            siblings: Vec::new(),
+            node: None,
+            caches: Vec::new(),
+            frequency: None,
+        }
+    }
+
+    // CPUs without topology information are treated as their own physical core
+    fn physical_core(&self) -> (Option<u32>, Option<u32>, Option<usize>) {
+        match self.core {
+            Some(core) => (self.package, Some(core), None),
+            None => (self.package, None, Some(self.id)),
+        }
+    }
+}
+
+/// The layout of every logical CPU in the system, as returned by `Spork::cpu_topology`. On Linux this is read from
+/// [/sys/devices/system/cpu](https://www.kernel.org/doc/Documentation/ABI/stable/sysfs-devices-system-cpu).
+///
+/// The counts only consider online CPUs.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuTopology {
+    /// Every present logical CPU, online or not, ordered by ID.
+    pub cpus: Vec<LogicalCpu>,
+}
+
+impl CpuTopology {
+    /// Iterate over the online logical CPUs.
+    pub fn online(&self) -> impl Iterator<Item = &LogicalCpu> {
+        self.cpus.iter().filter(|cpu| cpu.online)
+    }
+
+    /// Read the IDs of the offline logical CPUs.
+    pub fn offline(&self) -> Vec<usize> {
+        self.cpus.iter().filter(|cpu| !cpu.online).map(|cpu| cpu.id).collect()
+    }
+
+    /// Count the online logical CPUs.
+    pub fn logical_cpus(&self) -> usize {
+        self.online().count()
+    }
+
+    /// Count the physical packages (sockets) with an online CPU.
+    pub fn packages(&self) -> usize {
+        self.online().map(|cpu| cpu.package).collect::<BTreeSet<_>>().len()
+    }
+
+    /// Count the physical cores with an online CPU. This is less than `logical_cpus` when SMT is enabled.
+    pub fn physical_cores(&self) -> usize {
+        self.online()
+            .map(LogicalCpu::physical_core)
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// Read the NUMA nodes with an online CPU, in ascending order.
+    pub fn nodes(&self) -> Vec<u32> {
+        self.online()
+            .filter_map(|cpu| cpu.node)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Read every distinct cache of the online CPUs, ordered by level and the CPUs that share it.
+    pub fn caches(&self) -> Vec<CpuCache> {
+        let mut caches: Vec<CpuCache> = self
+            .online()
+            .flat_map(|cpu| cpu.caches.iter().cloned())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        caches.sort_by(|a, b| (a.level, a.kind, &a.shared_cpus).cmp(&(b.level, b.kind, &b.shared_cpus)));
+        caches
+    }
+
+    /// Convert a CPU load where 100% is one fully busy logical CPU, such as `Stats.cpu`, into a percentage of the
+    /// capacity of the physical cores, where 100% means every physical core is busy. With SMT this can exceed 100%,
+    /// since hardware threads share the execution units of their core.
+    pub fn normalize_cpu(&self, cpu: f64) -> f64 {
+        cpu / self.physical_cores().max(1) as f64
+    }
+
+    /// Calculate the average utilization of the physical cores from the per-core usage of `system`. A physical core is
+    /// as busy as its busiest hardware thread, since SMT siblings compete for the same execution units. Returns
+    /// `None` if none of the online CPUs appear in `system`.
+    pub fn physical_busy(&self, system: &SystemCpuStats) -> Option<f64> {
+        let mut cores: BTreeMap<_, f64> = BTreeMap::new();
+
+        for usage in system.cores.iter() {
+            let cpu = match usage.core.and_then(|id| self.online().find(|cpu| cpu.id == id)) {
+                Some(cpu) => cpu,
+                None => continue,
+            };
+            let busy = cores.entry(cpu.physical_core()).or_insert(0_f64);
+            *busy = busy.max(usage.busy());
+        }
+
+        if cores.is_empty() {
+            None
+        } else {
+            Some(cores.values().sum::<f64>() / cores.len() as f64)
+        }
+    }
+}
+
+/// Parse a CPU list such as `0-3,8,10-11`, as used throughout `/sys/devices/system/cpu`. An empty list has no CPUs.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, SporkError> {
+    let invalid = || {
+        SporkError::new(
+            SporkErrorKind::Parse,
+            format!("Invalid CPU list format: {}", list.trim()),
+        )
+    };
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start, end),
+            None => (range, range),
+        };
+        let start = start.parse::<usize>().map_err(|_| invalid())?;
+        let end = end.parse::<usize>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        cpus.extend(start..=end);
+    }
+
+    Ok(cpus)
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two packages with two physical cores each, with SMT enabled and CPU 7 offline
+    fn topology() -> CpuTopology {
+        let cpus = (0..8)
+            .map(|id| {
+                let core = (id % 4) as u32;
+                LogicalCpu {
+                    online: id != 7,
+                    package: Some(core / 2),
+                    core: Some(core % 2),
+                    siblings: vec![id % 4, id % 4 + 4],
+                    node: Some(core / 2),
+                    caches: vec![CpuCache {
+                        level: 3,
+                        kind: CacheType::Unified,
+                        size: 32 << 20,
+                        shared_cpus: if core < 2 { vec![0, 1, 4, 5] } else { vec![2, 3, 6, 7] },
+                    }],
+                    ..LogicalCpu::new(id)
+                }
+            })
+            .collect();
+
+        CpuTopology { cpus }
+    }
+
+    fn usage(core: usize, user: f64) -> CpuUsage {
+        CpuUsage {
+            core: Some(core),
+            user,
+            idle: 100_f64 - user,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_parse_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n").unwrap(), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5").unwrap(), vec![5]);
+        assert!(parse_cpu_list("\n").unwrap().is_empty());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn should_count_topology() {
+        let topology = topology();
+
+        assert_eq!(topology.logical_cpus(), 7);
+        assert_eq!(topology.offline(), vec![7]);
+        assert_eq!(topology.packages(), 2);
+        assert_eq!(topology.physical_cores(), 4);
+        assert_eq!(topology.nodes(), vec![0, 1]);
+        assert_eq!(topology.caches().len(), 2);
+    }
+
+    #[test]
+    fn should_treat_cpus_without_topology_as_cores() {
+        let topology = CpuTopology {
+            cpus: (0..3).map(LogicalCpu::new).collect(),
+        };
+
+        assert_eq!(topology.physical_cores(), 3);
+        assert_eq!(topology.packages(), 1);
+        assert_eq!(topology.normalize_cpu(150_f64), 50_f64);
+    }
+
+    #[test]
+    fn should_normalize_by_physical_cores() {
+        let topology = topology();
+        assert_eq!(topology.normalize_cpu(200_f64), 50_f64);
+
+        // CPUs 0 and 4 share a core, so the core is as busy as CPU 4
+        let system = SystemCpuStats {
+            polled: 0,
+            monotonic: Duration::ZERO,
+            duration: Duration::from_secs(1),
+            total: CpuUsage::default(),
+            cores: vec![
+                usage(0, 20_f64),
+                usage(4, 60_f64),
+                usage(1, 100_f64),
+                usage(2, 0_f64),
+                usage(3, 0_f64),
+            ],
+        };
+        assert_eq!(topology.physical_busy(&system), Some(40_f64));
+
+        let empty = SystemCpuStats {
+            cores: Vec::new(),
+            ..system
+        };
+        assert_eq!(topology.physical_busy(&empty), None);
+    }
+}
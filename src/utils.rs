@@ -2,130 +2,314 @@ use chrono::Utc;
 
 use libc::timespec;
 
-use std::borrow::BorrowMut;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::ops::{Deref, DerefMut};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError, Weak};
 use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 use super::*;
+use backend::{SystemCpuTimes, ThreadClock};
+use window::WindowConfig;
 
 pub fn get_thread_id() -> ThreadId {
     std::thread::current().id()
 }
 
+thread_local! {
+    // dropped when the thread exits, which is how the history detects dead threads
+    static ALIVE: Arc<()> = Arc::new(());
+}
+
+/// Read a token for the calling thread that can no longer be upgraded once the thread has exited. Threads polled from
+/// a thread-local destructor get a token that is already dead.
+pub fn get_thread_token() -> Weak<()> {
+    ALIVE.try_with(Arc::downgrade).unwrap_or_default()
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CpuTime {
     pub sec: u64,
     pub usec: u64,
 }
 
-#[derive(Clone, Debug)]
+// recover the data from a poisoned lock, since every update to the history is a single insert or remove
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Debug)]
 pub struct History {
-    process: RefCell<Option<Stats>>,
-    // maps thread_id's to the last polled stats
-    thread: RefCell<HashMap<ThreadId, Stats>>,
-    // maps thread_id's to the last polled stats
-    children: RefCell<HashMap<ThreadId, Stats>>,
+    // the retention policy for each of the rings below
+    window: WindowConfig,
+    process: Mutex<VecDeque<Stats>>,
+    // maps thread_id's to the recently polled stats
+    thread: Mutex<HashMap<ThreadId, VecDeque<Stats>>>,
+    // maps thread_id's to the recently polled stats
+    children: Mutex<HashMap<ThreadId, VecDeque<Stats>>>,
+    // maps the thread_id's with thread or children history to a token that dies with the thread
+    alive: Mutex<HashMap<ThreadId, Weak<()>>>,
+    // maps pids to the recently polled stats
+    pids: Mutex<HashMap<u32, VecDeque<Stats>>>,
+    // maps the clocks of registered thread handles to the recently polled stats
+    handles: Mutex<HashMap<ThreadClock, VecDeque<Stats>>>,
+    // maps tids to the stats from the last thread snapshot
+    tasks: Mutex<HashMap<u32, ThreadStats>>,
+    // the monotonic time and raw times of the last system CPU poll
+    system: Mutex<Option<(Duration, SystemCpuTimes)>>,
+    // maps pids, or None for the current process, to the last polled I/O stats
+    io: Mutex<HashMap<Option<u32>, IoStats>>,
 }
 
 impl Default for History {
     fn default() -> Self {
         History {
-            process: RefCell::new(None),
-            thread: RefCell::new(HashMap::new()),
-            children: RefCell::new(HashMap::new()),
+            window: WindowConfig::default(),
+            process: Mutex::new(VecDeque::new()),
+            thread: Mutex::new(HashMap::new()),
+            children: Mutex::new(HashMap::new()),
+            alive: Mutex::new(HashMap::new()),
+            pids: Mutex::new(HashMap::new()),
+            handles: Mutex::new(HashMap::new()),
+            tasks: Mutex::new(HashMap::new()),
+            system: Mutex::new(None),
+            io: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Clone for History {
+    fn clone(&self) -> Self {
+        History {
+            window: self.window.clone(),
+            process: Mutex::new(lock(&self.process).clone()),
+            thread: Mutex::new(lock(&self.thread).clone()),
+            children: Mutex::new(lock(&self.children).clone()),
+            alive: Mutex::new(lock(&self.alive).clone()),
+            pids: Mutex::new(lock(&self.pids).clone()),
+            handles: Mutex::new(lock(&self.handles).clone()),
+            tasks: Mutex::new(lock(&self.tasks).clone()),
+            system: Mutex::new(lock(&self.system).clone()),
+            io: Mutex::new(lock(&self.io).clone()),
         }
     }
 }
 
 impl History {
+    /// Change the retention policy, trimming any polls that now fall outside the window.
+    pub fn set_window(&mut self, window: WindowConfig) {
+        let trim = |ring: &mut VecDeque<Stats>| {
+            if let Some(last) = ring.pop_back() {
+                let older: Vec<Stats> = ring.drain(..).collect();
+                for stats in older {
+                    window.push(ring, stats);
+                }
+                window.push(ring, last);
+            }
+        };
+
+        trim(&mut lock(&self.process));
+        lock(&self.thread).values_mut().for_each(trim);
+        lock(&self.children).values_mut().for_each(trim);
+        lock(&self.pids).values_mut().for_each(trim);
+        lock(&self.handles).values_mut().for_each(trim);
+        self.window = window;
+    }
+
     pub fn set_last(&self, kind: &StatType, poll: Stats) -> Option<Stats> {
-        let last = self.get_last(kind);
+        let prev = {
+            let mut process;
+            let mut rings;
+            let mut pids;
+            let ring = match *kind {
+                StatType::Process => {
+                    process = lock(&self.process);
+                    &mut *process
+                }
+                StatType::Thread => {
+                    rings = lock(&self.thread);
+                    rings.entry(get_thread_id()).or_default()
+                }
+                StatType::Children => {
+                    rings = lock(&self.children);
+                    rings.entry(get_thread_id()).or_default()
+                }
+                StatType::Pid(pid) => {
+                    pids = lock(&self.pids);
+                    pids.entry(pid).or_default()
+                }
+            };
+
+            let prev = ring.back().cloned();
+            self.window.push(ring, poll);
+            prev
+        };
 
-        match *kind {
-            StatType::Process => {
-                let mut process = self.process.borrow_mut();
-                let process_ref = process.deref_mut();
+        if prev.is_none() && matches!(*kind, StatType::Thread | StatType::Children) {
+            self.track_thread();
+        }
+        prev
+    }
 
-                *process_ref = Some(poll);
-            }
-            StatType::Thread => {
-                let mut threads = self.thread.borrow_mut();
-                let threads_ref = threads.borrow_mut();
+    // remember when the calling thread exits, and clean up after any threads that exited since the last new thread
+    fn track_thread(&self) {
+        let id = get_thread_id();
+        let mut alive = lock(&self.alive);
+        if alive.get(&id).map(|token| token.strong_count() > 0).unwrap_or(false) {
+            return;
+        }
+        alive.insert(id, get_thread_token());
+        drop(alive);
 
-                threads_ref.insert(get_thread_id(), poll);
-            }
-            StatType::Children => {
-                let mut children = self.children.borrow_mut();
-                let children_ref = children.borrow_mut();
+        self.prune_threads();
+    }
 
-                children_ref.insert(get_thread_id(), poll);
+    /// Remove the thread and children history of every thread that has exited, returning the number of entries removed.
+    pub fn prune_threads(&self) -> usize {
+        let dead: Vec<ThreadId> = {
+            let mut alive = lock(&self.alive);
+            let dead: Vec<ThreadId> = alive
+                .iter()
+                .filter(|(_, token)| token.strong_count() == 0)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in dead.iter() {
+                alive.remove(id);
             }
+            dead
         };
 
-        last
+        let mut removed = 0;
+        for map in [&self.thread, &self.children] {
+            let mut rings = lock(map);
+            removed += dead.iter().filter(|id| rings.remove(id).is_some()).count();
+        }
+        removed
     }
 
     pub fn get_last(&self, kind: &StatType) -> Option<Stats> {
         match *kind {
-            StatType::Process => self.process.clone().into_inner(),
-            StatType::Thread => {
-                let t_id = get_thread_id();
-                let threads = self.thread.borrow();
-                let threads_ref = threads.deref();
+            StatType::Process => lock(&self.process).back().cloned(),
+            StatType::Thread => lock(&self.thread).get(&get_thread_id()).and_then(|r| r.back().cloned()),
+            StatType::Children => lock(&self.children)
+                .get(&get_thread_id())
+                .and_then(|r| r.back().cloned()),
+            StatType::Pid(pid) => lock(&self.pids).get(&pid).and_then(|r| r.back().cloned()),
+        }
+    }
 
-                threads_ref.get(&t_id).cloned()
-            }
-            StatType::Children => {
-                let t_id = get_thread_id();
-                let children = self.children.borrow();
-                let children_ref = children.deref();
+    /// Copy the polls in the rolling window for `kind`, oldest first.
+    pub fn get_window(&self, kind: &StatType) -> Vec<Stats> {
+        let copy = |ring: Option<&VecDeque<Stats>>| ring.map(|r| r.iter().cloned().collect()).unwrap_or_default();
 
-                children_ref.get(&t_id).cloned()
-            }
+        match *kind {
+            StatType::Process => copy(Some(&lock(&self.process))),
+            StatType::Thread => copy(lock(&self.thread).get(&get_thread_id())),
+            StatType::Children => copy(lock(&self.children).get(&get_thread_id())),
+            StatType::Pid(pid) => copy(lock(&self.pids).get(&pid)),
         }
     }
 
+    pub fn window_config(&self) -> &WindowConfig {
+        &self.window
+    }
+
     pub fn clear_last(&self, kind: &StatType) -> Option<Stats> {
-        let last = self.get_last(kind);
+        let ring = match *kind {
+            StatType::Process => Some(std::mem::take(&mut *lock(&self.process))),
+            StatType::Thread => lock(&self.thread).remove(&get_thread_id()),
+            StatType::Children => lock(&self.children).remove(&get_thread_id()),
+            StatType::Pid(pid) => lock(&self.pids).remove(&pid),
+        };
 
-        match *kind {
-            StatType::Process => {
-                let mut process = self.process.borrow_mut();
-                let process_ref = process.deref_mut();
+        ring.and_then(|mut r| r.pop_back())
+    }
+}
 
-                *process_ref = None;
-            }
-            StatType::Thread => {
-                let mut threads = self.thread.borrow_mut();
-                let threads_ref = threads.borrow_mut();
+impl History {
+    pub fn set_handle(&self, clock: &ThreadClock, poll: Stats) -> Option<Stats> {
+        let mut handles = lock(&self.handles);
+        let ring = handles.entry(clock.clone()).or_default();
 
-                threads_ref.remove(&get_thread_id());
-            }
-            StatType::Children => {
-                let mut children = self.children.borrow_mut();
-                let children_ref = children.borrow_mut();
+        let prev = ring.back().cloned();
+        self.window.push(ring, poll);
+        prev
+    }
 
-                children_ref.remove(&get_thread_id());
-            }
-        };
+    pub fn get_handle(&self, clock: &ThreadClock) -> Option<Stats> {
+        lock(&self.handles).get(clock).and_then(|r| r.back().cloned())
+    }
+
+    pub fn get_handle_window(&self, clock: &ThreadClock) -> Vec<Stats> {
+        lock(&self.handles)
+            .get(clock)
+            .map(|r| r.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear_handle(&self, clock: &ThreadClock) -> Option<Stats> {
+        lock(&self.handles).remove(clock).and_then(|mut r| r.pop_back())
+    }
+
+    pub fn get_handle_clocks(&self) -> Vec<ThreadClock> {
+        lock(&self.handles).keys().cloned().collect()
+    }
+
+    /// Count the history entries in each map.
+    pub fn size(&self) -> HistorySize {
+        HistorySize {
+            process: !lock(&self.process).is_empty() as usize,
+            thread: lock(&self.thread).len(),
+            children: lock(&self.children).len(),
+            pids: lock(&self.pids).len(),
+            handles: lock(&self.handles).len(),
+        }
+    }
+
+    /// Replace the last thread snapshot, dropping any threads that have since exited.
+    pub fn set_tasks(&self, snapshot: &[ThreadStats]) {
+        let mut tasks = lock(&self.tasks);
 
-        last
+        tasks.clear();
+        tasks.extend(snapshot.iter().map(|t| (t.tid, t.clone())));
+    }
+
+    pub fn get_task(&self, tid: u32) -> Option<ThreadStats> {
+        lock(&self.tasks).get(&tid).cloned()
+    }
+
+    pub fn get_io(&self, pid: Option<u32>) -> Option<IoStats> {
+        lock(&self.io).get(&pid).cloned()
+    }
+
+    pub fn set_io(&self, pid: Option<u32>, io: IoStats) -> Option<IoStats> {
+        lock(&self.io).insert(pid, io)
+    }
+
+    /// Replace the last system CPU times, returning the previous poll.
+    pub fn set_system(&self, now: Duration, times: SystemCpuTimes) -> Option<(Duration, SystemCpuTimes)> {
+        lock(&self.system).replace((now, times))
     }
 }
 
-pub fn safe_unsigned_sub(lhs: i64, rhs: i64) -> u64 {
-    (lhs - rhs).wrapping_abs() as u64
+// all monotonic timestamps are relative to the first time the clock is read in the process
+static MONOTONIC_ORIGIN: OnceLock<Instant> = OnceLock::new();
+
+/// Read the monotonic clock (`CLOCK_MONOTONIC` on Linux), relative to an arbitrary origin shared by the whole process.
+pub fn monotonic_now() -> Duration {
+    MONOTONIC_ORIGIN.get_or_init(Instant::now).elapsed()
 }
 
-pub fn calc_duration(kind: &StatType, history: &History, started: i64, polled: i64) -> u64 {
+pub fn calc_duration(kind: &StatType, history: &History, started: Duration, now: Duration) -> Duration {
     let last = match history.get_last(kind) {
-        Some(stats) => stats.polled,
+        Some(stats) => stats.monotonic,
         None => started,
     };
 
-    safe_unsigned_sub(last, polled)
+    now.saturating_sub(last)
+}
+
+pub fn calc_uptime(started: Duration, now: Duration) -> u64 {
+    now.saturating_sub(started).as_millis() as u64
 }
 
 pub fn now_ms() -> i64 {
@@ -147,13 +331,61 @@ pub const fn get_platform() -> Platform {
     Platform::Unknown
 }
 
-pub fn calc_cpu_percent(history: &History, kind: &StatType, curr_cpu_time: f64, duration: u64) -> f64 {
+/// Convert a change in CPU time (in seconds) over `duration` into a percentage, or `None` if `duration` is too short.
+pub fn cpu_percent(cpu_time_delta: f64, duration: Duration) -> Option<f64> {
+    if duration < MIN_CPU_INTERVAL {
+        None
+    } else {
+        Some((cpu_time_delta / duration.as_secs_f64()) * 100_f64)
+    }
+}
+
+pub fn calc_cpu_percent(history: &History, kind: &StatType, curr_cpu_time: f64, duration: Duration) -> Option<f64> {
     let prev_cpu_time = match history.get_last(kind) {
         Some(stats) => stats.cpu_time,
         None => 0_f64,
     };
-    let cpu_time_delta = curr_cpu_time - prev_cpu_time;
-    ((cpu_time_delta / duration as f64) * 1000_f64) * 100_f64
+    cpu_percent(curr_cpu_time - prev_cpu_time, duration)
+}
+
+/// Drop the `StatType::Pid` history of a process that reports less CPU time than at its last poll, which means the pid
+/// was reused by a new process. Returns whether the history was dropped. The new process started after the last poll,
+/// so measuring its CPU time from zero over the time since that poll is still accurate.
+pub fn reset_reused_pid(history: &History, kind: &StatType, curr_cpu_time: f64) -> bool {
+    if !matches!(kind, StatType::Pid(_)) {
+        return false;
+    }
+
+    match history.get_last(kind) {
+        Some(stats) if curr_cpu_time < stats.cpu_time => history.clear_last(kind).is_some(),
+        _ => false,
+    }
+}
+
+pub fn calc_extended_delta(history: &History, kind: &StatType, curr: &ExtendedStats) -> ExtendedStats {
+    match history.get_last(kind) {
+        Some(stats) => curr.delta(&stats.extended),
+        None => curr.clone(),
+    }
+}
+
+pub fn calc_task_cpu_percent(history: &History, tid: u32, curr_cpu_time: f64, duration: Duration) -> Option<f64> {
+    let prev_cpu_time = match history.get_task(tid) {
+        Some(stats) => stats.user_time + stats.system_time,
+        None => 0_f64,
+    };
+    cpu_percent(curr_cpu_time - prev_cpu_time, duration)
+}
+
+/// Calculate the usage of each core, matching cores to the previous poll by their ID. Cores that were offline during the
+/// previous poll report their usage since boot.
+pub fn calc_core_usage(curr: &[CpuTimes], prev: &[CpuTimes]) -> Vec<CpuUsage> {
+    curr.iter()
+        .map(|times| match prev.iter().find(|p| p.core == times.core) {
+            Some(prev) => CpuUsage::from_delta(&times.delta(prev)),
+            None => CpuUsage::from_delta(times),
+        })
+        .collect()
 }
 
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
@@ -179,11 +411,96 @@ pub fn empty_timespec() -> timespec {
     timespec { tv_sec: 0, tv_nsec: 0 }
 }
 
+/// Round an interval for a background poller up to `MIN_POLL_INTERVAL`, so a zero interval can't busy-spin.
+pub fn poll_interval(interval: Duration) -> Duration {
+    interval.max(MIN_POLL_INTERVAL)
+}
+
+/// Schedules the ticks of a background poller against the monotonic clock, starting with an immediate tick, so slow
+/// ticks do not cause the interval to drift.
+pub struct Ticker {
+    interval: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    pub fn new(interval: Duration) -> Ticker {
+        Ticker {
+            interval: poll_interval(interval),
+            next: Instant::now(),
+        }
+    }
+
+    /// Make the next tick due right away.
+    pub fn reset(&mut self) {
+        self.next = Instant::now();
+    }
+
+    /// Block on `signal` until the next tick is due or `interrupted` returns true for the guarded value, returning the
+    /// guard either way.
+    pub fn wait<'a, T, F>(&self, signal: &Condvar, mut guard: MutexGuard<'a, T>, interrupted: F) -> MutexGuard<'a, T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        loop {
+            let now = Instant::now();
+            if interrupted(&guard) || now >= self.next {
+                return guard;
+            }
+            guard = signal
+                .wait_timeout(guard, self.next - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Schedule the tick after the one that just ran.
+    pub fn advance(&mut self) {
+        self.next += self.interval;
+        let now = Instant::now();
+        if self.next < now {
+            // skip any ticks missed while the last one took longer than the interval
+            let behind = (now - self.next).as_nanos() / self.interval.as_nanos() + 1;
+            self.next += self.interval * behind as u32;
+        }
+    }
+}
+
+/// A directory of fixture files for tests, which is removed when dropped.
+#[cfg(test)]
+pub struct TempDir(pub std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Create an empty directory, unique to this process and `name`.
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("spork-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    /// Write `contents` to `file`, relative to the directory, creating any parent directories.
+    pub fn write(&self, file: &str, contents: &str) {
+        let path = self.0.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 // ---------------------------
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     #[cfg(target_os = "linux")]
@@ -226,9 +543,44 @@ mod tests {
     #[test]
     fn should_create_empty_history() {
         let history = History::default();
-        assert_eq!(history.process.into_inner(), None);
-        assert!(history.thread.into_inner().is_empty());
-        assert!(history.children.into_inner().is_empty());
+        assert!(history.process.into_inner().unwrap().is_empty());
+        assert!(history.thread.into_inner().unwrap().is_empty());
+        assert!(history.children.into_inner().unwrap().is_empty());
+        assert!(history.pids.into_inner().unwrap().is_empty());
+        assert!(history.tasks.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_keep_window_of_history() {
+        let mut history = History::default();
+        let poll = |secs| Stats {
+            monotonic: Duration::from_secs(secs),
+            ..Stats::new_empty(StatType::Pid(1))
+        };
+
+        history.set_last(&StatType::Pid(1), poll(1));
+        history.set_last(&StatType::Pid(1), poll(2));
+        assert_eq!(history.get_window(&StatType::Pid(1)).len(), 1);
+
+        history.set_window(WindowConfig::new(3));
+        for secs in 3..6 {
+            history.set_last(&StatType::Pid(1), poll(secs));
+        }
+        let window = history.get_window(&StatType::Pid(1));
+        assert_eq!(window.len(), 3);
+        assert_eq!(window[0].monotonic, Duration::from_secs(3));
+        assert_eq!(
+            history.get_last(&StatType::Pid(1)).unwrap().monotonic,
+            Duration::from_secs(5)
+        );
+
+        history.set_window(WindowConfig::new(2));
+        assert_eq!(history.get_window(&StatType::Pid(1)).len(), 2);
+        assert_eq!(
+            history.clear_last(&StatType::Pid(1)).unwrap().monotonic,
+            Duration::from_secs(5)
+        );
+        assert!(history.get_window(&StatType::Pid(1)).is_empty());
     }
 
     #[test]
@@ -303,30 +655,169 @@ mod tests {
         assert_eq!(last_stats, stats);
     }
 
+    #[test]
+    fn should_get_last_pid_history() {
+        let history = History::default();
+        let kind = StatType::Pid(42);
+        assert_eq!(history.get_last(&kind), None);
+
+        let stats = Stats::new_empty(kind.clone());
+        let last = history.set_last(&kind, stats.clone());
+        assert_eq!(last, None);
+
+        assert_eq!(history.get_last(&kind), Some(stats));
+        assert_eq!(history.get_last(&StatType::Pid(43)), None);
+    }
+
+    #[test]
+    fn should_clear_pid_history() {
+        let history = History::default();
+        let kind = StatType::Pid(42);
+        let stats = Stats::new_empty(kind.clone());
+
+        history.set_last(&kind, stats.clone());
+        assert_eq!(history.clear_last(&kind), Some(stats));
+        assert_eq!(history.get_last(&kind), None);
+    }
+
+    #[test]
+    fn should_replace_task_history() {
+        let history = History::default();
+        let first = ThreadStats::new_empty(1);
+        let second = ThreadStats::new_empty(2);
+
+        history.set_tasks(&[first.clone(), second.clone()]);
+        assert_eq!(history.get_task(1), Some(first));
+        assert_eq!(history.get_task(2), Some(second.clone()));
+
+        history.set_tasks(std::slice::from_ref(&second));
+        assert_eq!(history.get_task(1), None);
+        assert_eq!(history.get_task(2), Some(second));
+    }
+
+    #[test]
+    fn should_calc_task_cpu_percent_with_history() {
+        let history = History::default();
+        let mut prev = ThreadStats::new_empty(1);
+        prev.user_time = 1_f64;
+        prev.system_time = 0.5_f64;
+        history.set_tasks(&[prev]);
+
+        let percent = calc_task_cpu_percent(&history, 1, 2_f64, Duration::from_secs(1)).unwrap();
+        assert!((percent - 50_f64).abs() < 0.0001);
+    }
+
+    #[test]
+    fn should_clone_history() {
+        let history = History::default();
+        let stats = Stats::new_empty(StatType::Process);
+        history.set_last(&StatType::Process, stats.clone());
+
+        let cloned = history.clone();
+        history.clear_last(&StatType::Process);
+
+        assert_eq!(cloned.get_last(&StatType::Process), Some(stats));
+        assert_eq!(history.get_last(&StatType::Process), None);
+    }
+
+    #[test]
+    fn should_keep_thread_history_separate_across_threads() {
+        let history = Arc::new(History::default());
+        let mut stats = Stats::new_empty(StatType::Thread);
+        stats.polled = 1;
+        history.set_last(&StatType::Thread, stats.clone());
+
+        let handles: Vec<_> = (0..4_i64)
+            .map(|i| {
+                let history = history.clone();
+                std::thread::spawn(move || {
+                    assert_eq!(history.get_last(&StatType::Thread), None);
+
+                    let mut stats = Stats::new_empty(StatType::Thread);
+                    stats.polled = 10 + i;
+                    history.set_last(&StatType::Thread, stats);
+                    history.get_last(&StatType::Thread).unwrap().polled
+                })
+            })
+            .collect();
+
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), 10 + i as i64);
+        }
+        assert_eq!(history.get_last(&StatType::Thread), Some(stats));
+    }
+
     #[test]
     fn should_calc_duration_with_started() {
         let history = History::default();
         let kind = StatType::Thread;
-        let started = 1_i64;
-        let polled = 1000_i64;
+        let started = Duration::from_millis(1);
+        let now = Duration::from_millis(1000);
 
-        let duration = calc_duration(&kind, &history, started, polled);
-        assert_eq!(duration, (polled - started) as u64);
+        let duration = calc_duration(&kind, &history, started, now);
+        assert_eq!(duration, now - started);
     }
 
     #[test]
     fn should_calc_duration_with_history() {
         let history = History::default();
         let kind = StatType::Thread;
-        let started = 1_i64;
-        let polled = 1000_i64;
+        let started = Duration::from_millis(1);
+        let now = Duration::from_millis(1000);
 
         let stats = Stats::new_empty(kind.clone());
-        // stats.polled will be 0
+        // stats.monotonic will be 0
         history.set_last(&kind, stats.clone());
 
-        let duration = calc_duration(&kind, &history, started, polled);
-        assert_eq!(duration, (polled - stats.polled) as u64);
+        let duration = calc_duration(&kind, &history, started, now);
+        assert_eq!(duration, now - stats.monotonic);
+    }
+
+    #[test]
+    fn should_calc_zero_duration_when_clock_goes_backwards() {
+        let history = History::default();
+        let kind = StatType::Process;
+        let duration = calc_duration(&kind, &history, Duration::from_millis(10), Duration::from_millis(5));
+        assert_eq!(duration, Duration::ZERO);
+    }
+
+    #[test]
+    fn should_calc_uptime() {
+        assert_eq!(calc_uptime(Duration::from_millis(5), Duration::from_millis(1505)), 1500);
+        assert_eq!(calc_uptime(Duration::from_millis(5), Duration::from_millis(1)), 0);
+    }
+
+    #[test]
+    fn should_calc_cpu_percent() {
+        let history = History::default();
+        let kind = StatType::Process;
+        let percent = calc_cpu_percent(&history, &kind, 0.25_f64, Duration::from_millis(500)).unwrap();
+        assert!((percent - 50_f64).abs() < 0.0001);
+
+        let mut stats = Stats::new_empty(kind.clone());
+        stats.cpu_time = 0.25_f64;
+        history.set_last(&kind, stats);
+        let percent = calc_cpu_percent(&history, &kind, 0.75_f64, Duration::from_secs(1)).unwrap();
+        assert!((percent - 50_f64).abs() < 0.0001);
+    }
+
+    #[test]
+    fn should_not_calc_cpu_percent_over_short_intervals() {
+        let history = History::default();
+        let kind = StatType::Process;
+        assert_eq!(calc_cpu_percent(&history, &kind, 1_f64, Duration::ZERO), None);
+        assert_eq!(
+            calc_cpu_percent(&history, &kind, 1_f64, Duration::from_micros(10)),
+            None
+        );
+        assert!(calc_cpu_percent(&history, &kind, 1_f64, MIN_CPU_INTERVAL).is_some());
+    }
+
+    #[test]
+    fn should_read_monotonic_clock() {
+        let first = monotonic_now();
+        let second = monotonic_now();
+        assert!(second >= first);
     }
 
     #[test]
@@ -378,19 +869,121 @@ mod tests {
     }
 
     #[test]
-    fn should_do_valid_safe_unsigned_sub() {
-        let lhs = 100_i64;
-        let rhs = 50_i64;
-        let sub = safe_unsigned_sub(lhs, rhs);
-        assert_eq!(sub, (lhs - rhs) as u64);
+    fn should_reset_history_of_reused_pid() {
+        let history = History::default();
+        let kind = StatType::Pid(42);
+        let mut stats = Stats::new_empty(kind.clone());
+        stats.cpu_time = 5_f64;
+        history.set_last(&kind, stats);
+
+        assert!(!reset_reused_pid(&history, &kind, 6_f64));
+        assert!(reset_reused_pid(&history, &kind, 0.5_f64));
+        assert!(history.get_last(&kind).is_none());
+        assert_eq!(
+            calc_cpu_percent(&history, &kind, 0.5_f64, Duration::from_secs(1)),
+            Some(50_f64)
+        );
+
+        // other kinds keep their history
+        let mut stats = Stats::new_empty(StatType::Process);
+        stats.cpu_time = 5_f64;
+        history.set_last(&StatType::Process, stats);
+        assert!(!reset_reused_pid(&history, &StatType::Process, 0.5_f64));
+        assert!(history.get_last(&StatType::Process).is_some());
+    }
+
+    #[test]
+    fn should_calc_extended_delta_without_history() {
+        let history = History::default();
+        let curr = ExtendedStats {
+            minor_faults: 10,
+            voluntary_ctx_switches: 3,
+            ..Default::default()
+        };
+
+        let delta = calc_extended_delta(&history, &StatType::Process, &curr);
+        assert_eq!(delta, curr);
+    }
+
+    #[test]
+    fn should_calc_extended_delta_with_history() {
+        let history = History::default();
+        let kind = StatType::Thread;
+        let mut stats = Stats::new_empty(kind.clone());
+        stats.extended.minor_faults = 10;
+        stats.extended.signals = 2;
+        history.set_last(&kind, stats);
+
+        let curr = ExtendedStats {
+            minor_faults: 15,
+            signals: 1,
+            ..Default::default()
+        };
+        let delta = calc_extended_delta(&history, &kind, &curr);
+        assert_eq!(delta.minor_faults, 5);
+        assert_eq!(delta.signals, 0);
+    }
+
+    #[test]
+    fn should_prune_history_of_exited_threads() {
+        let history = Arc::new(History::default());
+
+        let worker = history.clone();
+        std::thread::spawn(move || {
+            worker.set_last(&StatType::Thread, Stats::new_empty(StatType::Thread));
+            worker.set_last(&StatType::Children, Stats::new_empty(StatType::Children));
+            assert_eq!(worker.size().thread, 1);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(history.size().thread, 1);
+        assert_eq!(history.size().children, 1);
+        assert_eq!(history.prune_threads(), 2);
+        assert_eq!(history.size().total(), 0);
+    }
+
+    #[test]
+    fn should_prune_exited_threads_when_new_thread_polls() {
+        let history = Arc::new(History::default());
+
+        for _ in 0..3 {
+            let worker = history.clone();
+            std::thread::spawn(move || worker.set_last(&StatType::Thread, Stats::new_empty(StatType::Thread)))
+                .join()
+                .unwrap();
+        }
+        history.set_last(&StatType::Thread, Stats::new_empty(StatType::Thread));
+
+        // only the calling thread is still alive
+        assert_eq!(history.size().thread, 1);
+        assert!(history.get_last(&StatType::Thread).is_some());
+        assert_eq!(history.prune_threads(), 0);
     }
 
     #[test]
-    fn should_do_invalid_safe_unsigned_sub() {
-        let lhs = 100_i64;
-        let rhs = -50_i64;
-        let sub = safe_unsigned_sub(lhs, rhs);
-        assert_eq!(sub, 150_u64);
+    fn should_keep_handle_history_per_clock() {
+        let mut history = History::default();
+        history.set_window(WindowConfig::new(2));
+        let first = ThreadClock { tid: 100, clock_id: 1 };
+        let second = ThreadClock { tid: 200, clock_id: 2 };
+
+        for secs in 0..3 {
+            let stats = Stats {
+                monotonic: Duration::from_secs(secs),
+                ..Stats::new_empty(StatType::Thread)
+            };
+            history.set_handle(&first, stats);
+        }
+        history.set_handle(&second, Stats::new_empty(StatType::Thread));
+
+        assert_eq!(history.get_handle_window(&first).len(), 2);
+        assert_eq!(history.get_handle(&first).unwrap().monotonic, Duration::from_secs(2));
+        assert!(history.get_last(&StatType::Thread).is_none());
+
+        assert!(history.clear_handle(&first).is_some());
+        assert!(history.get_handle(&first).is_none());
+        assert!(history.get_handle(&second).is_some());
     }
 
     #[test]
@@ -398,4 +991,38 @@ mod tests {
         let now = now_ms();
         assert!(now > 0);
     }
+
+    #[test]
+    fn should_round_up_zero_poll_interval() {
+        assert_eq!(poll_interval(Duration::ZERO), MIN_POLL_INTERVAL);
+        assert_eq!(poll_interval(Duration::from_secs(1)), Duration::from_secs(1));
+        assert_eq!(Ticker::new(Duration::ZERO).interval, MIN_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn should_skip_missed_ticks() {
+        let mut ticker = Ticker::new(Duration::from_millis(10));
+        let start = ticker.next;
+        ticker.next -= Duration::from_millis(35);
+
+        ticker.advance();
+        assert!(ticker.next > Instant::now() - Duration::from_millis(1));
+        assert!(ticker.next <= start + Duration::from_millis(10));
+    }
+
+    #[test]
+    fn should_wait_for_tick_or_interrupt() {
+        let signal = Condvar::new();
+        let stopped = Mutex::new(true);
+        let ticker = Ticker::new(Duration::from_secs(60));
+
+        // the first tick is due immediately
+        assert!(*ticker.wait(&signal, lock(&stopped), |_| false));
+
+        let mut ticker = ticker;
+        ticker.advance();
+        let before = Instant::now();
+        assert!(*ticker.wait(&signal, lock(&stopped), |stopped| *stopped));
+        assert!(before.elapsed() < Duration::from_secs(1));
+    }
 }
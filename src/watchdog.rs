@@ -0,0 +1,622 @@
+//! Poll a `Spork` instance on an interval and raise alerts when CPU or memory thresholds are crossed.
+//!
+//! ```
+//! use spork::watchdog::{Rule, Source, Watchdog};
+//! use spork::{Spork, StatType};
+//! use std::sync::atomic::AtomicBool;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let busy = Arc::new(AtomicBool::new(false));
+//! let spork = Arc::new(Spork::new().unwrap());
+//!
+//! let mut watchdog = Watchdog::new(spork, Duration::from_secs(1))
+//!   // process CPU > 90% for 30s, recovering once it drops to 80% or less
+//!   .with_rule(Rule::new("high-cpu", Source::Cpu(StatType::Process))
+//!     .above(90.0)
+//!     .for_duration(Duration::from_secs(30))
+//!     .recover_at(80.0))
+//!   // RSS > 2 GiB
+//!   .with_rule(Rule::new("high-rss", Source::Rss).above(2.0 * 1024.0 * 1024.0 * 1024.0))
+//!   // worker thread CPU == 0 for 60s while it's marked busy
+//!   .with_rule(Rule::new("stuck-worker", Source::ThreadCpu("worker".into()))
+//!     .at_most(0.0)
+//!     .for_duration(Duration::from_secs(60))
+//!     .when(busy.clone())
+//!     .on_trigger(|alert| println!("Worker is stuck: {:?}", alert)));
+//!
+//! watchdog.subscribe(|alert| println!("Alert: {:?}", alert));
+//! watchdog.start().unwrap();
+//! // the watchdog thread is stopped and joined when `watchdog` is dropped
+//! ```
+//!
+//! Each rule is a small state machine. A rule triggers once its condition has held on every check for at least
+//! `for_duration`, and recovers once the condition, checked against the `recover_at` threshold, has not held for at
+//! least `recover_after`. Setting `recover_at` below the trigger threshold (for `above` rules) adds hysteresis so a
+//! value hovering around the threshold doesn't flap between the two states.
+//!
+//! Polling `StatType::Process` or `StatType::Pid` updates the history of the `Spork` instance, so give the watchdog
+//! its own instance if other code polls the same kinds. `StatType::Thread` and `StatType::Children` are measured for
+//! the watchdog's own thread, so use `Source::ThreadCpu` to watch other threads.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::*;
+use utils::{lock, Ticker};
+
+/// A callback invoked with an `Alert`.
+pub type AlertCallback = Arc<dyn Fn(&Alert) + Send + Sync + 'static>;
+
+/// The value a `Rule` is checked against.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Source {
+    /// The CPU load (percentage) from `Spork::stats` for the `StatType`. Checks without a CPU load are skipped.
+    Cpu(StatType),
+    /// The memory usage from `Spork::stats` for the `StatType`, in bytes.
+    Memory(StatType),
+    /// The current resident set size of the process from `Spork::memory_stats`, in bytes.
+    Rss,
+    /// The CPU load (percentage) from `Spork::thread_snapshot` of the first thread with this name. The rule is
+    /// skipped while no thread has the name.
+    ThreadCpu(String),
+    /// The CPU load (percentage) from `Spork::thread_snapshot` of the thread with this ID.
+    ThreadCpuTid(u32),
+}
+
+/// How a value is compared to a rule's threshold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+impl Comparison {
+    fn holds(&self, value: f64, threshold: f64) -> bool {
+        match *self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+/// Whether an `Alert` was raised because a rule triggered or recovered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AlertState {
+    Triggered,
+    Recovered,
+}
+
+/// A change in the state of a `Rule`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    /// The name of the rule.
+    pub rule: String,
+    pub state: AlertState,
+    /// The value read on the check that changed the state, or `None` if the rule's `when` flag was unset.
+    pub value: Option<f64>,
+    /// How long the condition held (or didn't, when recovering) before the state changed.
+    pub duration: Duration,
+    /// Monotonic time of the check that changed the state.
+    pub monotonic: Duration,
+    /// Wall-clock time of the check that changed the state, in milliseconds since epoch.
+    pub polled: i64,
+}
+
+/// A threshold on a `Source`, along with its callbacks. Rules default to `above` a threshold of 0, triggering and
+/// recovering on the first check that crosses the threshold.
+#[derive(Clone)]
+pub struct Rule {
+    name: String,
+    source: Source,
+    comparison: Comparison,
+    threshold: f64,
+    recover_at: Option<f64>,
+    for_duration: Duration,
+    recover_after: Duration,
+    when: Option<Arc<AtomicBool>>,
+    on_trigger: Vec<AlertCallback>,
+    on_recover: Vec<AlertCallback>,
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rule")
+            .field("name", &self.name)
+            .field("source", &self.source)
+            .field("comparison", &self.comparison)
+            .field("threshold", &self.threshold)
+            .field("recover_at", &self.recover_at)
+            .field("for_duration", &self.for_duration)
+            .field("recover_after", &self.recover_after)
+            .field("when", &self.when)
+            .finish()
+    }
+}
+
+impl Rule {
+    /// Create a new rule with a name used to identify it in alerts.
+    pub fn new<T: Into<String>>(name: T, source: Source) -> Rule {
+        Rule {
+            name: name.into(),
+            source,
+            comparison: Comparison::Above,
+            threshold: 0_f64,
+            recover_at: None,
+            for_duration: Duration::ZERO,
+            recover_after: Duration::ZERO,
+            when: None,
+            on_trigger: Vec::new(),
+            on_recover: Vec::new(),
+        }
+    }
+
+    /// Trigger when the value is compared to `threshold` with `comparison`.
+    pub fn with_threshold(mut self, comparison: Comparison, threshold: f64) -> Rule {
+        self.comparison = comparison;
+        self.threshold = threshold;
+        self
+    }
+
+    /// Trigger when the value is greater than `threshold`.
+    pub fn above(self, threshold: f64) -> Rule {
+        self.with_threshold(Comparison::Above, threshold)
+    }
+
+    /// Trigger when the value is greater than or equal to `threshold`.
+    pub fn at_least(self, threshold: f64) -> Rule {
+        self.with_threshold(Comparison::AtLeast, threshold)
+    }
+
+    /// Trigger when the value is less than `threshold`.
+    pub fn below(self, threshold: f64) -> Rule {
+        self.with_threshold(Comparison::Below, threshold)
+    }
+
+    /// Trigger when the value is less than or equal to `threshold`.
+    pub fn at_most(self, threshold: f64) -> Rule {
+        self.with_threshold(Comparison::AtMost, threshold)
+    }
+
+    /// Only trigger once the condition has held for `duration`.
+    pub fn for_duration(mut self, duration: Duration) -> Rule {
+        self.for_duration = duration;
+        self
+    }
+
+    /// Recover once the comparison no longer holds against `threshold` rather than the trigger threshold.
+    pub fn recover_at(mut self, threshold: f64) -> Rule {
+        self.recover_at = Some(threshold);
+        self
+    }
+
+    /// Only recover once the recovery condition has held for `duration`.
+    pub fn recover_after(mut self, duration: Duration) -> Rule {
+        self.recover_after = duration;
+        self
+    }
+
+    /// Only check the rule while `flag` is set. While it's unset the condition is treated as not holding, so a
+    /// triggered rule will recover.
+    pub fn when(mut self, flag: Arc<AtomicBool>) -> Rule {
+        self.when = Some(flag);
+        self
+    }
+
+    /// Call `callback` from the watchdog thread whenever the rule triggers.
+    pub fn on_trigger<F>(mut self, callback: F) -> Rule
+    where
+        F: Fn(&Alert) + Send + Sync + 'static,
+    {
+        self.on_trigger.push(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` from the watchdog thread whenever the rule recovers.
+    pub fn on_recover<F>(mut self, callback: F) -> Rule
+    where
+        F: Fn(&Alert) + Send + Sync + 'static,
+    {
+        self.on_recover.push(Arc::new(callback));
+        self
+    }
+
+    /// Read the name of the rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Phase {
+    // the monotonic time the trigger condition started holding, if it does
+    Ok(Option<Duration>),
+    // the monotonic time the recovery condition started holding, if it does
+    Firing(Option<Duration>),
+}
+
+#[derive(Debug)]
+struct RuleState {
+    rule: Rule,
+    phase: Phase,
+}
+
+impl RuleState {
+    /// Advance the state machine with the value read at `now`, where `None` means the rule is gated off by its flag.
+    fn update(&mut self, value: Option<f64>, now: Duration) -> Option<(AlertState, Duration)> {
+        let rule = &self.rule;
+
+        match self.phase {
+            Phase::Ok(since) => {
+                if !value.map(|v| rule.comparison.holds(v, rule.threshold)).unwrap_or(false) {
+                    self.phase = Phase::Ok(None);
+                    return None;
+                }
+
+                let since = since.unwrap_or(now);
+                let held = now.saturating_sub(since);
+                if held >= rule.for_duration {
+                    self.phase = Phase::Firing(None);
+                    Some((AlertState::Triggered, held))
+                } else {
+                    self.phase = Phase::Ok(Some(since));
+                    None
+                }
+            }
+            Phase::Firing(since) => {
+                let recover_at = rule.recover_at.unwrap_or(rule.threshold);
+                if value.map(|v| rule.comparison.holds(v, recover_at)).unwrap_or(false) {
+                    self.phase = Phase::Firing(None);
+                    return None;
+                }
+
+                let since = since.unwrap_or(now);
+                let held = now.saturating_sub(since);
+                if held >= rule.recover_after {
+                    self.phase = Phase::Ok(None);
+                    Some((AlertState::Recovered, held))
+                } else {
+                    self.phase = Phase::Firing(Some(since));
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// The values read on a single check, shared by every rule.
+#[derive(Default)]
+struct Readings {
+    stats: HashMap<StatType, Option<Stats>>,
+    rss: Option<Option<u64>>,
+    threads: Option<Vec<ThreadStats>>,
+}
+
+impl Readings {
+    /// Read the value for `source`, polling each `StatType`, the memory and the thread snapshot at most once.
+    /// Returns `None` if the value isn't available on this check.
+    fn read(&mut self, spork: &Spork, source: &Source) -> Option<f64> {
+        match source {
+            Source::Cpu(kind) => self.stats(spork, kind).and_then(|s| s.cpu),
+            Source::Memory(kind) => self.stats(spork, kind).map(|s| s.memory as f64),
+            Source::Rss => self
+                .rss
+                .get_or_insert_with(|| spork.memory_stats().ok().map(|m| m.rss))
+                .map(|rss| rss as f64),
+            Source::ThreadCpu(name) => self.threads(spork).iter().find(|t| &t.name == name).and_then(|t| t.cpu),
+            Source::ThreadCpuTid(tid) => self.threads(spork).iter().find(|t| t.tid == *tid).and_then(|t| t.cpu),
+        }
+    }
+
+    fn stats(&mut self, spork: &Spork, kind: &StatType) -> Option<&Stats> {
+        self.stats
+            .entry(kind.clone())
+            .or_insert_with(|| spork.stats(kind.clone()).ok())
+            .as_ref()
+    }
+
+    fn threads(&mut self, spork: &Spork) -> &[ThreadStats] {
+        self.threads
+            .get_or_insert_with(|| spork.thread_snapshot().unwrap_or_default())
+    }
+}
+
+struct Shared {
+    rules: Mutex<Vec<RuleState>>,
+    callbacks: Mutex<Vec<AlertCallback>>,
+    stopped: Mutex<bool>,
+    signal: Condvar,
+}
+
+/// Checks a set of `Rule`s against a `Spork` instance from a dedicated thread, calling the callbacks of each rule
+/// whenever it triggers or recovers. Rules can also be checked without the thread with `check`.
+pub struct Watchdog {
+    spork: Arc<Spork>,
+    interval: Duration,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Create a new, stopped `Watchdog` that will check its rules every `interval`, rounded up to `MIN_POLL_INTERVAL`.
+    pub fn new(spork: Arc<Spork>, interval: Duration) -> Watchdog {
+        Watchdog {
+            spork,
+            interval: utils::poll_interval(interval),
+            shared: Arc::new(Shared {
+                rules: Mutex::new(Vec::new()),
+                callbacks: Mutex::new(Vec::new()),
+                stopped: Mutex::new(true),
+                signal: Condvar::new(),
+            }),
+            handle: None,
+        }
+    }
+
+    /// Add a rule to check.
+    pub fn with_rule(self, rule: Rule) -> Watchdog {
+        self.add_rule(rule);
+        self
+    }
+
+    /// Add a rule to check, taking effect on the next check.
+    pub fn add_rule(&self, rule: Rule) {
+        lock(&self.shared.rules).push(RuleState {
+            rule,
+            phase: Phase::Ok(None),
+        });
+    }
+
+    /// Remove the rules named `name`, returning whether any were removed. Removed rules don't recover.
+    pub fn remove_rule(&self, name: &str) -> bool {
+        let mut rules = lock(&self.shared.rules);
+        let len = rules.len();
+        rules.retain(|r| r.rule.name != name);
+        rules.len() != len
+    }
+
+    /// Register a callback to be called with every alert from every rule, after the rule's own callbacks.
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: Fn(&Alert) + Send + Sync + 'static,
+    {
+        lock(&self.shared.callbacks).push(Arc::new(callback));
+    }
+
+    /// Read the names of the rules that are currently triggered.
+    pub fn triggered(&self) -> Vec<String> {
+        lock(&self.shared.rules)
+            .iter()
+            .filter(|r| matches!(r.phase, Phase::Firing(_)))
+            .map(|r| r.rule.name.clone())
+            .collect()
+    }
+
+    /// Read the check interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Check every rule once, calling the callbacks for and returning any alerts raised.
+    pub fn check(&self) -> Vec<Alert> {
+        check(&self.spork, &self.shared)
+    }
+
+    /// Start the watchdog thread, checking the rules immediately. Starting a running watchdog does nothing.
+    pub fn start(&mut self) -> Result<(), SporkError> {
+        {
+            let mut stopped = lock(&self.shared.stopped);
+            if !*stopped {
+                return Ok(());
+            }
+            *stopped = false;
+        }
+
+        let spork = self.spork.clone();
+        let shared = self.shared.clone();
+        let interval = self.interval;
+        let handle = thread::Builder::new()
+            .name("spork-watchdog".into())
+            .spawn(move || run(&spork, &shared, interval));
+
+        match handle {
+            Ok(handle) => {
+                self.handle = Some(handle);
+                Ok(())
+            }
+            Err(e) => {
+                *lock(&self.shared.stopped) = true;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Stop the watchdog thread and wait for it to exit. Rules keep their state, so the watchdog can be started again.
+    pub fn stop(&mut self) {
+        *lock(&self.shared.stopped) = true;
+        self.shared.signal.notify_all();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Whether the watchdog thread is running.
+    pub fn is_running(&self) -> bool {
+        !*lock(&self.shared.stopped)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn check(spork: &Spork, shared: &Shared) -> Vec<Alert> {
+    let mut readings = Readings::default();
+    let mut raised = Vec::new();
+
+    {
+        let mut rules = lock(&shared.rules);
+        let now = spork.backend.now();
+        let polled = spork.backend.now_ms();
+
+        for state in rules.iter_mut() {
+            let enabled = state
+                .rule
+                .when
+                .as_ref()
+                .map(|f| f.load(Ordering::Acquire))
+                .unwrap_or(true);
+            let value = if enabled {
+                match readings.read(spork, &state.rule.source) {
+                    Some(value) => Some(value),
+                    // skip rules whose value can't be read on this check
+                    None => continue,
+                }
+            } else {
+                None
+            };
+
+            if let Some((alert_state, duration)) = state.update(value, now) {
+                let callbacks = match alert_state {
+                    AlertState::Triggered => state.rule.on_trigger.clone(),
+                    AlertState::Recovered => state.rule.on_recover.clone(),
+                };
+                let alert = Alert {
+                    rule: state.rule.name.clone(),
+                    state: alert_state,
+                    value,
+                    duration,
+                    monotonic: now,
+                    polled,
+                };
+                raised.push((alert, callbacks));
+            }
+        }
+    }
+
+    // call back without holding the rules lock, so callbacks can read or change the rules
+    let subscribers = lock(&shared.callbacks).clone();
+    raised
+        .into_iter()
+        .map(|(alert, callbacks)| {
+            for callback in callbacks.iter().chain(subscribers.iter()) {
+                callback(&alert);
+            }
+            alert
+        })
+        .collect()
+}
+
+fn run(spork: &Spork, shared: &Shared, interval: Duration) {
+    let mut ticker = Ticker::new(interval);
+
+    loop {
+        if *ticker.wait(&shared.signal, lock(&shared.stopped), |stopped| *stopped) {
+            return;
+        }
+
+        check(spork, shared);
+        ticker.advance();
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(rule: Rule) -> RuleState {
+        RuleState {
+            rule,
+            phase: Phase::Ok(None),
+        }
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn should_trigger_after_duration() {
+        let mut state = state(Rule::new("cpu", Source::Rss).above(90.0).for_duration(secs(30)));
+
+        assert_eq!(state.update(Some(95.0), secs(0)), None);
+        assert_eq!(state.update(Some(95.0), secs(20)), None);
+        assert_eq!(
+            state.update(Some(95.0), secs(30)),
+            Some((AlertState::Triggered, secs(30)))
+        );
+        assert_eq!(state.update(Some(95.0), secs(40)), None);
+    }
+
+    #[test]
+    fn should_reset_pending_trigger() {
+        let mut state = state(Rule::new("cpu", Source::Rss).above(90.0).for_duration(secs(30)));
+
+        state.update(Some(95.0), secs(0));
+        state.update(Some(50.0), secs(20));
+        assert_eq!(state.update(Some(95.0), secs(30)), None);
+        assert_eq!(
+            state.update(Some(95.0), secs(60)),
+            Some((AlertState::Triggered, secs(30)))
+        );
+    }
+
+    #[test]
+    fn should_recover_with_hysteresis() {
+        let mut state = state(Rule::new("cpu", Source::Rss).above(90.0).recover_at(80.0));
+
+        assert_eq!(
+            state.update(Some(91.0), secs(0)),
+            Some((AlertState::Triggered, secs(0)))
+        );
+        // hovering around the trigger threshold doesn't recover
+        assert_eq!(state.update(Some(89.0), secs(1)), None);
+        assert_eq!(state.update(Some(91.0), secs(2)), None);
+        assert_eq!(
+            state.update(Some(80.0), secs(3)),
+            Some((AlertState::Recovered, secs(0)))
+        );
+    }
+
+    #[test]
+    fn should_recover_after_duration() {
+        let mut state = state(Rule::new("idle", Source::Rss).at_most(0.0).recover_after(secs(10)));
+
+        assert_eq!(state.update(Some(0.0), secs(0)), Some((AlertState::Triggered, secs(0))));
+        assert_eq!(state.update(Some(5.0), secs(5)), None);
+        assert_eq!(state.update(Some(0.0), secs(10)), None);
+        assert_eq!(state.update(Some(5.0), secs(12)), None);
+        assert_eq!(
+            state.update(Some(5.0), secs(22)),
+            Some((AlertState::Recovered, secs(10)))
+        );
+    }
+
+    #[test]
+    fn should_treat_gated_rules_as_not_holding() {
+        let mut state = state(Rule::new("idle", Source::Rss).at_most(0.0));
+
+        assert_eq!(state.update(None, secs(0)), None);
+        assert_eq!(state.update(Some(0.0), secs(1)), Some((AlertState::Triggered, secs(0))));
+        assert_eq!(state.update(None, secs(2)), Some((AlertState::Recovered, secs(0))));
+    }
+
+    #[test]
+    fn should_round_up_zero_interval() {
+        let watchdog = Watchdog::new(Arc::new(Spork::new().unwrap()), Duration::ZERO);
+        assert_eq!(watchdog.interval(), MIN_POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,320 @@
+//! Keep a rolling window of recent `Stats` per history key and summarize it with smoothed values.
+//!
+//! By default a `Spork` only keeps the most recent poll of each `StatType`, so every CPU load is the value over a single
+//! interval. Configuring a `WindowConfig` keeps the most recent polls instead, which can be queried for the mean,
+//! min, max, EWMA and percentiles of the CPU load and memory usage.
+//!
+//! ```
+//! use spork::window::{Metric, WindowConfig};
+//! use spork::{Spork, StatType};
+//! use std::time::Duration;
+//!
+//! let spork = Spork::new().unwrap().with_window(WindowConfig::new(60).with_max_age(Duration::from_secs(60)));
+//!
+//! // poll periodically...
+//! spork.stats(StatType::Process).unwrap();
+//!
+//! let window = spork.window(StatType::Process);
+//! println!("CPU over the last minute: mean {:?}%, p95 {:?}%, EWMA {:?}%",
+//!   window.mean(Metric::Cpu), window.p95(Metric::Cpu), window.ewma(Metric::Cpu));
+//! ```
+
+use std::collections::VecDeque;
+
+use super::*;
+
+/// The default smoothing factor used by `Window::ewma`.
+pub const DEFAULT_EWMA_ALPHA: f64 = 0.3;
+
+/// The retention policy for the rolling window kept for each history key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowConfig {
+    max_samples: usize,
+    max_age: Option<Duration>,
+    ewma_alpha: f64,
+}
+
+impl Default for WindowConfig {
+    /// Keep only the most recent poll, which is enough to calculate the CPU load.
+    fn default() -> Self {
+        WindowConfig::new(1)
+    }
+}
+
+impl WindowConfig {
+    /// Keep at most `max_samples` polls per history key. The most recent poll is always kept, so 0 is treated as 1.
+    pub fn new(max_samples: usize) -> WindowConfig {
+        WindowConfig {
+            max_samples: max_samples.max(1),
+            max_age: None,
+            ewma_alpha: DEFAULT_EWMA_ALPHA,
+        }
+    }
+
+    /// Also drop polls older than `max_age`, measured back from the most recent poll with the monotonic clock.
+    pub fn with_max_age(mut self, max_age: Duration) -> WindowConfig {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set the smoothing factor used by `Window::ewma`, clamped to (0, 1]. Larger values weight recent polls more.
+    pub fn with_ewma_alpha(mut self, alpha: f64) -> WindowConfig {
+        self.ewma_alpha = if alpha.is_finite() {
+            alpha.clamp(f64::EPSILON, 1_f64)
+        } else {
+            DEFAULT_EWMA_ALPHA
+        };
+        self
+    }
+
+    /// Read the maximum number of polls kept per history key.
+    pub fn max_samples(&self) -> usize {
+        self.max_samples
+    }
+
+    /// Read the maximum age of the polls kept per history key, if any.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Read the smoothing factor used by `Window::ewma`.
+    pub fn ewma_alpha(&self) -> f64 {
+        self.ewma_alpha
+    }
+
+    /// Append `stats` to `ring` and drop the polls that fall outside the window.
+    pub(crate) fn push(&self, ring: &mut VecDeque<Stats>, stats: Stats) {
+        let newest = stats.monotonic;
+        ring.push_back(stats);
+
+        while ring.len() > self.max_samples {
+            ring.pop_front();
+        }
+        if let Some(max_age) = self.max_age {
+            while ring.len() > 1
+                && ring
+                    .front()
+                    .map(|s| newest.saturating_sub(s.monotonic) > max_age)
+                    .unwrap_or(false)
+            {
+                ring.pop_front();
+            }
+        }
+    }
+}
+
+/// A value that can be summarized over a `Window`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Metric {
+    /// The CPU load (percentage) of each poll. Polls without a CPU load are skipped.
+    Cpu,
+    /// The `Stats.memory` of each poll, in bytes. For `StatType::Process` and `StatType::Thread` this is the peak
+    /// resident memory, which never decreases, so the summaries describe how the high-water mark grew over the window
+    /// rather than the current usage. Poll `Spork::memory_stats` for the current resident memory instead.
+    Memory,
+}
+
+/// All of the summary values for one `Metric` over a `Window`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub metric: Metric,
+    /// The number of polls with a value for the metric.
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub ewma: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// A copy of the polls in the rolling window for one history key, oldest first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    samples: Vec<Stats>,
+    ewma_alpha: f64,
+}
+
+impl Window {
+    pub(crate) fn new(samples: Vec<Stats>, ewma_alpha: f64) -> Window {
+        Window { samples, ewma_alpha }
+    }
+
+    /// Read the polls in the window, oldest first.
+    pub fn samples(&self) -> &[Stats] {
+        &self.samples
+    }
+
+    /// Read the number of polls in the window.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether the window has no polls.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Read the monotonic time between the oldest and newest polls in the window.
+    pub fn span(&self) -> Duration {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => last.monotonic.saturating_sub(first.monotonic),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn values(&self, metric: Metric) -> Vec<f64> {
+        match metric {
+            Metric::Cpu => self.samples.iter().filter_map(|s| s.cpu).collect(),
+            Metric::Memory => self.samples.iter().map(|s| s.memory as f64).collect(),
+        }
+    }
+
+    /// Calculate the arithmetic mean of the metric over the polls in the window.
+    pub fn mean(&self, metric: Metric) -> Option<f64> {
+        let values = self.values(metric);
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f64>() / values.len() as f64)
+        }
+    }
+
+    /// Read the smallest value of the metric in the window.
+    pub fn min(&self, metric: Metric) -> Option<f64> {
+        self.values(metric).into_iter().reduce(f64::min)
+    }
+
+    /// Read the largest value of the metric in the window.
+    pub fn max(&self, metric: Metric) -> Option<f64> {
+        self.values(metric).into_iter().reduce(f64::max)
+    }
+
+    /// Calculate the exponentially weighted moving average of the metric, seeded with the oldest poll in the window.
+    pub fn ewma(&self, metric: Metric) -> Option<f64> {
+        let alpha = self.ewma_alpha;
+        self.values(metric)
+            .into_iter()
+            .reduce(|avg, value| alpha * value + (1_f64 - alpha) * avg)
+    }
+
+    /// Calculate the `percentile` (0 to 100) of the metric over the window with the nearest-rank method.
+    pub fn percentile(&self, metric: Metric, percentile: f64) -> Option<f64> {
+        let mut values = self.values(metric);
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+
+        let rank = ((percentile.clamp(0_f64, 100_f64) / 100_f64) * values.len() as f64).ceil() as usize;
+        Some(values[rank.saturating_sub(1).min(values.len() - 1)])
+    }
+
+    /// Calculate the median of the metric over the window.
+    pub fn p50(&self, metric: Metric) -> Option<f64> {
+        self.percentile(metric, 50_f64)
+    }
+
+    /// Calculate the 95th percentile of the metric over the window.
+    pub fn p95(&self, metric: Metric) -> Option<f64> {
+        self.percentile(metric, 95_f64)
+    }
+
+    /// Calculate the 99th percentile of the metric over the window.
+    pub fn p99(&self, metric: Metric) -> Option<f64> {
+        self.percentile(metric, 99_f64)
+    }
+
+    /// Calculate every summary value for the metric at once, or `None` if no poll in the window has a value for it.
+    pub fn summary(&self, metric: Metric) -> Option<Summary> {
+        Some(Summary {
+            metric,
+            count: self.values(metric).len(),
+            mean: self.mean(metric)?,
+            min: self.min(metric)?,
+            max: self.max(metric)?,
+            ewma: self.ewma(metric)?,
+            p50: self.p50(metric)?,
+            p95: self.p95(metric)?,
+            p99: self.p99(metric)?,
+        })
+    }
+}
+
+// ---------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(secs: u64, cpu: Option<f64>, memory: u64) -> Stats {
+        Stats {
+            monotonic: Duration::from_secs(secs),
+            cpu,
+            memory,
+            ..Stats::new_empty(StatType::Process)
+        }
+    }
+
+    #[test]
+    fn should_retain_max_samples() {
+        let config = WindowConfig::new(3);
+        let mut ring = VecDeque::new();
+        for i in 0..5 {
+            config.push(&mut ring, poll(i, None, i));
+        }
+
+        let memory: Vec<u64> = ring.iter().map(|s| s.memory).collect();
+        assert_eq!(memory, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn should_retain_max_age() {
+        let config = WindowConfig::new(100).with_max_age(Duration::from_secs(10));
+        let mut ring = VecDeque::new();
+        for secs in [0, 5, 12, 15] {
+            config.push(&mut ring, poll(secs, None, secs));
+        }
+        assert_eq!(ring.iter().map(|s| s.memory).collect::<Vec<_>>(), vec![5, 12, 15]);
+
+        // the newest poll is always kept
+        config.push(&mut ring, poll(100, None, 100));
+        assert_eq!(ring.len(), 1);
+    }
+
+    #[test]
+    fn should_summarize_window() {
+        let samples = (1..=10).map(|i| poll(i, Some(i as f64 * 10_f64), i * 1024)).collect();
+        let window = Window::new(samples, 0.5);
+
+        assert_eq!(window.len(), 10);
+        assert_eq!(window.span(), Duration::from_secs(9));
+        assert_eq!(window.mean(Metric::Cpu), Some(55.0));
+        assert_eq!(window.min(Metric::Cpu), Some(10.0));
+        assert_eq!(window.max(Metric::Memory), Some(10240.0));
+        assert_eq!(window.p50(Metric::Cpu), Some(50.0));
+        assert_eq!(window.p95(Metric::Cpu), Some(100.0));
+        assert_eq!(window.percentile(Metric::Cpu, 0_f64), Some(10.0));
+
+        let ewma = window.ewma(Metric::Cpu).unwrap();
+        assert!(ewma > 55.0 && ewma < 100.0);
+        assert_eq!(window.summary(Metric::Cpu).unwrap().count, 10);
+    }
+
+    #[test]
+    fn should_skip_polls_without_cpu() {
+        let window = Window::new(vec![poll(0, None, 1), poll(1, Some(20.0), 3)], DEFAULT_EWMA_ALPHA);
+
+        assert_eq!(window.mean(Metric::Cpu), Some(20.0));
+        assert_eq!(window.ewma(Metric::Cpu), Some(20.0));
+        assert_eq!(window.mean(Metric::Memory), Some(2.0));
+
+        let empty = Window::new(vec![poll(0, None, 1)], DEFAULT_EWMA_ALPHA);
+        assert_eq!(empty.summary(Metric::Cpu), None);
+        assert_eq!(empty.p99(Metric::Cpu), None);
+    }
+}
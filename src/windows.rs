@@ -10,6 +10,8 @@ use utils::CpuTime;
 
 use super::*;
 
+use backend::{RawStats, StatsBackend};
+
 fn get_thread_handle() -> HANDLE {
     unsafe { GetCurrentThread() }
 }
@@ -40,6 +42,12 @@ pub fn get_mem_stats(kind: &StatType) -> Result<PROCESS_MEMORY_COUNTERS, SporkEr
                 "Windows child thread memory stat not yet implemented!".to_owned(),
             ))
         }
+        &StatType::Pid(_) => {
+            return Err(SporkError::new(
+                SporkErrorKind::Unimplemented,
+                "Windows PID memory stat not yet implemented!".to_owned(),
+            ))
+        }
     };
 
     // SAFETY: Check the last windows error to ensure that the returned value
@@ -54,16 +62,20 @@ pub fn get_mem_stats(kind: &StatType) -> Result<PROCESS_MEMORY_COUNTERS, SporkEr
 
         if result == 0 {
             let error = unsafe { GetLastError() };
-            return Err(SporkError::new(
-                SporkErrorKind::Unknown,
-                format!("Win32Error: {error:x}"),
-            ));
+            return Err(std::io::Error::from_raw_os_error(error as i32).into());
         }
         memory.assume_init()
     };
     Ok(memory)
 }
 
+pub fn get_extended_stats(mem: &PROCESS_MEMORY_COUNTERS) -> ExtendedStats {
+    ExtendedStats {
+        minor_faults: mem.PageFaultCount as u64,
+        ..Default::default()
+    }
+}
+
 #[derive(Debug)]
 pub struct WindowsCpuStats {
     creation: u64,
@@ -92,10 +104,7 @@ pub fn get_cpu_times(kind: &StatType) -> Result<WindowsCpuStats, SporkError> {
 
                 if result == 0 {
                     let error = unsafe { GetLastError() };
-                    return Err(SporkError::new(
-                        SporkErrorKind::Unknown,
-                        format!("Win32Error: {error:x}"),
-                    ));
+                    return Err(std::io::Error::from_raw_os_error(error as i32).into());
                 }
             };
 
@@ -131,10 +140,7 @@ pub fn get_cpu_times(kind: &StatType) -> Result<WindowsCpuStats, SporkError> {
 
                 if result == 0 {
                     let error = unsafe { GetLastError() };
-                    return Err(SporkError::new(
-                        SporkErrorKind::Unknown,
-                        format!("Win32Error: {error:x}"),
-                    ));
+                    return Err(std::io::Error::from_raw_os_error(error as i32).into());
                 }
             };
 
@@ -158,6 +164,12 @@ pub fn get_cpu_times(kind: &StatType) -> Result<WindowsCpuStats, SporkError> {
                 "Windows child thread memory stat not yet implemented!".to_owned(),
             ))
         }
+        StatType::Pid(_) => {
+            return Err(SporkError::new(
+                SporkErrorKind::Unimplemented,
+                "Windows PID cpu stat not yet implemented!".to_owned(),
+            ))
+        }
     }
 }
 
@@ -171,6 +183,27 @@ pub fn combine_cpu_times(val: &WindowsCpuStats) -> f64 {
     (times.sec as f64) + (times.usec as f64 / 1000000_f64)
 }
 
+/// The native backend on Windows, which reads from the process and thread time and memory counters.
+#[derive(Clone, Debug, Default)]
+pub struct WindowsBackend;
+
+impl StatsBackend for WindowsBackend {
+    fn stats(&self, kind: &StatType) -> Result<RawStats, SporkError> {
+        let cpu_times = get_cpu_times(kind)?;
+        let mem = get_mem_stats(kind)?;
+
+        Ok(RawStats {
+            cpu_time: combine_cpu_times(&cpu_times),
+            memory: mem.PeakWorkingSetSize as u64,
+            extended: get_extended_stats(&mem),
+        })
+    }
+
+    fn rss(&self) -> Result<u64, SporkError> {
+        Ok(get_mem_stats(&StatType::Process)?.WorkingSetSize as u64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
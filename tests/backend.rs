@@ -0,0 +1,357 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::backend::{FakeClock, MockBackend, RawStats, SystemCpuTimes};
+use spork::{CpuTimes, ExtendedStats, MemoryBreakdown, MemoryStats, ThreadStats, TreeProcess};
+
+use spork::topology::{CpuTopology, LogicalCpu};
+
+use std::time::Duration;
+
+#[test]
+fn should_calc_cpu_from_mock_backend() {
+    let backend = MockBackend::new();
+    let spork = match Spork::with_backend(backend.clone()) {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    backend.clock().advance(Duration::from_secs(2));
+    backend.set_cpu_time(&StatType::Process, 1.0);
+    let stats = spork.stats(StatType::Process).unwrap();
+    assert_eq!(stats.duration, Duration::from_secs(2));
+    assert_eq!(stats.cpu, Some(50.0));
+    assert_eq!(stats.uptime, 2000);
+
+    backend.clock().advance(Duration::from_secs(1));
+    backend.add_cpu_time(&StatType::Process, 0.25);
+    let stats = spork.stats(StatType::Process).unwrap();
+    assert_eq!(stats.duration, Duration::from_secs(1));
+    assert_eq!(stats.cpu_time, 1.25);
+    assert_eq!(stats.cpu, Some(25.0));
+}
+
+#[test]
+fn should_not_report_cpu_without_time_passing() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    backend.set_cpu_time(&StatType::Thread, 1.0);
+    let stats = spork.stats(StatType::Thread).unwrap();
+    assert_eq!(stats.duration, Duration::ZERO);
+    assert_eq!(stats.cpu, None);
+}
+
+#[test]
+fn should_read_scripted_memory_and_counters() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    backend.set_stats(
+        &StatType::Children,
+        RawStats {
+            cpu_time: 0.0,
+            memory: 4096,
+            extended: ExtendedStats {
+                minor_faults: 10,
+                ..Default::default()
+            },
+        },
+    );
+    let stats = spork.stats(StatType::Children).unwrap();
+    assert_eq!(stats.memory, 4096);
+    assert_eq!(stats.extended_delta.minor_faults, 10);
+
+    backend.set_extended(
+        &StatType::Children,
+        ExtendedStats {
+            minor_faults: 15,
+            ..Default::default()
+        },
+    );
+    let stats = spork.stats(StatType::Children).unwrap();
+    assert_eq!(stats.extended.minor_faults, 15);
+    assert_eq!(stats.extended_delta.minor_faults, 5);
+
+    backend.set_memory_stats(Ok(MemoryStats {
+        rss: 2048,
+        ..Default::default()
+    }));
+    assert_eq!(spork.memory_stats().unwrap().rss, 2048);
+}
+
+#[test]
+fn should_return_scripted_errors() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    backend.set_error(
+        &StatType::Pid(1),
+        SporkError::new_borrowed(SporkErrorKind::Unknown, "No such process."),
+    );
+    let error = spork.stats(StatType::Pid(1)).unwrap_err();
+    assert_eq!(error.details(), "No such process.");
+    assert_eq!(spork.read_history(StatType::Pid(1)), None);
+}
+
+#[test]
+fn should_reset_cpu_baseline_when_pid_is_reused() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+    let kind = StatType::Pid(42);
+
+    backend.set_cpu_time(&kind, 5.0);
+    spork.stats(kind.clone()).unwrap();
+
+    // pid 42 exits and is reused by a process that has used less CPU time
+    backend.clock().advance(Duration::from_secs(1));
+    backend.set_cpu_time(&kind, 0.5);
+    let stats = spork.stats(kind.clone()).unwrap();
+    assert_eq!(stats.duration, Duration::from_secs(1));
+    assert_eq!(stats.cpu, Some(50.0));
+
+    backend.clock().advance(Duration::from_secs(1));
+    backend.set_cpu_time(&kind, 1.0);
+    assert_eq!(spork.stats(kind).unwrap().cpu, Some(50.0));
+}
+
+#[test]
+fn should_use_mock_cores_and_clock() {
+    let clock = FakeClock::with_origin(1_000_000);
+    let backend = MockBackend::with_clock(clock.clone())
+        .with_cores(8)
+        .with_cpu_speed(2_000);
+    let spork = Spork::with_backend(backend).unwrap();
+
+    assert_eq!(spork.num_cores(), 8);
+    assert_eq!(spork.clock_speed(), 2_000);
+
+    clock.advance(Duration::from_millis(1500));
+    let stats = spork.stats_with_cpus(StatType::Process, None).unwrap();
+    assert_eq!(stats.cores, 8);
+    assert_eq!(stats.polled, 1_001_500);
+    assert!(spork.stats_with_cpus(StatType::Process, Some(9)).is_err());
+}
+
+#[test]
+fn should_snapshot_mock_threads() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let mut thread = ThreadStats::new_empty(42);
+    thread.name = "worker".into();
+    thread.user_time = 1.0;
+    backend.set_threads(Ok(vec![thread.clone()]));
+    backend.clock().advance(Duration::from_secs(4));
+
+    let threads = spork.thread_snapshot().unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].name, "worker");
+    assert_eq!(threads[0].cpu, Some(25.0));
+
+    thread.user_time = 1.5;
+    thread.system_time = 0.5;
+    backend.set_threads(Ok(vec![thread]));
+    backend.clock().advance(Duration::from_secs(1));
+
+    let threads = spork.thread_snapshot().unwrap();
+    assert_eq!(threads[0].duration, Duration::from_secs(1));
+    assert_eq!(threads[0].cpu, Some(100.0));
+}
+
+#[test]
+fn should_poll_mock_thread_handles() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let worker = spork.register_current_thread("worker").unwrap();
+    let other = spork.register_current_thread("other").unwrap();
+    assert_eq!(worker.name(), "worker");
+    assert_ne!(worker.tid(), other.tid());
+
+    backend.clock().advance(Duration::from_secs(2));
+    backend.set_thread_clock_stats(
+        worker.tid(),
+        Ok(RawStats {
+            cpu_time: 2.0,
+            ..Default::default()
+        }),
+    );
+    let stats = spork.stats_for_thread(&worker).unwrap();
+    assert_eq!(stats.kind, StatType::Thread);
+    assert_eq!(stats.cpu, Some(100.0));
+    assert_eq!(spork.stats_for_thread(&other).unwrap().cpu, Some(0.0));
+
+    backend.clock().advance(Duration::from_secs(1));
+    backend.set_thread_clock_stats(
+        worker.tid(),
+        Ok(RawStats {
+            cpu_time: 2.5,
+            ..Default::default()
+        }),
+    );
+    let stats = spork.stats_for_thread(&worker).unwrap();
+    assert_eq!(stats.duration, Duration::from_secs(1));
+    assert_eq!(stats.cpu, Some(50.0));
+    assert_eq!(spork.thread_window(&worker).len(), 1);
+
+    // thread handles don't share history with the calling thread
+    assert_eq!(spork.read_history(StatType::Thread), None);
+
+    backend.set_thread_clock_stats(
+        worker.tid(),
+        Err(SporkError::new(SporkErrorKind::ProcessNotFound, "Thread has exited.")),
+    );
+    assert!(spork.drop_thread_history(&worker).is_some());
+
+    // an exited thread's history is dropped as soon as it can't be read
+    spork.stats_for_thread(&other).unwrap();
+    backend.set_thread_clock_stats(
+        other.tid(),
+        Err(SporkError::new(SporkErrorKind::ProcessNotFound, "Thread has exited.")),
+    );
+    assert_eq!(spork.history_size().handles, 1);
+    assert_eq!(spork.prune_history(), 1);
+    assert_eq!(spork.history_size().handles, 0);
+
+    let err = spork.stats_for_thread(&worker).unwrap_err();
+    assert_eq!(err.kind(), &SporkErrorKind::ProcessNotFound);
+}
+
+#[test]
+fn should_build_tree_from_mock_processes() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let process = |pid, ppid| TreeProcess {
+        pid,
+        ppid,
+        name: "mock".into(),
+        depth: 0,
+        cpu_time: 1.0,
+        children_cpu_time: 0.0,
+        rss: 1024,
+    };
+    backend.set_processes(Ok(vec![process(1, 0), process(2, 1), process(3, 2), process(4, 1)]));
+
+    let tree = spork.process_tree(Some(2)).unwrap();
+    assert_eq!(tree.processes.len(), 2);
+    assert_eq!(tree.cpu_time, 2.0);
+    assert_eq!(tree.rss, 2048);
+
+    let err = spork.process_tree(Some(5)).unwrap_err();
+    assert_eq!(err.kind(), &SporkErrorKind::ProcessNotFound);
+}
+
+#[test]
+fn should_keep_tree_cpu_time_when_grandchild_is_reaped() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let process = |pid, ppid, cpu_time, children_cpu_time| TreeProcess {
+        pid,
+        ppid,
+        name: "mock".into(),
+        depth: 0,
+        cpu_time,
+        children_cpu_time,
+        rss: 1024,
+    };
+    // a shell running a compiler
+    backend.set_processes(Ok(vec![
+        process(1, 0, 1.0, 0.0),
+        process(2, 1, 0.5, 0.0),
+        process(3, 2, 4.0, 0.0),
+    ]));
+    let before = spork.process_tree(Some(1)).unwrap().cpu_time;
+    assert_eq!(before, 5.5);
+
+    // the shell reaps the compiler, moving its CPU time into the shell's children time
+    backend.set_processes(Ok(vec![process(1, 0, 1.0, 0.0), process(2, 1, 0.5, 4.0)]));
+    let after = spork.process_tree(Some(1)).unwrap().cpu_time;
+    assert!(after >= before);
+    assert_eq!(after, 5.5);
+}
+
+#[test]
+fn should_calc_system_cpu_from_mock_times() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let times = |core, user, idle| CpuTimes {
+        core,
+        user,
+        idle,
+        ..Default::default()
+    };
+    backend.set_system_cpu_times(Ok(SystemCpuTimes {
+        total: times(None, 100, 100),
+        cores: vec![times(Some(0), 50, 50), times(Some(1), 50, 50)],
+    }));
+    let system = spork.system_cpu_stats().unwrap();
+    assert_eq!(system.total.user, 50.0);
+
+    backend.clock().advance(Duration::from_secs(1));
+    backend.set_system_cpu_times(Ok(SystemCpuTimes {
+        total: times(None, 175, 125),
+        cores: vec![times(Some(0), 100, 50), times(Some(1), 75, 75)],
+    }));
+    let system = spork.system_cpu_stats().unwrap();
+    assert_eq!(system.duration, Duration::from_secs(1));
+    assert_eq!(system.total.busy(), 75.0);
+    assert_eq!(system.total.idle, 25.0);
+    assert_eq!(system.cores[0].user, 100.0);
+    assert_eq!(system.cores[1].user, 50.0);
+
+    // process stats are unaffected
+    assert_eq!(spork.read_history(StatType::Process), None);
+}
+
+#[test]
+fn should_read_scripted_memory_breakdown() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    assert_eq!(spork.memory_breakdown(StatType::Process).unwrap().pss, 0);
+    backend.set_memory_breakdown(
+        Some(42),
+        Ok(MemoryBreakdown {
+            rss: 8192,
+            pss: 2048,
+            ..Default::default()
+        }),
+    );
+    assert_eq!(spork.memory_breakdown(StatType::Pid(42)).unwrap().pss, 2048);
+    assert!(spork.memory_maps(StatType::Process).unwrap().is_empty());
+}
+
+#[test]
+fn should_read_scripted_cpu_topology() {
+    let backend = MockBackend::new().with_cores(4);
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let topology = spork.cpu_topology().unwrap();
+    assert_eq!(topology.logical_cpus(), 4);
+    assert_eq!(topology.physical_cores(), 4);
+
+    // two physical cores with two hardware threads each
+    backend.set_cpu_topology(Ok(CpuTopology {
+        cpus: (0..4)
+            .map(|id| LogicalCpu {
+                package: Some(0),
+                core: Some(id as u32 % 2),
+                siblings: vec![id % 2, id % 2 + 2],
+                ..LogicalCpu::new(id)
+            })
+            .collect(),
+    }));
+    let topology = spork.cpu_topology().unwrap();
+    assert_eq!(topology.packages(), 1);
+    assert_eq!(topology.physical_cores(), 2);
+    assert_eq!(topology.normalize_cpu(100.0), 50.0);
+
+    backend.set_cpu_topology(Err(SporkError::unimplemented()));
+    assert_eq!(spork.cpu_topology().unwrap_err().kind(), &SporkErrorKind::Unimplemented);
+}
@@ -0,0 +1,116 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::backend::MockBackend;
+use spork::{CgroupStats, CgroupVersion};
+
+use std::time::Duration;
+
+fn cgroup_with_cpu_limit(cpu_limit: Option<f64>) -> CgroupStats {
+    CgroupStats {
+        version: CgroupVersion::V2,
+        cpu_limit,
+        memory_limit: Some(512 * 1024 * 1024),
+        memory_usage: 64 * 1024 * 1024,
+        nr_periods: 100,
+        nr_throttled: 25,
+        throttled_usec: 500_000,
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_read_current_cgroup() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let cgroup = match spork.cgroup_stats() {
+        Ok(c) => c,
+        Err(e) => panic!("Error reading cgroup stats! {:?}", e),
+    };
+    println!("{:?}", cgroup);
+
+    assert_eq!(spork.cpu_limit(), cgroup.cpu_limit);
+    assert!(spork.effective_cores() >= 1);
+    assert!(spork.effective_cores() <= spork.num_cores());
+    assert!(cgroup.nr_throttled <= cgroup.nr_periods);
+    if let Some(limit) = cgroup.memory_limit {
+        assert!(limit > 0);
+    }
+}
+
+#[test]
+fn should_normalize_to_cgroup_quota() {
+    let backend = MockBackend::new().with_cores(8);
+    backend.set_cgroup_stats(Ok(cgroup_with_cpu_limit(Some(1.5))));
+    let spork = Spork::with_backend(backend).unwrap();
+
+    assert_eq!(spork.num_cores(), 8);
+    assert_eq!(spork.cpu_limit(), Some(1.5));
+    assert_eq!(spork.effective_cores(), 2);
+
+    assert_eq!(spork.cpu_capacity(), Some(1.5));
+
+    let stats = spork.stats_with_cpus(StatType::Process, None).unwrap();
+    assert_eq!(stats.cores, 2);
+    // explicit core counts are still validated against the host
+    let stats = spork.stats_with_cpus(StatType::Process, Some(8)).unwrap();
+    assert_eq!(stats.cores, 8);
+}
+
+#[test]
+fn should_normalize_cpu_to_fractional_quota() {
+    let backend = MockBackend::new().with_cores(8);
+    backend.set_cgroup_stats(Ok(cgroup_with_cpu_limit(Some(1.5))));
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    // 1.5 CPU-seconds over one second uses all of a 1.5 core quota
+    spork.stats_with_cpus(StatType::Process, None).unwrap();
+    backend.clock().advance(Duration::from_secs(1));
+    backend.add_cpu_time(&StatType::Process, 1.5);
+    let stats = spork.stats_with_cpus(StatType::Process, None).unwrap();
+    assert_eq!(stats.cpu, Some(100.0));
+    assert_eq!(stats.cores, 2);
+
+    // explicit core counts and `stats` stay relative to a single core
+    backend.clock().advance(Duration::from_secs(1));
+    backend.add_cpu_time(&StatType::Process, 1.5);
+    assert_eq!(
+        spork.stats_with_cpus(StatType::Process, Some(8)).unwrap().cpu,
+        Some(150.0)
+    );
+}
+
+#[test]
+fn should_clamp_effective_cores() {
+    let backend = MockBackend::new().with_cores(4);
+    backend.set_cgroup_stats(Ok(cgroup_with_cpu_limit(Some(16.0))));
+    let spork = Spork::with_backend(backend).unwrap();
+    assert_eq!(spork.effective_cores(), 4);
+    assert_eq!(spork.cpu_capacity(), Some(4.0));
+
+    let backend = MockBackend::new().with_cores(4);
+    backend.set_cgroup_stats(Ok(cgroup_with_cpu_limit(Some(0.1))));
+    let spork = Spork::with_backend(backend).unwrap();
+    assert_eq!(spork.effective_cores(), 1);
+    assert_eq!(spork.cpu_capacity(), Some(0.1));
+}
+
+#[test]
+fn should_use_host_cores_without_quota() {
+    let backend = MockBackend::new().with_cores(4);
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+    assert_eq!(spork.cpu_limit(), None);
+    assert_eq!(spork.effective_cores(), 4);
+    assert_eq!(spork.cpu_capacity(), None);
+    assert!(spork.cgroup_stats().is_err());
+
+    backend.set_cgroup_stats(Ok(cgroup_with_cpu_limit(None)));
+    let cgroup = spork.cgroup_stats().unwrap();
+    assert_eq!(cgroup.nr_throttled, 25);
+    assert_eq!(spork.stats_with_cpus(StatType::Process, None).unwrap().cores, 4);
+}
@@ -0,0 +1,74 @@
+#![cfg(target_os = "linux")]
+
+extern crate serde_json;
+
+use std::process::Command;
+
+fn spork() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_spork"))
+}
+
+#[test]
+fn should_stat_current_process() {
+    let pid = std::process::id().to_string();
+    let output = spork().args(["stat", &pid]).output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("PID:           {}", pid)));
+    assert!(stdout.contains("Peak memory:"));
+}
+
+#[test]
+fn should_watch_as_json_lines() {
+    let pid = std::process::id();
+    let output = spork()
+        .args([
+            "watch",
+            &pid.to_string(),
+            "--interval",
+            "50ms",
+            "--count",
+            "2",
+            "--json",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = stdout.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    for line in lines {
+        assert_eq!(line["pid"], pid);
+        assert!(line["memory_bytes"].as_u64().unwrap() > 0);
+        assert!(line["cpu_percent"].is_number());
+    }
+}
+
+#[test]
+fn should_run_command_and_forward_exit_code() {
+    let output = spork()
+        .args(["run", "--json", "--", "sh", "-c", "exit 3"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let summary: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(summary["exit_code"], 3);
+    assert!(summary["max_rss_bytes"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn should_fail_on_missing_process() {
+    let output = spork().args(["stat", "4000000000"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn should_print_usage_on_bad_args() {
+    let output = spork().args(["watch"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Usage:"));
+}
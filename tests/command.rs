@@ -0,0 +1,70 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::command;
+
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_report_command_exit_status() {
+    let stats = match command::run(Command::new("sh").args(["-c", "exit 3"])) {
+        Ok(s) => s,
+        Err(e) => panic!("Error running command! {:?}", e),
+    };
+
+    assert_eq!(stats.status.code(), Some(3));
+    assert!(stats.pid > 0);
+    assert!(stats.samples.is_empty());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_measure_command_cpu_time() {
+    let stats =
+        command::run(Command::new("sh").args(["-c", "i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done"])).unwrap();
+
+    assert!(stats.status.success());
+    assert!(stats.user_time + stats.system_time > 0_f64);
+    assert!(stats.max_rss > 0);
+    assert!(stats.wall_time > Duration::ZERO);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_sample_live_command() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let mut child = command::spawn(Command::new("sleep").arg("1")).unwrap();
+    child
+        .sample(Duration::from_millis(100), move |stats| {
+            assert!(matches!(stats.kind, StatType::Pid(_)));
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+    let stats = child.wait().unwrap();
+
+    assert!(stats.status.success());
+    assert!(stats.wall_time >= Duration::from_secs(1));
+    assert!(stats.samples.len() >= 5);
+    assert_eq!(stats.samples.len(), calls.load(Ordering::SeqCst));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_error_when_command_is_missing() {
+    assert!(command::run(&mut Command::new("spork-command-that-does-not-exist")).is_err());
+}
+
+#[test]
+#[cfg(not(target_os = "linux"))]
+fn should_not_measure_commands_on_other_platforms() {
+    let error = command::run(&mut Command::new("ls")).unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::Unimplemented);
+}
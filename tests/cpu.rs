@@ -1,8 +1,8 @@
 #[allow(unused_imports)]
-use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats, MIN_CPU_INTERVAL};
 
 use std::thread;
-use std::time;
+use std::time::{self, Duration, Instant};
 
 use chrono::Utc;
 use rand::Rng;
@@ -44,7 +44,7 @@ fn should_poll_no_cpu() {
         Err(e) => panic!("Error polling stats! {:?}", e),
     };
 
-    assert!(stats.cpu < 2_f64);
+    assert!(stats.cpu.unwrap() < 2_f64);
 }
 
 #[test]
@@ -60,7 +60,7 @@ fn should_poll_full_cpu() {
         Err(e) => panic!("Error polling stats! {:?}", e),
     };
 
-    assert!(stats.cpu > 95_f64);
+    assert!(stats.cpu.unwrap() > 95_f64);
 }
 
 #[test]
@@ -70,7 +70,7 @@ fn should_get_linux_process_stats_fib_25() {
     let wait = rand_in_range(100, 400);
     let expected_cpu = 1_f64;
 
-    let before = now_ms() as u64;
+    let before = Instant::now();
     let spork = Spork::new().unwrap();
 
     sleep_ms!(wait);
@@ -81,16 +81,17 @@ fn should_get_linux_process_stats_fib_25() {
         Ok(s) => s,
         Err(e) => panic!("Stats error {:?}", e),
     };
+    let elapsed = before.elapsed();
     let _final = now_ms() as u64;
 
     println!("{:?}", stats);
-    assert!(stats.cpu > expected_cpu);
-    assert!(stats.duration >= wait);
-    assert!(stats.duration <= _final - before);
+    assert!(stats.cpu.unwrap() > expected_cpu);
+    assert!(stats.duration >= Duration::from_millis(wait));
+    assert!(stats.duration <= elapsed);
     assert_eq!(stats.cores, 1);
     assert_eq!(stats.kind, StatType::Process);
     assert!(stats.uptime >= wait);
-    assert!(stats.uptime <= _final - before);
+    assert!(stats.uptime <= elapsed.as_millis() as u64);
     assert!(stats.polled <= _final as i64);
 }
 
@@ -99,7 +100,7 @@ fn should_get_linux_thread_stats_fib_35() {
     let wait = rand_in_range(100, 400);
     let expected_cpu = 10_f64;
 
-    let before = now_ms() as u64;
+    let before = Instant::now();
     let spork = Spork::new().unwrap();
 
     sleep_ms!(wait);
@@ -110,16 +111,17 @@ fn should_get_linux_thread_stats_fib_35() {
         Ok(s) => s,
         Err(e) => panic!("Stats error {:?}", e),
     };
+    let elapsed = before.elapsed();
     let _final = now_ms() as u64;
 
     println!("{:?}", stats);
-    assert!(stats.cpu > expected_cpu);
-    assert!(stats.duration >= wait);
-    assert!(stats.duration <= _final - before);
+    assert!(stats.cpu.unwrap() > expected_cpu);
+    assert!(stats.duration >= Duration::from_millis(wait));
+    assert!(stats.duration <= elapsed);
     assert_eq!(stats.cores, 1);
     assert_eq!(stats.kind, StatType::Thread);
     assert!(stats.uptime >= wait);
-    assert!(stats.uptime <= _final - before);
+    assert!(stats.uptime <= elapsed.as_millis() as u64);
     assert!(stats.polled <= _final as i64);
 }
 
@@ -128,7 +130,7 @@ fn should_get_low_cpu_linux_thread_stats() {
     let wait = rand_in_range(4000, 6000);
     let expected_cpu = 1.5_f64;
 
-    let before = now_ms() as u64;
+    let before = Instant::now();
     let spork = Spork::new().unwrap();
 
     sleep_ms!(wait);
@@ -137,16 +139,17 @@ fn should_get_low_cpu_linux_thread_stats() {
         Ok(s) => s,
         Err(e) => panic!("Stats error {:?}", e),
     };
+    let elapsed = before.elapsed();
     let _final = now_ms() as u64;
 
     println!("{:?}", stats);
-    assert!(stats.cpu < expected_cpu);
-    assert!(stats.duration >= wait);
-    assert!(stats.duration <= _final - before);
+    assert!(stats.cpu.unwrap() < expected_cpu);
+    assert!(stats.duration >= Duration::from_millis(wait));
+    assert!(stats.duration <= elapsed);
     assert_eq!(stats.cores, 1);
     assert_eq!(stats.kind, StatType::Thread);
     assert!(stats.uptime >= wait);
-    assert!(stats.uptime <= _final - before);
+    assert!(stats.uptime <= elapsed.as_millis() as u64);
     assert!(stats.polled <= _final as i64);
 }
 
@@ -155,7 +158,7 @@ fn should_get_linux_process_stats_with_cpus() {
     let wait = 1500;
     let expected_cpu = 5_f64;
 
-    let before = now_ms() as u64;
+    let before = Instant::now();
     let spork = Spork::new().unwrap();
 
     sleep_ms!(wait);
@@ -166,17 +169,18 @@ fn should_get_linux_process_stats_with_cpus() {
         Ok(s) => s,
         Err(e) => panic!("Stats error {:?}", e),
     };
+    let elapsed = before.elapsed();
     let _final = now_ms() as u64;
 
     println!("{:?}", stats);
-    assert!(stats.cpu > expected_cpu);
+    assert!(stats.cpu.unwrap() > expected_cpu);
     assert!(stats.memory > 0);
-    assert!(stats.duration >= wait);
-    assert!(stats.duration <= _final - before);
+    assert!(stats.duration >= Duration::from_millis(wait));
+    assert!(stats.duration <= elapsed);
     assert_eq!(stats.cores, spork.num_cores());
     assert_eq!(stats.kind, StatType::Process);
     assert!(stats.uptime >= wait);
-    assert!(stats.uptime <= _final - before);
+    assert!(stats.uptime <= elapsed.as_millis() as u64);
     assert!(stats.polled <= _final as i64);
 }
 
@@ -185,7 +189,7 @@ fn should_get_linux_thread_stats_with_cpus() {
     let wait = rand_in_range(100, 400);
     let expected_cpu = 5_f64;
 
-    let before = now_ms() as u64;
+    let before = Instant::now();
     let spork = Spork::new().unwrap();
 
     sleep_ms!(wait);
@@ -196,16 +200,17 @@ fn should_get_linux_thread_stats_with_cpus() {
         Ok(s) => s,
         Err(e) => panic!("Stats error {:?}", e),
     };
+    let elapsed = before.elapsed();
     let _final = now_ms() as u64;
 
     println!("{:?}", stats);
-    assert!(stats.cpu > expected_cpu);
-    assert!(stats.duration >= wait);
-    assert!(stats.duration <= _final - before);
+    assert!(stats.cpu.unwrap() > expected_cpu);
+    assert!(stats.duration >= Duration::from_millis(wait));
+    assert!(stats.duration <= elapsed);
     assert_eq!(stats.cores, spork.num_cores());
     assert_eq!(stats.kind, StatType::Thread);
     assert!(stats.uptime >= wait);
-    assert!(stats.uptime <= _final - before);
+    assert!(stats.uptime <= elapsed.as_millis() as u64);
     assert!(stats.polled <= _final as i64);
 }
 
@@ -257,6 +262,23 @@ fn should_always_have_increasing_cpus_times() {
     }
 }
 
+#[test]
+fn should_only_report_cpu_over_meaningful_intervals() {
+    let spork = Spork::new().unwrap();
+
+    for _x in 0..10 {
+        let stats = match spork.stats(StatType::Thread) {
+            Ok(s) => s,
+            Err(e) => panic!("Stats error {:?}", e),
+        };
+
+        assert_eq!(stats.cpu.is_some(), stats.duration >= MIN_CPU_INTERVAL);
+        if let Some(cpu) = stats.cpu {
+            assert!(cpu.is_finite());
+        }
+    }
+}
+
 #[test]
 fn should_poll_half_cpu() {}
 
@@ -0,0 +1,106 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::backend::MockBackend;
+use spork::IoStats;
+
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+use std::thread;
+use std::time::{self, Duration};
+
+macro_rules! sleep_ms(
+  ($($arg:tt)*) => { {
+    thread::sleep(time::Duration::from_millis($($arg)*))
+  } }
+);
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_count_process_writes() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let before = match spork.io_stats(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling io stats! {:?}", e),
+    };
+    assert_eq!(before.pid, None);
+
+    let path = std::env::temp_dir().join(format!("spork-io-{}", std::process::id()));
+    let mut file = fs::File::create(&path).unwrap();
+    for _ in 0..16 {
+        file.write_all(&[0_u8; 4096]).unwrap();
+    }
+    drop(file);
+    fs::remove_file(&path).unwrap();
+    sleep_ms!(10);
+
+    let after = spork.io_stats(StatType::Process).unwrap();
+    assert!(after.wchar >= before.wchar + 16 * 4096);
+    assert!(after.syscw >= before.syscw + 16);
+    assert!(after.duration >= Duration::from_millis(10));
+    assert!(after.rates.unwrap().wchar > 0.0);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_child_io_by_pid() {
+    let spork = Spork::new().unwrap();
+    let mut child = Command::new("sleep").arg("1").spawn().unwrap();
+
+    let io = spork.io_stats(StatType::Pid(child.id())).unwrap();
+    assert_eq!(io.pid, Some(child.id()));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn should_reject_thread_io_stats() {
+    let spork = Spork::with_backend(MockBackend::new()).unwrap();
+
+    for kind in [StatType::Thread, StatType::Children] {
+        let error = spork.io_stats(kind).unwrap_err();
+        assert_eq!(error.kind(), &SporkErrorKind::InvalidStatType);
+    }
+}
+
+#[test]
+fn should_calc_io_rates_from_history() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    let counters = |read_bytes, syscr| IoStats {
+        read_bytes,
+        syscr,
+        ..IoStats::new_empty(Some(7))
+    };
+
+    backend.clock().advance(Duration::from_secs(2));
+    backend.set_io_stats(Some(7), Ok(counters(4096, 10)));
+    let io = spork.io_stats(StatType::Pid(7)).unwrap();
+    assert_eq!(io.read_bytes, 4096);
+    assert_eq!(io.rates.unwrap().read_bytes, 2048.0);
+
+    backend.clock().advance(Duration::from_millis(500));
+    backend.set_io_stats(Some(7), Ok(counters(8192, 15)));
+    let io = spork.io_stats(StatType::Pid(7)).unwrap();
+    let rates = io.rates.unwrap();
+    assert_eq!(io.duration, Duration::from_millis(500));
+    assert_eq!(rates.read_bytes, 8192.0);
+    assert_eq!(rates.syscr, 10.0);
+
+    // the current process has its own history
+    let io = spork.io_stats(StatType::Process).unwrap();
+    assert_eq!(io.duration, Duration::from_millis(2500));
+    assert_eq!(io.rates.unwrap().read_bytes, 0.0);
+
+    let io = spork.io_stats(StatType::Process).unwrap();
+    assert!(io.rates.is_none());
+}
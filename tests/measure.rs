@@ -0,0 +1,100 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use std::cell::RefCell;
+use std::thread;
+use std::time;
+
+macro_rules! sleep_ms(
+  ($($arg:tt)*) => { {
+    thread::sleep(time::Duration::from_millis($($arg)*))
+  } }
+);
+
+fn fib(n: u64) -> u64 {
+    if n > 2 {
+        fib(n - 1) + fib(n - 2)
+    } else {
+        1
+    }
+}
+
+#[test]
+fn should_measure_closure() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let (result, stats) = match spork.measure(|| fib(30)) {
+        Ok(r) => r,
+        Err(e) => panic!("Error measuring closure! {:?}", e),
+    };
+
+    assert_eq!(result, 832040);
+    assert_eq!(stats.kind, StatType::Thread);
+    assert!(stats.cpu_time > 0_f64);
+    assert!(stats.duration > time::Duration::ZERO);
+    // cpu time can't exceed wall time for a single thread, allowing for clock resolution
+    assert!(stats.cpu_time <= stats.duration.as_secs_f64() + 0.01);
+}
+
+#[test]
+fn should_not_count_sleep_as_cpu_time() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let (_, stats) = spork.measure(|| sleep_ms!(200)).unwrap();
+    assert!(stats.duration >= time::Duration::from_millis(200));
+    assert!(stats.cpu.unwrap() < 50_f64);
+}
+
+#[test]
+fn should_not_disturb_thread_history() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let before = spork.stats(StatType::Thread).unwrap();
+    let _ = spork.measure(|| fib(25)).unwrap();
+    {
+        let _guard = spork.measure_guard().unwrap();
+        fib(25);
+    }
+
+    assert_eq!(spork.read_history(StatType::Thread), Some(before.clone()));
+    let after = spork.stats(StatType::Thread).unwrap();
+    assert_eq!(after.duration, after.monotonic - before.monotonic);
+}
+
+#[test]
+fn should_call_guard_callback_on_drop() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let measured = RefCell::new(None);
+    {
+        let _guard = spork
+            .measure_guard()
+            .unwrap()
+            .on_drop(|stats| *measured.borrow_mut() = Some(stats));
+        fib(25);
+        assert!(measured.borrow().is_none());
+    }
+
+    let stats = measured.into_inner().unwrap();
+    assert!(stats.cpu_time > 0_f64);
+
+    let called = RefCell::new(false);
+    let guard = spork.measure_guard().unwrap().on_drop(|_| *called.borrow_mut() = true);
+    let stats = guard.finish().unwrap();
+    assert!(!*called.borrow());
+    assert_eq!(stats.kind, StatType::Thread);
+}
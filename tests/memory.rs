@@ -79,6 +79,7 @@ fn should_poll_no_memory_change_thread() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)]
 #[cfg(target_os = "linux")]
 fn should_poll_increased_memory_thread() {
     let spork = match Spork::new() {
@@ -108,3 +109,61 @@ fn should_poll_increased_memory_thread() {
 
     assert!(start_memo < end_memo);
 }
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_current_memory_breakdown() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    // allocating here would raise the peak RSS seen by the other tests in this file, so only check the breakdown
+    let memory = match spork.memory_stats() {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling memory stats! {:?}", e),
+    };
+
+    let stats = match spork.stats(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+
+    assert!(memory.rss > 0);
+    assert!(memory.peak_rss > 0);
+    assert!(memory.virtual_size > memory.rss);
+    assert!(stats.memory >= memory.rss);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_read_proportional_memory_breakdown() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let memory = match spork.memory_breakdown(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling memory breakdown! {:?}", e),
+    };
+    assert!(memory.rss > 0);
+    assert!(memory.pss > 0 && memory.pss <= memory.rss);
+    assert!(memory.uss <= memory.pss);
+    assert_eq!(memory.uss, memory.private_clean + memory.private_dirty);
+    assert_eq!(memory.rss, memory.anonymous + memory.file_backed);
+
+    let maps = spork.memory_maps(StatType::Pid(std::process::id())).unwrap();
+    assert!(maps.iter().any(|m| m.path.as_deref() == Some("[stack]")));
+    let pss: u64 = maps.iter().map(|m| m.memory.pss).sum();
+    assert!(pss > 0);
+}
+
+#[test]
+fn should_reject_thread_memory_breakdown() {
+    let spork = Spork::new().unwrap();
+
+    let error = spork.memory_breakdown(StatType::Thread).unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::InvalidStatType);
+    assert!(spork.memory_maps(StatType::Children).is_err());
+}
@@ -0,0 +1,164 @@
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use std::process::Command;
+use std::thread;
+use std::time;
+
+macro_rules! sleep_ms(
+  ($($arg:tt)*) => { {
+    thread::sleep(time::Duration::from_millis($($arg)*))
+  } }
+);
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_child_process_by_pid() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+    let kind = StatType::Pid(child.id());
+
+    let first = match spork.stats(kind.clone()) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+    sleep_ms!(200);
+    let second = match spork.stats(kind.clone()) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert_eq!(second.kind, kind);
+    assert!(second.memory > 0);
+    assert!(second.cpu_time >= first.cpu_time);
+    assert!(second.cpu.unwrap() < 5_f64);
+    assert_eq!(spork.read_history(kind), Some(second));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_own_pid_like_process() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let process = match spork.stats(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+    let pid = match spork.stats(StatType::Pid(std::process::id())) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+
+    // /proc reports times in clock ticks, so only compare within a coarse margin
+    assert!(pid.cpu_time + 0.5 >= process.cpu_time);
+    assert!(pid.memory > 0);
+    assert!(pid.extended.minor_faults > 0);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_err_on_missing_pid() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    // pid_max can never exceed 2^22 on Linux
+    assert!(spork.stats(StatType::Pid(u32::MAX)).is_err());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_report_exited_pid_as_process_not_found() {
+    let spork = Spork::new().unwrap();
+
+    let mut child = Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    let _ = child.wait();
+
+    let error = spork.stats(StatType::Pid(pid)).unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::ProcessNotFound);
+    assert!(error.errno().is_some());
+    assert!(std::error::Error::source(&error).is_some());
+
+    let error = spork.io_stats(StatType::Pid(u32::MAX)).unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::ProcessNotFound);
+}
+
+#[test]
+fn should_reject_too_many_cpus_as_invalid_argument() {
+    let spork = Spork::new().unwrap();
+
+    let error = spork
+        .stats_with_cpus(StatType::Process, Some(spork.num_cores() + 1))
+        .unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::InvalidArgument);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_sum_live_process_tree() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    // a shell with two children of its own, none of which have been waited on
+    let mut child = Command::new("sh")
+        .args(["-c", "sleep 5 & sleep 5; wait"])
+        .spawn()
+        .unwrap();
+    sleep_ms!(200);
+
+    let tree = match spork.process_tree(None) {
+        Ok(t) => t,
+        Err(e) => panic!("Error reading process tree! {:?}", e),
+    };
+    let shell = match spork.process_tree(Some(child.id())) {
+        Ok(t) => t,
+        Err(e) => panic!("Error reading process tree! {:?}", e),
+    };
+
+    let _ = child.kill();
+    let _ = child.wait();
+    for sleeper in shell.descendants() {
+        let _ = Command::new("kill").arg(sleeper.pid.to_string()).status();
+    }
+
+    assert_eq!(tree.root, std::process::id());
+    assert_eq!(tree.processes[0].depth, 0);
+    assert!(tree.rss > tree.processes[0].rss);
+    let found = tree.descendants().find(|p| p.pid == child.id()).unwrap();
+    assert_eq!(found.depth, 1);
+    assert_eq!(tree.descendants().filter(|p| p.ppid == child.id()).count(), 2);
+    assert!(tree
+        .descendants()
+        .filter(|p| p.ppid == child.id())
+        .all(|p| p.depth == 2 && p.name == "sleep"));
+
+    assert_eq!(shell.root, child.id());
+    assert_eq!(shell.processes.len(), 3);
+    assert!(tree.cpu_time >= shell.cpu_time);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_err_on_missing_process_tree() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let err = spork.process_tree(Some(4_000_000_000)).unwrap_err();
+    assert_eq!(err.kind(), &SporkErrorKind::ProcessNotFound);
+}
@@ -0,0 +1,107 @@
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::prometheus::{Metrics, MetricsServer};
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn new_spork() -> Arc<Spork> {
+    match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    }
+}
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn should_render_polled_stats() {
+    let spork = new_spork();
+    let stats = match spork.stats(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling stats! {:?}", e),
+    };
+
+    let mut metrics = Metrics::new();
+    metrics.add_stats(&stats);
+    let out = metrics.render();
+
+    assert!(out.contains("# TYPE process_cpu_seconds_total counter\n"));
+    assert!(out.contains(&format!(
+        "process_cpu_seconds_total{{kind=\"process\"}} {}\n",
+        stats.cpu_time
+    )));
+}
+
+#[test]
+fn should_serve_process_metrics() {
+    let server = MetricsServer::start(0, new_spork()).unwrap();
+
+    let response = get(server.local_addr(), "/metrics");
+    assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("process_cpu_seconds_total{kind=\"process\"}"));
+
+    #[cfg(target_os = "linux")]
+    {
+        assert!(response.contains("process_resident_memory_bytes{kind=\"process\"}"));
+        assert!(response.contains("thread=\"spork-metrics\""));
+    }
+}
+
+#[test]
+fn should_serve_custom_metrics() {
+    let server = MetricsServer::bind("127.0.0.1:0", || "foo 1\n".to_owned()).unwrap();
+
+    let response = get(server.local_addr(), "/metrics");
+    assert!(response.ends_with("\r\n\r\nfoo 1\n"));
+
+    let response = get(server.local_addr(), "/");
+    assert!(response.starts_with("HTTP/1.0 404 Not Found\r\n"));
+}
+
+#[test]
+fn should_stop_metrics_server() {
+    let mut server = MetricsServer::bind("127.0.0.1:0", String::new).unwrap();
+    let addr = server.local_addr();
+    server.stop();
+
+    assert!(TcpStream::connect(addr).is_err());
+}
+
+#[test]
+fn should_not_block_on_slow_clients() {
+    let mut server = MetricsServer::bind("127.0.0.1:0", || "foo 1\n".to_owned()).unwrap();
+    let addr = server.local_addr();
+
+    // connect without sending a request
+    let _slow = TcpStream::connect(addr).unwrap();
+    let start = Instant::now();
+    let response = get(addr, "/metrics");
+    assert!(response.ends_with("\r\n\r\nfoo 1\n"));
+
+    server.stop();
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn should_not_change_history_when_scraped() {
+    let spork = new_spork();
+    let polled = spork.stats(StatType::Process).unwrap();
+    let server = MetricsServer::start(0, spork.clone()).unwrap();
+
+    let response = get(server.local_addr(), "/metrics");
+    assert!(response.contains("process_cpu_seconds_total{kind=\"process\"}"));
+    assert!(!response.contains("spork_resident_memory_peak_bytes"));
+    assert_eq!(spork.read_history(StatType::Process), Some(polled));
+}
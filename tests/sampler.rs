@@ -0,0 +1,115 @@
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::sampler::Sampler;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{self, Duration};
+
+macro_rules! sleep_ms(
+  ($($arg:tt)*) => { {
+    thread::sleep(time::Duration::from_millis($($arg)*))
+  } }
+);
+
+fn new_spork() -> Arc<Spork> {
+    match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    }
+}
+
+#[test]
+fn should_deliver_samples_to_channels() {
+    let mut sampler = Sampler::new(new_spork(), Duration::from_millis(20)).with_kind(StatType::Process);
+    let rx = sampler.channel();
+
+    sampler.start().unwrap();
+    assert!(sampler.is_running());
+
+    for _ in 0..3 {
+        let sample = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(sample.errors.is_empty());
+        assert_eq!(sample.stats.len(), 1);
+        assert_eq!(sample.stats[0].kind, StatType::Process);
+    }
+}
+
+#[test]
+fn should_deliver_samples_to_callbacks() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut sampler = Sampler::new(new_spork(), Duration::from_millis(20)).with_kind(StatType::Process);
+
+    let callback_count = count.clone();
+    sampler.subscribe(move |_| {
+        callback_count.fetch_add(1, Ordering::SeqCst);
+    });
+
+    sampler.start().unwrap();
+    sleep_ms!(200);
+    sampler.stop();
+
+    let stopped = count.load(Ordering::SeqCst);
+    assert!(stopped >= 2);
+    assert!(!sampler.is_running());
+
+    sleep_ms!(100);
+    assert_eq!(count.load(Ordering::SeqCst), stopped);
+}
+
+#[test]
+fn should_pause_and_resume_sampler() {
+    let mut sampler = Sampler::new(new_spork(), Duration::from_millis(20)).with_kind(StatType::Process);
+    let rx = sampler.channel();
+
+    sampler.start().unwrap();
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    sampler.pause();
+    assert!(sampler.is_paused());
+    // let any in flight sample land before draining
+    sleep_ms!(50);
+    while rx.try_recv().is_ok() {}
+    sleep_ms!(100);
+    assert!(rx.try_recv().is_err());
+
+    sampler.resume();
+    assert!(sampler.is_running());
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn should_stop_sampler_on_drop() {
+    let sampler_spork = new_spork();
+    let rx = {
+        let mut sampler = Sampler::new(sampler_spork.clone(), Duration::from_millis(20)).with_kind(StatType::Process);
+        let rx = sampler.channel();
+        sampler.start().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        rx
+    };
+
+    // the sender is dropped along with the sampler thread
+    while rx.try_recv().is_ok() {}
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(Arc::strong_count(&sampler_spork), 1);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_sample_memory_and_threads() {
+    let mut sampler = Sampler::new(new_spork(), Duration::from_millis(20))
+        .with_memory(true)
+        .with_threads(true);
+    let rx = sampler.channel();
+
+    sampler.start().unwrap();
+    let sample = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    assert!(sample.errors.is_empty());
+    assert!(sample.stats.is_empty());
+    assert!(sample.memory.unwrap().rss > 0);
+    assert!(sample.threads.unwrap().iter().any(|t| t.name == "spork-sampler"));
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use serde_json::{json, Value};
+
+#[test]
+fn should_serialize_stats_with_units() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let stats = match spork.stats(StatType::Process) {
+        Ok(s) => s,
+        Err(e) => panic!("Stats error {:?}", e),
+    };
+
+    let value = serde_json::to_value(&stats).unwrap();
+    for field in [
+        "polled_ms",
+        "monotonic_ns",
+        "duration_ns",
+        "cpu_time_secs",
+        "cpu_percent",
+        "memory_bytes",
+        "uptime_ms",
+        "kind",
+        "cores",
+        "extended",
+        "extended_delta",
+    ] {
+        assert!(value.get(field).is_some(), "missing field {}", field);
+    }
+    assert_eq!(value["kind"], json!("process"));
+    assert_eq!(value["monotonic_ns"], json!(stats.monotonic.as_nanos() as u64));
+    assert_eq!(value["memory_bytes"], json!(stats.memory));
+
+    let parsed: Stats = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed, stats);
+}
+
+#[test]
+fn should_serialize_enums() {
+    assert_eq!(serde_json::to_value(StatType::Thread).unwrap(), json!("thread"));
+    assert_eq!(serde_json::to_value(StatType::Pid(42)).unwrap(), json!({ "pid": 42 }));
+    assert_eq!(serde_json::to_value(Platform::MacOS).unwrap(), json!("macos"));
+    assert_eq!(
+        serde_json::to_value(SporkErrorKind::InvalidStatType).unwrap(),
+        json!("invalid_stat_type")
+    );
+
+    let kind: StatType = serde_json::from_value(json!({ "pid": 7 })).unwrap();
+    assert_eq!(kind, StatType::Pid(7));
+}
+
+#[test]
+fn should_serialize_errors() {
+    let error = SporkError::new(SporkErrorKind::Unimplemented, "Not on this platform.");
+    let value = serde_json::to_value(&error).unwrap();
+
+    assert_eq!(
+        value,
+        json!({
+            "kind": "unimplemented",
+            "description": "Unimplemented",
+            "details": "Not on this platform.",
+        })
+    );
+
+    let parsed: SporkError = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed, error);
+
+    let parsed: SporkError = serde_json::from_str(r#"{"kind":"unknown","details":"foo"}"#).unwrap();
+    assert_eq!(parsed.kind(), &SporkErrorKind::Unknown);
+    assert_eq!(parsed.to_string(), "Unknown Error: foo");
+}
+
+#[test]
+fn should_ignore_null_cpu_load() {
+    let stats = Stats::new_empty(StatType::Children);
+    let value = serde_json::to_value(&stats).unwrap();
+
+    assert_eq!(value["cpu_percent"], Value::Null);
+    assert_eq!(serde_json::from_value::<Stats>(value).unwrap(), stats);
+}
@@ -0,0 +1,47 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use std::thread;
+use std::time;
+
+macro_rules! sleep_ms(
+  ($($arg:tt)*) => { {
+    thread::sleep(time::Duration::from_millis($($arg)*))
+  } }
+);
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_system_cpu_stats() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let first = match spork.system_cpu_stats() {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling system cpu stats! {:?}", e),
+    };
+    assert_eq!(first.total.core, None);
+    assert!(!first.cores.is_empty());
+
+    sleep_ms!(250);
+    let second = spork.system_cpu_stats().unwrap();
+    assert!(second.duration >= time::Duration::from_millis(250));
+
+    for usage in second.cores.iter().chain(Some(&second.total)) {
+        let sum = usage.busy() + usage.idle + usage.iowait;
+        // a core with no ticks over the interval reports 0 for every state
+        assert!(sum == 0.0 || (sum - 100.0).abs() < 0.001, "invalid usage {:?}", usage);
+    }
+}
+
+#[test]
+#[cfg(not(target_os = "linux"))]
+fn should_err_on_system_cpu_stats() {
+    let spork = Spork::new().unwrap();
+    let error = spork.system_cpu_stats().unwrap_err();
+    assert_eq!(error.kind(), &SporkErrorKind::Unimplemented);
+}
@@ -7,6 +7,7 @@ use std::time;
 
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
 use std::thread;
 
 macro_rules! sleep_ms(
@@ -44,7 +45,7 @@ fn should_correctly_poll_2_threads_separately() {
             Ok(s) => s,
             Err(e) => panic!("Error polling stats! {:?}", e),
         };
-        tx_expensive.send(stats.cpu).unwrap();
+        tx_expensive.send(stats.cpu.unwrap()).unwrap();
     });
 
     let (tx_simple, rx_simple): (Sender<f64>, Receiver<f64>) = mpsc::channel();
@@ -62,7 +63,7 @@ fn should_correctly_poll_2_threads_separately() {
             Err(e) => panic!("Error polling stats! {:?}", e),
         };
 
-        tx_simple.send(stats.cpu).unwrap();
+        tx_simple.send(stats.cpu.unwrap()).unwrap();
     });
 
     // Store values
@@ -72,6 +73,7 @@ fn should_correctly_poll_2_threads_separately() {
 }
 
 #[test]
+#[allow(clippy::assertions_on_constants)]
 fn should_correctly_poll_10_threads_separately() {
     let mut thread_handles = vec![];
 
@@ -91,7 +93,7 @@ fn should_correctly_poll_10_threads_separately() {
                     Ok(s) => s,
                     Err(e) => panic!("Error polling stats! {:?}", e),
                 };
-                tx_expensive.send(stats.cpu).unwrap();
+                tx_expensive.send(stats.cpu.unwrap()).unwrap();
             });
 
             let (tx_simple, rx_simple): (Sender<f64>, Receiver<f64>) = mpsc::channel();
@@ -109,7 +111,7 @@ fn should_correctly_poll_10_threads_separately() {
                     Err(e) => panic!("Error polling stats! {:?}", e),
                 };
 
-                tx_simple.send(stats.cpu).unwrap();
+                tx_simple.send(stats.cpu.unwrap()).unwrap();
             });
 
             // Store values
@@ -125,3 +127,243 @@ fn should_correctly_poll_10_threads_separately() {
     }
     assert!(true);
 }
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_count_thread_context_switches_between_polls() {
+    let handle = thread::spawn(move || {
+        let spork = match Spork::new() {
+            Ok(s) => s,
+            Err(e) => panic!("Error creating spork! {:?}", e),
+        };
+
+        let first = match spork.stats(StatType::Thread) {
+            Ok(s) => s,
+            Err(e) => panic!("Error polling stats! {:?}", e),
+        };
+
+        // every sleep should yield the CPU at least once
+        for _ in 0..5 {
+            sleep_ms!(10);
+        }
+
+        let second = match spork.stats(StatType::Thread) {
+            Ok(s) => s,
+            Err(e) => panic!("Error polling stats! {:?}", e),
+        };
+
+        assert!(second.extended_delta.voluntary_ctx_switches >= 5);
+        assert_eq!(
+            second.extended.voluntary_ctx_switches,
+            first.extended.voluntary_ctx_switches + second.extended_delta.voluntary_ctx_switches
+        );
+    });
+
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_snapshot_all_threads() {
+    let spork = match Spork::new() {
+        Ok(s) => s,
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+    let worker = thread::Builder::new()
+        .name("spork-worker".into())
+        .spawn(move || {
+            fib(30);
+            let _ = rx.recv();
+        })
+        .unwrap();
+
+    sleep_ms!(200);
+    let first = match spork.thread_snapshot() {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling thread snapshot! {:?}", e),
+    };
+    sleep_ms!(100);
+    let second = match spork.thread_snapshot() {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling thread snapshot! {:?}", e),
+    };
+
+    tx.send(()).unwrap();
+    worker.join().unwrap();
+
+    assert!(first.len() >= 2);
+    let first_worker = first.iter().find(|t| t.name == "spork-worker").unwrap();
+    let second_worker = second.iter().find(|t| t.tid == first_worker.tid).unwrap();
+
+    assert_eq!(second_worker.name, "spork-worker");
+    assert!(second_worker.user_time >= first_worker.user_time);
+    assert!(second_worker.duration < first_worker.duration);
+    // the worker is blocked on the channel between the two snapshots
+    assert!(second_worker.cpu.unwrap() < 5_f64);
+}
+
+#[test]
+fn should_share_spork_across_threads() {
+    let spork = match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let spork = spork.clone();
+            thread::spawn(move || {
+                if i % 2 == 0 {
+                    fib(30);
+                }
+
+                let stats = match spork.stats(StatType::Thread) {
+                    Ok(s) => s,
+                    Err(e) => panic!("Error polling stats! {:?}", e),
+                };
+                // each thread should only ever see its own history
+                assert_eq!(spork.read_history(StatType::Thread), Some(stats.clone()));
+                stats.cpu_time
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(spork.read_history(StatType::Thread), None);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_poll_registered_thread_from_another_thread() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let spork = match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let spinning = Arc::new(AtomicBool::new(true));
+    let (tx, rx) = mpsc::channel();
+    let worker = {
+        let spork = spork.clone();
+        let spinning = spinning.clone();
+        thread::spawn(move || {
+            tx.send(spork.register_current_thread("spinner").unwrap()).unwrap();
+            while spinning.load(Ordering::Relaxed) {
+                fib(20);
+            }
+        })
+    };
+
+    let handle = rx.recv().unwrap();
+    assert_eq!(handle.name(), "spinner");
+    spork.stats_for_thread(&handle).unwrap();
+    sleep_ms!(300);
+
+    let stats = match spork.stats_for_thread(&handle) {
+        Ok(s) => s,
+        Err(e) => panic!("Error polling registered thread! {:?}", e),
+    };
+    // the main thread is asleep, so this can only be the worker's CPU time
+    assert!(stats.cpu.unwrap() > 50_f64);
+    assert!(stats.memory > 0);
+
+    spinning.store(false, Ordering::Relaxed);
+    worker.join().unwrap();
+
+    let err = spork.stats_for_thread(&handle).unwrap_err();
+    assert_eq!(err.kind(), &SporkErrorKind::ProcessNotFound);
+}
+
+#[test]
+fn should_report_lifetime_stats_of_spawned_thread() {
+    let handle = match spork::thread::spawn("spork-task", || {
+        sleep_ms!(50);
+        fib(30)
+    }) {
+        Ok(h) => h,
+        Err(e) => panic!("Error spawning thread! {:?}", e),
+    };
+    assert_eq!(handle.thread().name(), Some("spork-task"));
+
+    let (result, stats) = handle.join().unwrap();
+    assert_eq!(result, 832040);
+    assert_eq!(stats.kind, StatType::Thread);
+    assert!(stats.cpu_time > 0_f64);
+    assert!(stats.duration >= time::Duration::from_millis(50));
+    // cpu time can't exceed wall time for a single thread, allowing for clock resolution
+    assert!(stats.cpu_time <= stats.duration.as_secs_f64() + 0.01);
+    assert!(stats.cpu.is_some());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_count_spawned_thread_context_switches() {
+    let (_, stats) = spork::thread::spawn("spork-sleeper", || {
+        for _ in 0..5 {
+            sleep_ms!(5);
+        }
+    })
+    .unwrap()
+    .join()
+    .unwrap();
+
+    // each sleep blocks the thread at least once
+    assert!(stats.extended.voluntary_ctx_switches >= 5);
+    assert_eq!(stats.extended, stats.extended_delta);
+}
+
+#[test]
+fn should_not_keep_history_of_exited_threads() {
+    let spork = match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    for _ in 0..20 {
+        let spork = spork.clone();
+        thread::spawn(move || {
+            spork.stats(StatType::Thread).unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+    // each new thread prunes the ones that exited before it
+    assert!(spork.history_size().thread <= 1);
+
+    spork.prune_history();
+    assert_eq!(spork.history_size().thread, 0);
+
+    spork.stats(StatType::Thread).unwrap();
+    assert_eq!(spork.prune_history(), 0);
+    assert_eq!(spork.history_size().thread, 1);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn should_prune_history_of_exited_registered_threads() {
+    let spork = match Spork::new() {
+        Ok(s) => Arc::new(s),
+        Err(e) => panic!("Error creating spork! {:?}", e),
+    };
+
+    let handle = {
+        let spork = spork.clone();
+        thread::spawn(move || {
+            let handle = spork.register_current_thread("short-lived").unwrap();
+            spork.stats_for_thread(&handle).unwrap();
+            handle
+        })
+        .join()
+        .unwrap()
+    };
+    assert_eq!(spork.history_size().handles, 1);
+
+    assert_eq!(spork.prune_history(), 1);
+    assert_eq!(spork.history_size().handles, 0);
+    assert!(spork.thread_window(&handle).is_empty());
+}
@@ -0,0 +1,136 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::backend::MockBackend;
+use spork::watchdog::{AlertState, Rule, Source, Watchdog};
+use spork::{MemoryStats, ThreadStats};
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+fn tick(backend: &MockBackend, cpu_time: f64) {
+    backend.clock().advance(Duration::from_secs(10));
+    backend.add_cpu_time(&StatType::Process, cpu_time);
+}
+
+#[test]
+fn should_alert_on_sustained_process_cpu() {
+    let backend = MockBackend::new();
+    let spork = Arc::new(Spork::with_backend(backend.clone()).unwrap());
+    let triggered = Arc::new(AtomicUsize::new(0));
+    let recovered = Arc::new(AtomicUsize::new(0));
+    let (t, r) = (triggered.clone(), recovered.clone());
+
+    let watchdog = Watchdog::new(spork, Duration::from_secs(10)).with_rule(
+        Rule::new("high-cpu", Source::Cpu(StatType::Process))
+            .above(90.0)
+            .for_duration(Duration::from_secs(30))
+            .recover_at(80.0)
+            .on_trigger(move |_| {
+                t.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_recover(move |_| {
+                r.fetch_add(1, Ordering::SeqCst);
+            }),
+    );
+
+    // 95% CPU on each 10s check
+    for _ in 0..3 {
+        tick(&backend, 9.5);
+        assert!(watchdog.check().is_empty());
+    }
+    tick(&backend, 9.5);
+    let alerts = watchdog.check();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].rule, "high-cpu");
+    assert_eq!(alerts[0].state, AlertState::Triggered);
+    assert_eq!(alerts[0].duration, Duration::from_secs(30));
+    assert_eq!(watchdog.triggered(), vec!["high-cpu".to_owned()]);
+
+    // 85% is inside the hysteresis band
+    tick(&backend, 8.5);
+    assert!(watchdog.check().is_empty());
+    tick(&backend, 5.0);
+    assert_eq!(watchdog.check()[0].state, AlertState::Recovered);
+
+    assert_eq!(triggered.load(Ordering::SeqCst), 1);
+    assert_eq!(recovered.load(Ordering::SeqCst), 1);
+    assert!(watchdog.triggered().is_empty());
+}
+
+#[test]
+fn should_alert_on_rss() {
+    let backend = MockBackend::new();
+    let spork = Arc::new(Spork::with_backend(backend.clone()).unwrap());
+    let watchdog =
+        Watchdog::new(spork, Duration::from_secs(1)).with_rule(Rule::new("high-rss", Source::Rss).above(2048.0));
+
+    let rss = |rss| {
+        Ok(MemoryStats {
+            rss,
+            ..Default::default()
+        })
+    };
+    backend.set_memory_stats(rss(1024));
+    assert!(watchdog.check().is_empty());
+    backend.set_memory_stats(rss(4096));
+    assert_eq!(watchdog.check()[0].value, Some(4096.0));
+}
+
+#[test]
+fn should_only_check_busy_threads() {
+    let backend = MockBackend::new();
+    let spork = Arc::new(Spork::with_backend(backend.clone()).unwrap());
+    let busy = Arc::new(AtomicBool::new(false));
+    let watchdog = Watchdog::new(spork, Duration::from_secs(1)).with_rule(
+        Rule::new("stuck-worker", Source::ThreadCpu("worker".into()))
+            .at_most(0.0)
+            .for_duration(Duration::from_secs(60))
+            .when(busy.clone()),
+    );
+
+    let mut worker = ThreadStats::new_empty(7);
+    worker.name = "worker".into();
+    backend.set_threads(Ok(vec![worker]));
+
+    for _ in 0..10 {
+        backend.clock().advance(Duration::from_secs(30));
+        assert!(watchdog.check().is_empty());
+    }
+
+    busy.store(true, Ordering::SeqCst);
+    let mut alerts = Vec::new();
+    for _ in 0..3 {
+        backend.clock().advance(Duration::from_secs(30));
+        alerts.extend(watchdog.check());
+    }
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].state, AlertState::Triggered);
+
+    busy.store(false, Ordering::SeqCst);
+    let alerts = watchdog.check();
+    assert_eq!(alerts[0].state, AlertState::Recovered);
+    assert_eq!(alerts[0].value, None);
+}
+
+#[test]
+fn should_check_rules_from_watchdog_thread() {
+    let spork = Arc::new(Spork::new().unwrap());
+    let (tx, rx) = mpsc::channel();
+    let mut watchdog = Watchdog::new(spork, Duration::from_millis(10))
+        .with_rule(Rule::new("any-memory", Source::Memory(StatType::Process)).above(0.0));
+    watchdog.subscribe(move |alert| {
+        let _ = tx.send(alert.clone());
+    });
+
+    watchdog.start().unwrap();
+    assert!(watchdog.is_running());
+    let alert = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(alert.rule, "any-memory");
+
+    watchdog.stop();
+    assert!(!watchdog.is_running());
+}
@@ -0,0 +1,68 @@
+extern crate spork;
+
+#[allow(unused_imports)]
+use spork::{Platform, Spork, SporkError, SporkErrorKind, StatType, Stats};
+
+use spork::backend::MockBackend;
+use spork::window::{Metric, WindowConfig};
+
+use std::time::Duration;
+
+#[test]
+fn should_summarize_rolling_window() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone())
+        .unwrap()
+        .with_window(WindowConfig::new(5));
+
+    // 10%, 20%, ... 70% over 1 second intervals, keeping only the last 5
+    for i in 1..=7 {
+        backend.clock().advance(Duration::from_secs(1));
+        backend.add_cpu_time(&StatType::Process, i as f64 / 10_f64);
+        backend.set_memory(&StatType::Process, i * 1024);
+        spork.stats(StatType::Process).unwrap();
+    }
+
+    let window = spork.window(StatType::Process);
+    assert_eq!(window.len(), 5);
+    assert_eq!(window.span(), Duration::from_secs(4));
+    assert_eq!(window.min(Metric::Cpu).map(f64::round), Some(30.0));
+    assert_eq!(window.max(Metric::Cpu).map(f64::round), Some(70.0));
+    assert_eq!(window.mean(Metric::Memory), Some(5.0 * 1024.0));
+    assert_eq!(window.p50(Metric::Memory), Some(5.0 * 1024.0));
+    assert_eq!(window.p99(Metric::Memory), Some(7.0 * 1024.0));
+
+    let summary = window.summary(Metric::Cpu).unwrap();
+    assert_eq!(summary.count, 5);
+    assert!(summary.ewma > summary.mean);
+}
+
+#[test]
+fn should_expire_old_polls_from_window() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone())
+        .unwrap()
+        .with_window(WindowConfig::new(100).with_max_age(Duration::from_secs(10)));
+
+    for _ in 0..30 {
+        backend.clock().advance(Duration::from_secs(1));
+        spork.stats(StatType::Process).unwrap();
+    }
+
+    let window = spork.window(StatType::Process);
+    assert_eq!(window.len(), 11);
+    assert_eq!(window.span(), Duration::from_secs(10));
+}
+
+#[test]
+fn should_only_keep_last_poll_by_default() {
+    let backend = MockBackend::new();
+    let spork = Spork::with_backend(backend.clone()).unwrap();
+
+    for _ in 0..3 {
+        backend.clock().advance(Duration::from_secs(1));
+        spork.stats(StatType::Thread).unwrap();
+    }
+    assert_eq!(spork.window(StatType::Thread).len(), 1);
+    assert!(spork.window(StatType::Children).is_empty());
+}